@@ -0,0 +1,99 @@
+#ifndef EMERALD_H
+#define EMERALD_H
+
+/* generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#define EMERALD_OK 0
+
+#define EMERALD_ERROR -1
+
+// the machine is paused, stopped on something it can't emulate or exited, emerald_last_error says which
+#define EMERALD_HALTED 1
+
+#define EMERALD_BUTTON_A 0
+
+#define EMERALD_BUTTON_B 1
+
+#define EMERALD_BUTTON_X 2
+
+#define EMERALD_BUTTON_Y 3
+
+#define EMERALD_BUTTON_START 4
+
+#define EMERALD_BUTTON_LEFT 5
+
+#define EMERALD_BUTTON_RIGHT 6
+
+#define EMERALD_BUTTON_UP 7
+
+#define EMERALD_BUTTON_DOWN 8
+
+typedef struct EmeraldHandle EmeraldHandle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// a machine with no bios loaded. load one with emerald_load_bios(_buffer) before running
+struct EmeraldHandle *emerald_create(void);
+
+void emerald_destroy(struct EmeraldHandle *handle);
+
+// the message for the last EMERALD_ERROR, valid until the next call on this handle
+const char *emerald_last_error(const struct EmeraldHandle *handle);
+
+// checksums are verified against known dumps unless verify is false
+void emerald_set_verify_checksums(struct EmeraldHandle *handle, bool verify);
+
+int32_t emerald_load_bios(struct EmeraldHandle *handle,
+                          const char *bios_path,
+                          const char *flash_path);
+
+int32_t emerald_load_bios_buffer(struct EmeraldHandle *handle,
+                                 const uint8_t *bios,
+                                 size_t bios_len,
+                                 const uint8_t *flash,
+                                 size_t flash_len);
+
+// a .gdi sheet, its tracks are read relative to it
+int32_t emerald_load_disc(struct EmeraldHandle *handle, const char *gdi_path);
+
+// the contents of a .gdi sheet plus one buffer per track, in the order the sheet lists them.
+// the buffers are copied, the caller keeps ownership
+int32_t emerald_load_disc_buffer(struct EmeraldHandle *handle,
+                                 const char *gdi,
+                                 const uint8_t *const *tracks,
+                                 const size_t *track_lens,
+                                 size_t track_count);
+
+int32_t emerald_load_elf(struct EmeraldHandle *handle, const char *elf_path);
+
+int32_t emerald_load_elf_buffer(struct EmeraldHandle *handle, const uint8_t *elf, size_t elf_len);
+
+// runs until the next vblank
+int32_t emerald_run_frame(struct EmeraldHandle *handle);
+
+// the last frame scanned out of the framebuffer as rgba8888, or null if there hasn't been one yet.
+// valid until the next emerald_run_frame or emerald_destroy
+const uint8_t *emerald_get_framebuffer(const struct EmeraldHandle *handle,
+                                       uint32_t *width,
+                                       uint32_t *height);
+
+// button is one of the EMERALD_BUTTON_* values
+int32_t emerald_set_button(struct EmeraldHandle *handle, uint32_t button, bool pressed);
+
+// copies up to max_frames interleaved stereo s16 frames at 44100hz into samples and returns how many were
+// written. anything that doesn't fit is kept for the next call
+size_t emerald_get_audio_samples(struct EmeraldHandle *handle,
+                                 int16_t *samples,
+                                 size_t max_frames);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* EMERALD_H */
@@ -0,0 +1,378 @@
+// compares two sh4 instruction traces and reports where they first disagree.
+//
+// either trace can be an emerald trace (`--trace instructions=verbose`, one json TraceRecord per line, anything
+// that isn't an instruction is skipped) or the plain reference format other emulators can be patched to emit,
+// one instruction per line as whitespace separated hex:
+//
+//     pc opcode r0 .. r15 fr0 .. fr15 sr fpscr
+//
+// that's the layout the old trace_instrs feature printed. registers are the state before the instruction runs,
+// r is the current bank and fr is fpr bank 0/1 as selected by fpscr.fr, as raw bits. a line with only the pc
+// and opcode is fine too, then only those get compared. blank lines and lines starting with # are ignored.
+//
+// the traces are lined up at the first instruction of ours (or --start-pc) and then compared one instruction
+// at a time, so they need to come from the same program starting in the same state.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    fs::File,
+    io::{BufRead, BufReader, Lines},
+    process::ExitCode,
+};
+
+use emerald_core::trace::{Sh4Registers, TraceEvent, TraceRecord};
+
+const USAGE: &str = "usage: trace-diff [options] <ours> <reference>
+options:
+  --start-pc <hex address> (defaults to the first pc in ours)
+  --context <instructions> (defaults to 8)
+  --ignore <register,...> (r0-r15, fr0-fr15, sr, fpscr, or r/fr for the whole file)";
+
+struct Step {
+    line: usize,
+    pc: u32,
+    opcode: u16,
+    disassembly: Option<String>,
+    registers: Option<Sh4Registers>,
+}
+
+impl Step {
+    fn describe(&self) -> String {
+        match &self.disassembly {
+            Some(disassembly) => format!("{:08x} {:04x}  {}", self.pc, self.opcode, disassembly),
+            None => format!("{:08x} {:04x}", self.pc, self.opcode),
+        }
+    }
+}
+
+struct Trace {
+    path: String,
+    lines: Lines<BufReader<File>>,
+    line: usize,
+}
+
+impl Trace {
+    fn open(path: &str) -> Result<Self, String> {
+        let file = File::open(path).map_err(|e| format!("couldn't open {}: {}", path, e))?;
+
+        Ok(Self {
+            path: path.to_owned(),
+            lines: BufReader::new(file).lines(),
+            line: 0,
+        })
+    }
+
+    fn next(&mut self) -> Result<Option<Step>, String> {
+        while let Some(line) = self.lines.next() {
+            let line = line.map_err(|e| format!("couldn't read {}: {}", self.path, e))?;
+            self.line += 1;
+
+            let step = if line.starts_with('{') {
+                self.parse_record(&line)?
+            } else {
+                self.parse_reference(&line)?
+            };
+
+            if step.is_some() {
+                return Ok(step);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn parse_record(&self, line: &str) -> Result<Option<Step>, String> {
+        let record: TraceRecord = serde_json::from_str(line)
+            .map_err(|e| format!("{}:{}: not a trace record: {}", self.path, self.line, e))?;
+
+        match record.event {
+            TraceEvent::Instruction {
+                opcode,
+                disassembly,
+                registers,
+                ..
+            } => Ok(Some(Step {
+                line: self.line,
+                pc: record.pc,
+                opcode,
+                disassembly: Some(disassembly),
+                registers,
+            })),
+            _ => Ok(None),
+        }
+    }
+
+    fn parse_reference(&self, line: &str) -> Result<Option<Step>, String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let words = line
+            .split_whitespace()
+            .map(|word| u32::from_str_radix(word.trim_start_matches("0x"), 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("{}:{}: {}", self.path, self.line, e))?;
+
+        let registers = match words.len() {
+            2 => None,
+            36 => Some(Sh4Registers {
+                r: words[2..18].try_into().unwrap(),
+                fr: words[18..34].try_into().unwrap(),
+                sr: words[34],
+                fpscr: words[35],
+            }),
+            count => {
+                return Err(format!(
+                    "{}:{}: expected 2 or 36 values, found {}",
+                    self.path, self.line, count
+                ))
+            }
+        };
+
+        Ok(Some(Step {
+            line: self.line,
+            pc: words[0],
+            opcode: words[1] as u16,
+            disassembly: None,
+            registers,
+        }))
+    }
+}
+
+fn named_registers(registers: &Sh4Registers) -> Vec<(String, u32)> {
+    let mut named = Vec::with_capacity(34);
+    named.extend((0..16).map(|i| (format!("r{}", i), registers.r[i])));
+    named.extend((0..16).map(|i| (format!("fr{}", i), registers.fr[i])));
+    named.push(("sr".to_owned(), registers.sr));
+    named.push(("fpscr".to_owned(), registers.fpscr));
+    named
+}
+
+fn ignored(name: &str, ignore: &HashSet<String>) -> bool {
+    let file = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    ignore.contains(name) || (file != name && ignore.contains(file))
+}
+
+// (register, ours, reference) for everything that differs
+fn register_diff(
+    ours: &Step,
+    reference: &Step,
+    ignore: &HashSet<String>,
+) -> Vec<(String, u32, u32)> {
+    let (Some(a), Some(b)) = (&ours.registers, &reference.registers) else {
+        return Vec::new();
+    };
+
+    named_registers(a)
+        .into_iter()
+        .zip(named_registers(b))
+        .filter(|((name, a), (_, b))| a != b && !ignored(name, ignore))
+        .map(|((name, a), (_, b))| (name, a, b))
+        .collect()
+}
+
+fn skip_to(trace: &mut Trace, pc: u32) -> Result<Option<Step>, String> {
+    while let Some(step) = trace.next()? {
+        if step.pc == pc {
+            return Ok(Some(step));
+        }
+    }
+
+    Ok(None)
+}
+
+// exits with 0 when the traces agree, 1 when they diverge and 2 when they couldn't be compared
+pub fn main() -> ExitCode {
+    match run() {
+        Ok(code) => code,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<ExitCode, String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let mut start_pc = None;
+    let mut context = 8;
+    let mut ignore = HashSet::new();
+    let mut paths = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(argument) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("{} needs a value\n{}", argument, USAGE))
+        };
+
+        match argument.as_str() {
+            "--start-pc" => {
+                let v = value()?;
+                start_pc = Some(
+                    u32::from_str_radix(v.trim_start_matches("0x"), 16)
+                        .map_err(|_| format!("invalid address {}", v))?,
+                );
+            }
+            "--context" => {
+                let v = value()?;
+                context = v.parse().map_err(|_| format!("invalid count {}", v))?;
+            }
+            "--ignore" => ignore.extend(value()?.split(',').map(|r| r.to_ascii_lowercase())),
+            _ if argument.starts_with("--") => {
+                return Err(format!("unknown argument {}\n{}", argument, USAGE))
+            }
+            _ => paths.push(argument.clone()),
+        }
+    }
+
+    let [ours_path, reference_path] = paths.as_slice() else {
+        return Err(USAGE.to_owned());
+    };
+
+    let mut ours = Trace::open(ours_path)?;
+    let mut reference = Trace::open(reference_path)?;
+
+    let first = match start_pc {
+        Some(pc) => skip_to(&mut ours, pc)?,
+        None => ours.next()?,
+    };
+
+    let Some(mut a) = first else {
+        return Err(format!("{} has no instructions to compare", ours.path));
+    };
+
+    let Some(mut b) = skip_to(&mut reference, a.pc)? else {
+        return Err(format!("{} never reaches {:08x}", reference.path, a.pc));
+    };
+
+    let mut history: VecDeque<Step> = VecDeque::with_capacity(context + 1);
+    let mut count = 0u64;
+
+    loop {
+        let diff = register_diff(&a, &b, &ignore);
+
+        if a.pc != b.pc || a.opcode != b.opcode || !diff.is_empty() {
+            report(
+                &a,
+                &b,
+                &diff,
+                &history,
+                count,
+                context,
+                &mut ours,
+                &mut reference,
+            )?;
+            return Ok(ExitCode::FAILURE);
+        }
+
+        count += 1;
+
+        if history.len() == context {
+            history.pop_front();
+        }
+        if context > 0 {
+            history.push_back(a);
+        }
+
+        match (ours.next()?, reference.next()?) {
+            (Some(next_a), Some(next_b)) => {
+                a = next_a;
+                b = next_b;
+            }
+            (None, None) => {
+                println!("traces match ({} instructions)", count);
+                return Ok(ExitCode::SUCCESS);
+            }
+            (None, Some(next)) => {
+                println!(
+                    "{} instructions match, then ours ends while the reference continues with\n  {}",
+                    count,
+                    next.describe()
+                );
+                return Ok(ExitCode::SUCCESS);
+            }
+            (Some(next), None) => {
+                println!(
+                    "{} instructions match, then the reference ends while ours continues with\n  {}",
+                    count,
+                    next.describe()
+                );
+                return Ok(ExitCode::SUCCESS);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn report(
+    a: &Step,
+    b: &Step,
+    diff: &[(String, u32, u32)],
+    history: &VecDeque<Step>,
+    count: u64,
+    context: usize,
+    ours: &mut Trace,
+    reference: &mut Trace,
+) -> Result<(), String> {
+    println!("diverged after {} matching instructions", count);
+    println!("  ours      {}:{}: {}", ours.path, a.line, a.describe());
+    println!(
+        "  reference {}:{}: {}",
+        reference.path,
+        b.line,
+        b.describe()
+    );
+
+    if !diff.is_empty() {
+        // registers are captured before an instruction runs, so the culprit is usually the one before
+        match history.back() {
+            Some(previous) => println!("\nregisters after {}", previous.describe()),
+            None => println!("\nregisters on entry"),
+        }
+
+        println!("  {:<6} {:>8}   {:>8}", "", "ours", "reference");
+        for (name, ours, reference) in diff {
+            if name.starts_with("fr") {
+                println!(
+                    "  {:<6} {:08x}   {:08x}   ({} != {})",
+                    name,
+                    ours,
+                    reference,
+                    f32::from_bits(*ours),
+                    f32::from_bits(*reference)
+                );
+            } else {
+                println!("  {:<6} {:08x}   {:08x}", name, ours, reference);
+            }
+        }
+    }
+
+    if !history.is_empty() {
+        println!("\nbefore (both agree)");
+        for step in history {
+            println!("  {}", step.describe());
+        }
+    }
+
+    for trace in [ours, reference] {
+        let mut after = Vec::new();
+        while after.len() < context {
+            match trace.next()? {
+                Some(step) => after.push(step),
+                None => break,
+            }
+        }
+
+        if !after.is_empty() {
+            println!("\nafter, in {}", trace.path);
+            for step in after {
+                println!("  {}", step.describe());
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,301 @@
+// direct boot, starting a program at 0x8c010000 the way ip.bin's bootstrap leaves things, without running the
+// bios or needing a disc.
+//
+// the bios keeps its syscalls and work area in the first 64k of ram. the syscall code is in the rom at the
+// offsets it runs from, so it's copied over and the vectors pointed at it. the rest of that 64k is the bios's
+// work area and the bootstrap (ip.bin, when given), the stack grows down from the bootstrap's end.
+// the system info the bios reads out of flash goes where sysinfo_init would put it. the video registers are
+// left scanning out of vram with the display on, like the bios's boot animation does.
+//
+// the program then starts in privileged mode with interrupts masked and the fpu at its reset state. if it
+// returns, it lands in the bios's idle loop.
+//
+// a disc can be booted the same way, see load_disc, skipping the bios's boot animation and disc checks.
+
+use std::fmt;
+
+use crate::{
+    context::Context,
+    hw::{
+        holly::g1::{
+            gdi::{GdiImage, Track},
+            iso9660::{self, Filesystem},
+        },
+        sh4::{bus::CpuBus, cpu::Cpu},
+    },
+};
+
+// where 1st_read.bin, and so every program, is loaded and entered
+pub const PROGRAM_ADDR: u32 = 0x8c010000;
+
+const IP_BIN_ADDR: u32 = 0x8c008000;
+const IP_BIN_SIZE: usize = 0x8000;
+
+const BIOS_WORK_AREA: usize = 0x10000; // bytes at the start of ram
+
+const STACK_TOP: u32 = 0x8c00f400;
+const VBR: u32 = 0x8c00f400;
+
+// sr.md, sr.rb and all interrupts masked
+const SR: u32 = 0x600000f0;
+
+// the sh4's reset value, denormals flushed and round to zero
+const FPSCR: u32 = 0x00040001;
+
+// where the bios's copy of each syscall lives in ram, the hle bios traps these instead
+pub(crate) const SYSINFO_ENTRY: u32 = 0x8c003c00;
+pub(crate) const ROMFONT_ENTRY: u32 = 0x8c003b80;
+pub(crate) const FLASHROM_ENTRY: u32 = 0x8c003d00;
+pub(crate) const GDROM_ENTRY: u32 = 0x8c001000;
+pub(crate) const GDROM_DIRECT_ENTRY: u32 = 0x8c0010f0; // some games jump here rather than through the vector
+pub(crate) const SYSTEM_ENTRY: u32 = 0x8c000800;
+
+pub(crate) const SYSCALL_VECTORS: [(u32, u32); 6] = [
+    (0x8c0000b0, SYSINFO_ENTRY),
+    (0x8c0000b4, ROMFONT_ENTRY),
+    (0x8c0000b8, FLASHROM_ENTRY),
+    (0x8c0000bc, GDROM_ENTRY),
+    (0x8c0000c0, GDROM_DIRECT_ENTRY),
+    (0x8c0000e0, SYSTEM_ENTRY),
+];
+
+// gd-roms have their filesystem in the high density area, starting with track 3
+const HIGH_DENSITY_AREA: usize = 45150;
+
+// ip.bin's hardware id, then the name of the program it boots
+const IP_BIN_HARDWARE_ID: &[u8] = b"SEGA SEGAKATANA ";
+const IP_BIN_BOOT_FILENAME: std::ops::Range<usize> = 0x60..0x70;
+
+// the syscalls' code, as offsets into both the rom and ram
+const BIOS_SYSCALLS: std::ops::Range<usize> = 0x100..0x4000;
+
+// the console's unique id, then region, language and broadcast standard
+pub(crate) const SYSINFO_ADDR: u32 = 0x8c000068;
+const FLASH_UNIQUE_ID: u32 = 0x0021a056;
+const FLASH_FACTORY_SETTINGS: u32 = 0x0021a000;
+
+// the bios's idle loop and the stubs after it. some of its interrupt handlers jump to the rte
+// rather than having their own
+const IDLE_LOOP: u32 = 0x8c000000;
+const BIOS_STUBS: [(u32, u16); 14] = [
+    (0x8c000000, 0x0009), // nop
+    (0x8c000002, 0x0009), // nop
+    (0x8c000004, 0x0009), // nop
+    (0x8c000006, 0x001b), // sleep
+    (0x8c000008, 0xaffd), // bra 0x8c000006
+    (0x8c00000a, 0x0009), // nop
+    (0x8c000010, 0x0009), // nop
+    (0x8c000012, 0x0009), // nop
+    (0x8c000014, 0x002b), // rte
+    (0x8c000016, 0x0009), // nop
+    (0x8c000018, 0x0009), // nop
+    (0x8c00001a, 0x0009), // nop
+    (0x8c00001c, 0x000b), // rts
+    (0x8c00001e, 0x0009), // nop
+];
+
+// 640x480, 32 bits per pixel, the display on
+const VIDEO_REGISTERS: [(u32, u32); 6] = [
+    (0x005f8044, 0x0080000d), // FB_R_CTRL
+    (0x005f8048, 0x00000006), // FB_W_CTRL
+    (0x005f8050, 0x00200000), // FB_R_SOF1
+    (0x005f8054, 0x00200000), // FB_R_SOF2
+    (0x005f8060, 0x00600000), // FB_W_SOF1
+    (0x005f8064, 0x00600000), // FB_W_SOF2
+];
+
+// sets up memory and the cpu so a program loaded afterwards can be entered at entry
+pub fn prepare(
+    entry: u32,
+    ip_bin: Option<&[u8]>,
+    cpu: &mut Cpu,
+    context: &mut Context,
+    bus: &mut CpuBus,
+) {
+    bus.system_ram[..BIOS_WORK_AREA].fill(0);
+
+    let bios = &bus.holly.g1_bus.boot_rom.bios;
+    bus.system_ram[BIOS_SYSCALLS].copy_from_slice(&bios[BIOS_SYSCALLS]);
+
+    for (vector, syscall) in SYSCALL_VECTORS {
+        bus.write_32(vector, syscall, context);
+    }
+
+    for (addr, opcode) in BIOS_STUBS {
+        bus.write_16(addr, opcode, context);
+    }
+
+    load_sysinfo(bus, context);
+
+    if let Some(ip_bin) = ip_bin {
+        let offset = (IP_BIN_ADDR & 0xffffff) as usize;
+        let len = ip_bin.len().min(IP_BIN_SIZE);
+        bus.system_ram[offset..offset + len].copy_from_slice(&ip_bin[..len]);
+    }
+
+    // the bios unlocks the gd-rom once it has checked the disc
+    bus.write_32(0xa05f74e4, 0x001fffff, context);
+
+    for (addr, value) in VIDEO_REGISTERS {
+        bus.write_32(addr, value, context);
+    }
+
+    cpu.registers.sr = SR;
+    cpu.registers.fpscr = FPSCR;
+    cpu.registers.vbr = VBR;
+    cpu.registers.pr = IDLE_LOOP;
+    cpu.registers.current_pc = entry;
+
+    for i in 0..15 {
+        cpu.set_register_by_index(i, 0);
+    }
+
+    cpu.set_register_by_index(15, STACK_TOP);
+}
+
+// what sysinfo_init copies out of flash
+pub(crate) fn load_sysinfo(bus: &mut CpuBus, context: &mut Context) {
+    for i in 0..8 {
+        let byte = bus.read_8(FLASH_UNIQUE_ID + i, true, context);
+        bus.write_8(SYSINFO_ADDR + i, byte, context);
+    }
+
+    for i in 0..5 {
+        let byte = bus.read_8(FLASH_FACTORY_SETTINGS + i, true, context);
+        bus.write_8(SYSINFO_ADDR + 8 + i, byte, context);
+    }
+}
+
+// a raw binary, 1st_read.bin or a stripped elf, loaded and entered at PROGRAM_ADDR
+pub fn load_binary(
+    binary: &[u8],
+    ip_bin: Option<&[u8]>,
+    cpu: &mut Cpu,
+    context: &mut Context,
+    bus: &mut CpuBus,
+) -> Result<(), ()> {
+    let offset = (PROGRAM_ADDR & 0xffffff) as usize;
+    if offset + binary.len() > bus.system_ram.len() {
+        return Err(());
+    }
+
+    prepare(PROGRAM_ADDR, ip_bin, cpu, context, bus);
+    bus.system_ram[offset..offset + binary.len()].copy_from_slice(binary);
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum DiscBootError {
+    NoDataTrack,
+    NoFilesystem,
+    NoIpBin,
+    NoBootFile(String),
+    BootFileSize(String, usize),
+}
+
+impl fmt::Display for DiscBootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiscBootError::NoDataTrack => write!(f, "there's no data track"),
+            DiscBootError::NoFilesystem => write!(f, "the data track has no iso9660 filesystem"),
+            DiscBootError::NoIpBin => write!(f, "the data track doesn't start with an ip.bin"),
+            DiscBootError::NoBootFile(name) => {
+                write!(f, "ip.bin boots {}, which isn't on the disc", name)
+            }
+            DiscBootError::BootFileSize(name, size) => {
+                write!(f, "{} is {} bytes, too big to load", name, size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DiscBootError {}
+
+// what the bios does once it has checked the disc: ip.bin, the first 16 sectors of the data track, goes to
+// IP_BIN_ADDR and the program it names to PROGRAM_ADDR, both from the high density area on gd-roms or the
+// last session's data track on a cd. programs on cds (mil-cd) are scrambled, so they're descrambled first
+pub fn load_disc(
+    cpu: &mut Cpu,
+    context: &mut Context,
+    bus: &mut CpuBus,
+) -> Result<(), DiscBootError> {
+    let image = bus.holly.g1_bus.gd_rom.gdi_image.as_ref();
+    let track = image
+        .and_then(boot_track)
+        .ok_or(DiscBootError::NoDataTrack)?;
+    let filesystem = Filesystem::new(track).ok_or(DiscBootError::NoFilesystem)?;
+
+    let ip_bin = filesystem
+        .read_sectors(
+            track.offset as u32,
+            (IP_BIN_SIZE / iso9660::SECTOR_SIZE) as u32,
+        )
+        .filter(|ip_bin| ip_bin.starts_with(IP_BIN_HARDWARE_ID))
+        .ok_or(DiscBootError::NoIpBin)?;
+
+    let name = String::from_utf8_lossy(&ip_bin[IP_BIN_BOOT_FILENAME])
+        .trim_end_matches([' ', '\0'])
+        .to_owned();
+
+    let program = filesystem
+        .read_file(&name)
+        .ok_or_else(|| DiscBootError::NoBootFile(name.clone()))?;
+
+    let program = if track.offset < HIGH_DENSITY_AREA {
+        descramble(&program)
+    } else {
+        program
+    };
+
+    let size = program.len();
+    load_binary(&program, Some(&ip_bin), cpu, context, bus)
+        .map_err(|_| DiscBootError::BootFileSize(name, size))
+}
+
+fn boot_track(image: &GdiImage) -> Option<&Track> {
+    let mut data_tracks = image.tracks.iter().filter(|track| !track.is_audo_track);
+
+    data_tracks
+        .clone()
+        .find(|track| track.offset >= HIGH_DENSITY_AREA)
+        .or_else(|| data_tracks.next_back())
+}
+
+// mil-cd programs are shuffled in 32 byte slices, within chunks of 2mb that halve down to a single slice
+// for the tail. the order comes from a prng seeded with the size, so it can be replayed to put them back
+fn descramble(scrambled: &[u8]) -> Vec<u8> {
+    const SLICE: usize = 32;
+    const MAX_CHUNK: usize = 2048 * 1024;
+
+    let mut seed = scrambled.len() as u32 & 0xffff;
+    let mut next = || {
+        seed = (seed * 2109 + 9273) & 0x7fff;
+        (seed + 0xc000) & 0xffff
+    };
+
+    let mut program = vec![0; scrambled.len()];
+    let mut offset = 0;
+    let mut chunk_size = MAX_CHUNK;
+
+    while chunk_size >= SLICE {
+        while scrambled.len() - offset >= chunk_size {
+            let mut slices: Vec<usize> = (0..chunk_size / SLICE).collect();
+
+            for (n, i) in (0..slices.len()).rev().enumerate() {
+                let swap = ((next() * i as u32) >> 16) as usize;
+                slices.swap(i, swap);
+
+                let src = offset + n * SLICE;
+                let dest = offset + slices[i] * SLICE;
+                program[dest..dest + SLICE].copy_from_slice(&scrambled[src..src + SLICE]);
+            }
+
+            offset += chunk_size;
+        }
+
+        chunk_size /= 2;
+    }
+
+    program[offset..].copy_from_slice(&scrambled[offset..]);
+    program
+}
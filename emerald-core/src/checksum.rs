@@ -0,0 +1,35 @@
+// standard reflected crc-32 (same as zlib/png)
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffff_u32;
+
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xedb88320;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+
+    !crc
+}
+
+// crc used by the bios for each 64-byte flash block (ccitt poly, inverted result)
+pub fn flash_crc16(data: &[u8]) -> u16 {
+    let mut n = 0xffff_u32;
+
+    for byte in data {
+        n ^= (*byte as u32) << 8;
+        for _ in 0..8 {
+            if n & 0x8000 != 0 {
+                n = (n << 1) ^ 0x1021;
+            } else {
+                n <<= 1;
+            }
+        }
+    }
+
+    (!n & 0xffff) as u16
+}
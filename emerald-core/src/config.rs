@@ -0,0 +1,665 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boot::DiscBootError,
+    checksum::{crc32, flash_crc16},
+    gdb::{GdbTarget, GDB_DEFAULT_PORT},
+    movie::MovieError,
+    serial::SerialConfig,
+    trace::TraceConfig,
+};
+
+pub const BIOS_SIZE: usize = 0x200000;
+pub const FLASH_SIZE: usize = 0x20000;
+
+// crc32s of bios dumps we know boot
+pub const KNOWN_BIOS_CRCS: &[(u32, &str)] = &[(0x89f2b1a1, "v1.01d (world)")];
+
+// the flash user partition (system settings) lives here, 64-byte blocks with a trailing crc16
+const FLASH_USER_PARTITION: usize = 0x1c000;
+const FLASH_USER_PARTITION_SIZE: usize = 0x4000;
+const FLASH_BLOCK_SIZE: usize = 64;
+const FLASH_PARTITION_MAGIC: &[u8; 16] = b"KATANA_FLASH____";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Region {
+    Japan,
+    Usa,
+    Europe,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Language {
+    Japanese,
+    English,
+    German,
+    French,
+    Spanish,
+    Italian,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum CableType {
+    Vga,
+    Rgb,
+    Composite,
+}
+
+impl Region {
+    // ascii digit the factory flash partition uses for the region
+    pub fn flash_value(&self) -> u8 {
+        b'0' + *self as u8
+    }
+
+    // 0 = ntsc, 1 = pal
+    pub fn broadcast_value(&self) -> u8 {
+        match self {
+            Region::Europe => b'1',
+            _ => b'0',
+        }
+    }
+}
+
+impl Language {
+    pub fn flash_value(&self) -> u8 {
+        b'0' + *self as u8
+    }
+}
+
+impl CableType {
+    // value reported in PDTRA bits 8-9
+    pub fn pdtra_value(&self) -> u16 {
+        match self {
+            CableType::Vga => 0,
+            CableType::Rgb => 2,
+            CableType::Composite => 3,
+        }
+    }
+}
+
+impl FromStr for Region {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "japan" | "jp" => Ok(Region::Japan),
+            "usa" | "us" => Ok(Region::Usa),
+            "europe" | "eu" => Ok(Region::Europe),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for Language {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "japanese" | "ja" => Ok(Language::Japanese),
+            "english" | "en" => Ok(Language::English),
+            "german" | "de" => Ok(Language::German),
+            "french" | "fr" => Ok(Language::French),
+            "spanish" | "es" => Ok(Language::Spanish),
+            "italian" | "it" => Ok(Language::Italian),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for CableType {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "vga" => Ok(CableType::Vga),
+            "rgb" => Ok(CableType::Rgb),
+            "composite" | "tv" => Ok(CableType::Composite),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        message: String,
+    },
+    UnknownArgument(String),
+    MissingValue(String),
+    InvalidValue {
+        argument: String,
+        value: String,
+    },
+    BiosSize {
+        path: PathBuf,
+        actual: usize,
+    },
+    FlashSize {
+        path: PathBuf,
+        actual: usize,
+    },
+    UnknownBios {
+        path: PathBuf,
+        crc32: u32,
+    },
+    FlashPartitionMissing {
+        path: PathBuf,
+    },
+    InvalidElf(PathBuf),
+    NoSymbols(PathBuf),
+    HleBiosNeedsProgram, // it can't run the bios's boot, it needs an elf or a disc to start directly
+    FastBootNeedsDisc,
+    DiscBoot {
+        path: PathBuf,
+        error: DiscBootError,
+    },
+    FlashBlockChecksum {
+        path: PathBuf,
+        offset: usize,
+        expected: u16,
+        actual: u16,
+    },
+    Movie {
+        path: PathBuf,
+        error: MovieError,
+    },
+    Gdb {
+        port: u16,
+        error: std::io::Error,
+    },
+    Trace {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Serial {
+        serial: SerialConfig,
+        error: std::io::Error,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => {
+                write!(f, "couldn't read {}: {}", path.display(), error)
+            }
+            ConfigError::Parse { path, message } => {
+                write!(f, "couldn't parse config {}: {}", path.display(), message)
+            }
+            ConfigError::UnknownArgument(argument) => write!(f, "unknown argument {}", argument),
+            ConfigError::MissingValue(argument) => write!(f, "{} expects a value", argument),
+            ConfigError::InvalidValue { argument, value } => {
+                write!(f, "invalid value '{}' for {}", value, argument)
+            }
+            ConfigError::BiosSize { path, actual } => write!(
+                f,
+                "bios {} is {} bytes, expected {} bytes",
+                path.display(),
+                actual,
+                BIOS_SIZE
+            ),
+            ConfigError::FlashSize { path, actual } => write!(
+                f,
+                "flash {} is {} bytes, expected {} bytes",
+                path.display(),
+                actual,
+                FLASH_SIZE
+            ),
+            ConfigError::UnknownBios { path, crc32 } => write!(
+                f,
+                "bios {} has an unrecognized crc32 of {:08x} (bad dump?), set verify_checksums to false to use it anyway",
+                path.display(),
+                crc32
+            ),
+            ConfigError::FlashPartitionMissing { path } => write!(
+                f,
+                "flash {} has no system partition header at 0x{:05x}",
+                path.display(),
+                FLASH_USER_PARTITION
+            ),
+            ConfigError::InvalidElf(path) => {
+                write!(
+                    f,
+                    "{} couldn't be loaded as an elf or a raw binary",
+                    path.display()
+                )
+            }
+            ConfigError::NoSymbols(path) => {
+                write!(f, "{} has no symbols in nm or ld map format", path.display())
+            }
+            ConfigError::HleBiosNeedsProgram => {
+                write!(f, "--hle-bios needs an --elf or a --disc to boot")
+            }
+            ConfigError::FastBootNeedsDisc => write!(f, "--fast-boot needs a --disc"),
+            ConfigError::DiscBoot { path, error } => {
+                write!(f, "couldn't boot {}: {}", path.display(), error)
+            }
+            ConfigError::FlashBlockChecksum {
+                path,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "flash {} block @ 0x{:05x} has crc {:04x}, expected {:04x}",
+                path.display(),
+                offset,
+                actual,
+                expected
+            ),
+            ConfigError::Movie { path, error } => {
+                write!(f, "movie {}: {}", path.display(), error)
+            }
+            ConfigError::Gdb { port, error } => {
+                write!(f, "couldn't listen for gdb on port {}: {}", port, error)
+            }
+            ConfigError::Trace { path, error } => {
+                write!(f, "couldn't create trace {}: {}", path.display(), error)
+            }
+            ConfigError::Serial { serial, error } => {
+                write!(f, "couldn't open serial port {}: {}", serial, error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// everything needed to bring up a machine. loadable from ron/json, overridable from the command line
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmulatorConfig {
+    pub bios_path: PathBuf,
+    pub flash_path: PathBuf,
+    pub disc_path: Option<PathBuf>,
+    pub elf_path: Option<PathBuf>, // or a raw binary, see Machine::load_elf_bytes
+
+    // extra symbols as `nm` output or ld .map files, on top of whatever the elf has
+    pub symbol_paths: Vec<PathBuf>,
+
+    // only used when direct booting an elf, see boot.rs
+    pub ip_bin_path: Option<PathBuf>,
+
+    // service the bios's syscalls instead of needing a dump of it, see hle_bios.rs
+    pub hle_bios: bool,
+
+    // start the disc's program directly rather than through the bios's boot, see boot::load_disc. implied by
+    // hle_bios when there's a disc but no elf
+    pub fast_boot: bool,
+
+    pub region: Region,
+    pub language: Language,
+    pub cable_type: CableType,
+    pub verify_checksums: bool,
+
+    // input movies. both imply frame aligned input
+    pub record_movie: Option<PathBuf>,
+    pub play_movie: Option<PathBuf>,
+
+    // only apply controller input on vblank so runs are reproducible
+    pub frame_aligned_input: bool,
+
+    // wait for a gdb connection on this port before running, see gdb.rs
+    pub gdb_port: Option<u16>,
+    pub gdb_target: GdbTarget,
+
+    // where the scif's output goes and its input comes from, see serial.rs
+    pub serial: SerialConfig,
+
+    // serve dcload host syscalls from this directory to a direct booted elf, see dcload.rs
+    pub dcload_root: Option<PathBuf>,
+    pub dcload_gdb_port: Option<u16>,
+
+    // see trace.rs, everything is off by default
+    pub trace: TraceConfig,
+}
+
+impl Default for EmulatorConfig {
+    fn default() -> Self {
+        Self {
+            bios_path: PathBuf::from("roms/dc_boot.bin"),
+            flash_path: PathBuf::from("roms/dc_flash.bin"),
+            disc_path: None,
+            elf_path: None,
+            symbol_paths: Vec::new(),
+            ip_bin_path: None,
+            hle_bios: false,
+            fast_boot: false,
+            region: Region::Usa,
+            language: Language::English,
+            cable_type: CableType::Vga,
+            verify_checksums: true,
+            record_movie: None,
+            play_movie: None,
+            frame_aligned_input: false,
+            gdb_port: None,
+            gdb_target: GdbTarget::Sh4,
+            serial: SerialConfig::None,
+            dcload_root: None,
+            dcload_gdb_port: None,
+            trace: TraceConfig::default(),
+        }
+    }
+}
+
+pub struct SystemRoms {
+    pub bios: Vec<u8>,
+    pub flash: Vec<u8>,
+}
+
+pub const USAGE: &str = "options:
+  --config <file.ron|file.json>
+  --bios <path>
+  --flash <path>
+  --disc <path.gdi>
+  --elf <path.elf|path.bin> (anything that isn't an elf is loaded at 0x8c010000)
+  --symbols <path.map|nm output> (can be repeated)
+  --ip-bin <path>
+  --hle-bios (no bios needed, direct boots --elf or --disc)
+  --fast-boot (skips the bios's boot, starts --disc's program directly)
+  --region <japan|usa|europe>
+  --language <japanese|english|german|french|spanish|italian>
+  --cable <vga|rgb|composite>
+  --no-verify
+  --record-movie <path>
+  --play-movie <path>
+  --frame-aligned-input
+  --gdb [port] (defaults to 2159)
+  --gdb-target <sh4|arm7>
+  --serial <none|stdout|file:<path>|pty|tcp:<port>>
+  --dcload <dir> (host syscalls for --elf, the guest's /pc)
+  --dcload-gdb <port>
+  --trace <category[=off|info|verbose],...> (instructions, memory, interrupts, dma, gdrom, io, bios, symbols, serial)
+  --trace-file <path> (defaults to stdout)
+  --trace-start-pc <hex address>
+  --trace-pc <hex start>-<hex end> (inclusive)
+  --trace-cycles <start>-<end>";
+
+impl EmulatorConfig {
+    // picks ron or json based on the file extension
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|error| ConfigError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        let is_json = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("json"))
+            .unwrap_or(false);
+
+        let parsed = if is_json {
+            serde_json::from_str(&contents).map_err(|e| e.to_string())
+        } else {
+            ron::from_str(&contents).map_err(|e| e.to_string())
+        };
+
+        parsed.map_err(|message| ConfigError::Parse {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    // --config is applied first, everything else overrides what it loaded
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, ConfigError> {
+        let args: Vec<String> = args.into_iter().collect();
+
+        let mut config = match args.iter().position(|a| a == "--config") {
+            Some(idx) => match args.get(idx + 1) {
+                Some(path) => Self::load_from_file(path)?,
+                None => return Err(ConfigError::MissingValue("--config".to_owned())),
+            },
+            None => Self::default(),
+        };
+
+        let mut iter = args.iter();
+        while let Some(argument) = iter.next() {
+            if argument == "--no-verify" {
+                config.verify_checksums = false;
+                continue;
+            }
+
+            if argument == "--hle-bios" {
+                config.hle_bios = true;
+                continue;
+            }
+
+            if argument == "--fast-boot" {
+                config.fast_boot = true;
+                continue;
+            }
+
+            if argument == "--frame-aligned-input" {
+                config.frame_aligned_input = true;
+                continue;
+            }
+
+            // the port is optional, `--gdb` alone listens on the usual one
+            if argument == "--gdb" {
+                config.gdb_port = Some(GDB_DEFAULT_PORT);
+                if let Some(port) = iter.clone().next().filter(|a| !a.starts_with("--")) {
+                    config.gdb_port =
+                        Some(port.parse().map_err(|_| ConfigError::InvalidValue {
+                            argument: argument.clone(),
+                            value: port.clone(),
+                        })?);
+                    iter.next();
+                }
+                continue;
+            }
+
+            let mut value = || {
+                iter.next()
+                    .cloned()
+                    .ok_or_else(|| ConfigError::MissingValue(argument.clone()))
+            };
+
+            let invalid = |value: String| ConfigError::InvalidValue {
+                argument: argument.clone(),
+                value,
+            };
+
+            match argument.as_str() {
+                "--config" => {
+                    value()?;
+                }
+                "--bios" => config.bios_path = value()?.into(),
+                "--flash" => config.flash_path = value()?.into(),
+                "--disc" => config.disc_path = Some(value()?.into()),
+                "--elf" => config.elf_path = Some(value()?.into()),
+                "--symbols" => config.symbol_paths.push(value()?.into()),
+                "--ip-bin" => config.ip_bin_path = Some(value()?.into()),
+                "--record-movie" => config.record_movie = Some(value()?.into()),
+                "--play-movie" => config.play_movie = Some(value()?.into()),
+                "--region" => {
+                    let v = value()?;
+                    config.region = v.parse().map_err(|_| invalid(v))?;
+                }
+                "--language" => {
+                    let v = value()?;
+                    config.language = v.parse().map_err(|_| invalid(v))?;
+                }
+                "--cable" => {
+                    let v = value()?;
+                    config.cable_type = v.parse().map_err(|_| invalid(v))?;
+                }
+                "--gdb-target" => {
+                    let v = value()?;
+                    config.gdb_target = v.parse().map_err(|_| invalid(v))?;
+                }
+                "--serial" => {
+                    let v = value()?;
+                    config.serial = v.parse().map_err(|_| invalid(v))?;
+                }
+                "--dcload" => config.dcload_root = Some(value()?.into()),
+                "--dcload-gdb" => {
+                    let v = value()?;
+                    config.dcload_gdb_port = Some(v.parse().map_err(|_| invalid(v))?);
+                }
+                "--trace" => {
+                    let v = value()?;
+                    config.trace.parse_levels(&v).map_err(|_| invalid(v))?;
+                }
+                "--trace-file" => config.trace.output = Some(value()?.into()),
+                "--trace-start-pc" => {
+                    let v = value()?;
+                    config.trace.start_pc = Some(parse_address(&v).map_err(|_| invalid(v))?);
+                }
+                "--trace-pc" => {
+                    let v = value()?;
+                    let (start, end) = v
+                        .split_once('-')
+                        .and_then(|(start, end)| {
+                            Some((parse_address(start).ok()?, parse_address(end).ok()?))
+                        })
+                        .ok_or_else(|| invalid(v.clone()))?;
+                    config.trace.pc_range = Some(start..=end);
+                }
+                "--trace-cycles" => {
+                    let v = value()?;
+                    let (start, end) = v
+                        .split_once('-')
+                        .and_then(|(start, end)| Some((start.parse().ok()?, end.parse().ok()?)))
+                        .ok_or_else(|| invalid(v.clone()))?;
+                    config.trace.cycle_range = Some(start..end);
+                }
+                _ => return Err(ConfigError::UnknownArgument(argument.clone())),
+            }
+        }
+
+        Ok(config)
+    }
+
+    // reads and validates the bios and flash, reporting every problem found rather than just the first
+    pub fn load_roms(&self) -> Result<SystemRoms, Vec<ConfigError>> {
+        let mut errors = Vec::new();
+
+        // the hle bios only needs the rom to be there, not what's in it
+        let bios = if self.hle_bios {
+            Some(vec![0; BIOS_SIZE])
+        } else {
+            read_file(&self.bios_path).map_err(|e| errors.push(e)).ok()
+        };
+        let flash = read_file(&self.flash_path).map_err(|e| errors.push(e)).ok();
+
+        if let (Some(bios), false) = (&bios, self.hle_bios) {
+            errors.extend(self.validate_bios(&self.bios_path, bios));
+        }
+
+        if let Some(flash) = &flash {
+            errors.extend(self.validate_flash(&self.flash_path, flash));
+        }
+
+        match (bios, flash) {
+            (Some(bios), Some(flash)) if errors.is_empty() => Ok(SystemRoms { bios, flash }),
+            _ => Err(errors),
+        }
+    }
+
+    // path is only used for reporting, the image may not have come from a file
+    pub fn validate_bios(&self, path: &Path, bios: &[u8]) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+
+        if bios.len() != BIOS_SIZE {
+            errors.push(ConfigError::BiosSize {
+                path: path.to_path_buf(),
+                actual: bios.len(),
+            });
+        } else if self.verify_checksums {
+            let crc = crc32(bios);
+            if !KNOWN_BIOS_CRCS.iter().any(|(known, _)| *known == crc) {
+                errors.push(ConfigError::UnknownBios {
+                    path: path.to_path_buf(),
+                    crc32: crc,
+                });
+            }
+        }
+
+        errors
+    }
+
+    pub fn validate_flash(&self, path: &Path, flash: &[u8]) -> Vec<ConfigError> {
+        if flash.len() != FLASH_SIZE {
+            return vec![ConfigError::FlashSize {
+                path: path.to_path_buf(),
+                actual: flash.len(),
+            }];
+        }
+
+        if self.verify_checksums {
+            verify_flash(path, flash)
+        } else {
+            Vec::new()
+        }
+    }
+
+    pub fn read_optional(path: &Option<PathBuf>) -> Result<Option<Vec<u8>>, ConfigError> {
+        match path {
+            Some(path) => read_file(path).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+// hex, with or without the 0x
+fn parse_address(s: &str) -> Result<u32, ()> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    u32::from_str_radix(digits, 16).map_err(|_| ())
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, ConfigError> {
+    fs::read(path).map_err(|error| ConfigError::Io {
+        path: path.to_path_buf(),
+        error,
+    })
+}
+
+fn verify_flash(path: &Path, flash: &[u8]) -> Vec<ConfigError> {
+    let mut errors = Vec::new();
+    let partition = &flash[FLASH_USER_PARTITION..FLASH_USER_PARTITION + FLASH_USER_PARTITION_SIZE];
+
+    if &partition[0..16] != FLASH_PARTITION_MAGIC {
+        errors.push(ConfigError::FlashPartitionMissing {
+            path: path.to_path_buf(),
+        });
+        return errors;
+    }
+
+    // block 0 is the partition header and the last block is the allocation bitmap
+    let block_count = FLASH_USER_PARTITION_SIZE / FLASH_BLOCK_SIZE;
+    for i in 1..block_count - 1 {
+        let block = &partition[i * FLASH_BLOCK_SIZE..(i + 1) * FLASH_BLOCK_SIZE];
+        let block_id = u16::from_le_bytes([block[0], block[1]]);
+
+        // erased
+        if block_id == 0xffff {
+            continue;
+        }
+
+        let expected = u16::from_le_bytes([block[62], block[63]]);
+        let actual = flash_crc16(&block[0..62]);
+
+        if expected != actual {
+            errors.push(ConfigError::FlashBlockChecksum {
+                path: path.to_path_buf(),
+                offset: FLASH_USER_PARTITION + i * FLASH_BLOCK_SIZE,
+                expected,
+                actual,
+            });
+        }
+    }
+
+    errors
+}
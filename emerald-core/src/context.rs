@@ -1,9 +1,19 @@
 use core::fmt;
 
-use crate::{scheduler::Scheduler};
+use crate::{error::EmulatorError, scheduler::Scheduler, trace::Tracer};
 
-pub struct Context<'b> {
-    pub scheduler: &'b mut Scheduler,
+pub struct Context {
+    pub scheduler: Scheduler,
     pub cyc: u64,
-    pub tracing: bool,
+    pub tracer: Tracer,
+    pub error: Option<EmulatorError>, // first device error hit by the current instruction
+}
+
+impl Context {
+    // errors can't unwind through the cpu's memory accesses, so they're parked here and the access
+    // carries on with a default value. the machine halts once the instruction finishes.
+    pub fn fault<T: Default>(&mut self, error: EmulatorError) -> T {
+        self.error.get_or_insert(error);
+        T::default()
+    }
 }
@@ -0,0 +1,474 @@
+// dc-load host services, for homebrew built to run under dcload-ip or dcload-serial.
+//
+// those loaders leave 0xdeadbeef at 0x8c004004 and a syscall entry point at 0x8c004008, and kos (among others)
+// uses them for stdio and a /pc filesystem when it finds them. with --dcload <dir> load_elf sets the same thing
+// up, and the machine services a call when the sh4 reaches the entry point: r4 is the call, r5-r7 the
+// arguments, r0 gets the result and the rts placed there returns to the caller.
+//
+// files come from the given directory only, the guest's / is its root and nothing above it can be reached.
+// console writes go to stdout and stderr, or to whoever is capturing the serial port, see Machine::capture_serial.
+// exit stops the machine with the guest's status, see EmulatorState::Exited. gdbpacket forwards kos's gdb stub
+// to a debugger on --dcload-gdb's port, like dc-tool -g.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{TcpListener, TcpStream},
+    path::{Component, Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    context::Context,
+    hw::sh4::{
+        bus::{CpuBus, GuestMemory},
+        cpu::Cpu,
+    },
+    trace::TraceCategory,
+};
+
+pub const MAGIC_ADDR: u32 = 0x8c004004;
+pub const MAGIC: u32 = 0xdeadbeef;
+pub const SYSCALL_ADDR: u32 = 0x8c004008; // holds the entry point
+pub const ENTRY_POINT: u32 = 0x8c004010;
+
+// readdir hands back a pointer to one of these, it lives in the loader's memory
+const DIRENT_ADDR: u32 = 0x8c004100;
+
+const MAX_PATH: usize = 1024;
+
+// read and write copy through a buffer this size, whatever length the guest asks for
+const TRANSFER_CHUNK: usize = 0x10000;
+
+// the calls, in the order of kos's fs_dcload.h
+const READ: u32 = 0;
+const WRITE: u32 = 1;
+const OPEN: u32 = 2;
+const CLOSE: u32 = 3;
+const CREAT: u32 = 4;
+const UNLINK: u32 = 6;
+const LSEEK: u32 = 9;
+const FSTAT: u32 = 10;
+const TIME: u32 = 11;
+const STAT: u32 = 12;
+const EXIT: u32 = 15;
+const OPENDIR: u32 = 16;
+const CLOSEDIR: u32 = 17;
+const READDIR: u32 = 18;
+const GDBPACKET: u32 = 20;
+const REWINDDIR: u32 = 21;
+
+// newlib's open flags
+const O_ACCMODE: u32 = 0x3;
+const O_APPEND: u32 = 0x8;
+const O_CREAT: u32 = 0x200;
+const O_TRUNC: u32 = 0x400;
+const O_EXCL: u32 = 0x800;
+
+// st_mode types
+const S_IFCHR: u32 = 0o020000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+
+const FIRST_HANDLE: u32 = 3; // 0-2 are the console
+
+struct Directory {
+    entries: Vec<String>, // sorted, so listings don't depend on the host
+    position: usize,
+}
+
+pub struct DcLoad {
+    root: PathBuf,
+    files: BTreeMap<u32, File>,
+    directories: BTreeMap<u32, Directory>,
+    next_handle: u32,
+    gdb: Option<TcpListener>,
+    gdb_stream: Option<TcpStream>,
+}
+
+impl DcLoad {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            files: BTreeMap::new(),
+            directories: BTreeMap::new(),
+            next_handle: FIRST_HANDLE,
+            gdb: None,
+            gdb_stream: None,
+        }
+    }
+
+    pub fn listen_for_gdb(&mut self, port: u16) -> io::Result<()> {
+        self.gdb = Some(TcpListener::bind(("127.0.0.1", port))?);
+        Ok(())
+    }
+
+    // what the loader would have left in memory before jumping to the program
+    pub fn install(&self, bus: &mut CpuBus, context: &mut Context) {
+        bus.write_32(MAGIC_ADDR, MAGIC, context);
+        bus.write_32(SYSCALL_ADDR, ENTRY_POINT, context);
+        bus.write_16(ENTRY_POINT, 0x000b, context); // rts
+        bus.write_16(ENTRY_POINT + 2, 0x0009, context); // nop
+    }
+
+    // services the call the cpu is about to make, some(status) if it was exit
+    pub fn syscall(
+        &mut self,
+        cpu: &mut Cpu,
+        bus: &mut CpuBus,
+        context: &mut Context,
+    ) -> Option<i32> {
+        let [call, a, b, c] = [4, 5, 6, 7].map(|i| cpu.get_register_by_index(i));
+        let mut memory = GuestMemory::new(bus, context);
+
+        let result = match call {
+            READ => self.read(&mut memory, a, b, c),
+            WRITE => self.write(&mut memory, a, b, c),
+            OPEN => self.open(&mut memory, a, b),
+            CLOSE => self.close(a),
+            CREAT => self.open(&mut memory, a, 0x1 | O_CREAT | O_TRUNC),
+            UNLINK => self
+                .resolve(&memory.read_string(a, MAX_PATH))
+                .and_then(|path| fs::remove_file(path).ok())
+                .map_or(-1, |_| 0),
+            LSEEK => self.lseek(a, b as i32, c),
+            FSTAT => self.fstat(&mut memory, a, b),
+            TIME => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(-1, |d| d.as_secs() as i32),
+            STAT => self.stat(&mut memory, a, b),
+            EXIT => return Some(a as i32),
+            OPENDIR => self.opendir(&mut memory, a),
+            CLOSEDIR => self.directories.remove(&a).map_or(-1, |_| 0),
+            READDIR => self.readdir(&mut memory, a),
+            REWINDDIR => match self.directories.get_mut(&a) {
+                Some(directory) => {
+                    directory.position = 0;
+                    0
+                }
+                None => -1,
+            },
+            GDBPACKET => self.gdbpacket(&mut memory, a, b, c),
+            _ => {
+                let tracer = &mut memory.context.tracer;
+                if tracer.enabled(TraceCategory::Bios) {
+                    tracer.message(
+                        TraceCategory::Bios,
+                        format!("dcload: unsupported syscall {}", call),
+                    );
+                }
+
+                -1
+            }
+        };
+
+        cpu.set_register_by_index(0, result as u32);
+        None
+    }
+
+    // maps a guest path into the root, none if it tries to leave it
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let mut resolved = self.root.clone();
+        let mut depth = 0;
+
+        for component in Path::new(path).components() {
+            match component {
+                Component::Normal(part) => {
+                    resolved.push(part);
+                    depth += 1;
+                }
+                Component::ParentDir if depth > 0 => {
+                    resolved.pop();
+                    depth -= 1;
+                }
+                Component::ParentDir | Component::Prefix(_) => return None,
+                Component::RootDir | Component::CurDir => {}
+            }
+        }
+
+        Some(resolved)
+    }
+
+    fn allocate_handle(&mut self) -> u32 {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        handle
+    }
+
+    fn read(&mut self, memory: &mut GuestMemory, fd: u32, buffer: u32, len: u32) -> i32 {
+        let Some(file) = self.files.get_mut(&fd) else {
+            return -1;
+        };
+
+        let len = len.min(i32::MAX as u32);
+        let mut data = vec![0; TRANSFER_CHUNK];
+        let mut total = 0;
+
+        while total < len {
+            let size = ((len - total) as usize).min(TRANSFER_CHUNK);
+            match file.read(&mut data[..size]) {
+                Ok(0) => break,
+                Ok(read) => {
+                    memory.write(buffer.wrapping_add(total), &data[..read]);
+                    total += read as u32;
+                }
+                Err(_) if total == 0 => return -1,
+                Err(_) => break,
+            }
+        }
+
+        total as i32
+    }
+
+    fn write(&mut self, memory: &mut GuestMemory, fd: u32, buffer: u32, len: u32) -> i32 {
+        if !matches!(fd, 1 | 2) && !self.files.contains_key(&fd) {
+            return -1;
+        }
+
+        let len = len.min(i32::MAX as u32);
+        let mut total = 0;
+
+        while total < len {
+            let size = ((len - total) as usize).min(TRANSFER_CHUNK);
+            let data = memory.read(buffer.wrapping_add(total), size);
+
+            if self.output(memory, fd, &data).is_err() {
+                return -1;
+            }
+
+            total += size as u32;
+        }
+
+        len as i32
+    }
+
+    // the console goes to the host's, or to the serial capture while the machine is capturing it
+    fn output(&mut self, memory: &mut GuestMemory, fd: u32, data: &[u8]) -> io::Result<()> {
+        match fd {
+            1 | 2 if memory.bus.scif.captured.is_some() => {
+                memory
+                    .bus
+                    .scif
+                    .captured
+                    .as_mut()
+                    .unwrap()
+                    .extend_from_slice(data);
+                Ok(())
+            }
+            1 => io::stdout()
+                .write_all(data)
+                .and_then(|_| io::stdout().flush()),
+            2 => io::stderr().write_all(data),
+            _ => match self.files.get_mut(&fd) {
+                Some(file) => file.write_all(data),
+                None => Err(io::ErrorKind::NotFound.into()),
+            },
+        }
+    }
+
+    fn open(&mut self, memory: &mut GuestMemory, path: u32, flags: u32) -> i32 {
+        let Some(path) = self.resolve(&memory.read_string(path, MAX_PATH)) else {
+            return -1;
+        };
+
+        let access = flags & O_ACCMODE;
+        let file = OpenOptions::new()
+            .read(access != 1)
+            .write(access != 0)
+            .append(flags & O_APPEND != 0)
+            .truncate(flags & O_TRUNC != 0)
+            .create(flags & O_CREAT != 0)
+            .create_new(flags & O_CREAT != 0 && flags & O_EXCL != 0)
+            .open(path);
+
+        match file {
+            Ok(file) => {
+                let fd = self.allocate_handle();
+                self.files.insert(fd, file);
+                fd as i32
+            }
+            Err(_) => -1,
+        }
+    }
+
+    fn close(&mut self, fd: u32) -> i32 {
+        self.files.remove(&fd).map_or(-1, |_| 0)
+    }
+
+    fn lseek(&mut self, fd: u32, offset: i32, whence: u32) -> i32 {
+        let Some(file) = self.files.get_mut(&fd) else {
+            return -1;
+        };
+
+        let position = match whence {
+            0 if offset >= 0 => SeekFrom::Start(offset as u64),
+            1 => SeekFrom::Current(offset as i64),
+            2 => SeekFrom::End(offset as i64),
+            _ => return -1,
+        };
+
+        file.seek(position).map_or(-1, |position| position as i32)
+    }
+
+    fn fstat(&mut self, memory: &mut GuestMemory, fd: u32, stat: u32) -> i32 {
+        if fd < FIRST_HANDLE {
+            memory.write(stat, &Self::stat_buffer(S_IFCHR | 0o666, 0));
+            return 0;
+        }
+
+        match self.files.get(&fd).map(File::metadata) {
+            Some(Ok(metadata)) => {
+                memory.write(stat, &Self::stat_buffer_from(&metadata));
+                0
+            }
+            _ => -1,
+        }
+    }
+
+    fn stat(&mut self, memory: &mut GuestMemory, path: u32, stat: u32) -> i32 {
+        let metadata = self
+            .resolve(&memory.read_string(path, MAX_PATH))
+            .and_then(|path| fs::metadata(path).ok());
+
+        match metadata {
+            Some(metadata) => {
+                memory.write(stat, &Self::stat_buffer_from(&metadata));
+                0
+            }
+            None => -1,
+        }
+    }
+
+    fn stat_buffer_from(metadata: &fs::Metadata) -> Vec<u8> {
+        let mode = if metadata.is_dir() {
+            S_IFDIR | 0o755
+        } else {
+            S_IFREG | 0o644
+        };
+
+        let mut buffer = Self::stat_buffer(mode, metadata.len() as u32);
+
+        let seconds = |time: io::Result<SystemTime>| {
+            time.ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs() as u32)
+        };
+
+        for (offset, time) in [
+            (20, seconds(metadata.accessed())),
+            (28, seconds(metadata.modified())),
+            (36, seconds(metadata.modified())),
+        ] {
+            buffer[offset..offset + 4].copy_from_slice(&time.to_le_bytes());
+        }
+
+        buffer
+    }
+
+    // kos's dcload_stat_t, the old newlib struct stat. 60 bytes, times and blocks filled in by the caller
+    fn stat_buffer(mode: u32, size: u32) -> Vec<u8> {
+        let mut buffer = vec![0; 60];
+        buffer[4..8].copy_from_slice(&mode.to_le_bytes());
+        buffer[8..10].copy_from_slice(&1_u16.to_le_bytes()); // st_nlink
+        buffer[16..20].copy_from_slice(&size.to_le_bytes());
+        buffer[44..48].copy_from_slice(&512_u32.to_le_bytes()); // st_blksize
+        buffer[48..52].copy_from_slice(&size.div_ceil(512).to_le_bytes());
+        buffer
+    }
+
+    fn opendir(&mut self, memory: &mut GuestMemory, path: u32) -> i32 {
+        let entries = self
+            .resolve(&memory.read_string(path, MAX_PATH))
+            .and_then(|path| fs::read_dir(path).ok());
+
+        let Some(entries) = entries else {
+            return 0; // a null DIR *
+        };
+
+        let mut entries: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect();
+        entries.sort();
+
+        let handle = self.allocate_handle();
+        self.directories.insert(
+            handle,
+            Directory {
+                entries,
+                position: 0,
+            },
+        );
+
+        handle as i32
+    }
+
+    // a pointer to the next entry as a dcload_dirent_t, or null at the end
+    fn readdir(&mut self, memory: &mut GuestMemory, handle: u32) -> i32 {
+        let Some(directory) = self.directories.get_mut(&handle) else {
+            return 0;
+        };
+
+        let Some(name) = directory.entries.get(directory.position) else {
+            return 0;
+        };
+
+        directory.position += 1;
+
+        // d_ino, d_off, d_reclen, d_type, then d_name[256]
+        let size = 11 + 256;
+        let mut dirent = vec![0; size];
+        dirent[0..4].copy_from_slice(&(directory.position as u32).to_le_bytes());
+        dirent[8..10].copy_from_slice(&(size as u16).to_le_bytes());
+
+        let name = &name.as_bytes()[..name.len().min(255)];
+        dirent[11..11 + name.len()].copy_from_slice(name);
+
+        memory.write(DIRENT_ADDR, &dirent);
+        DIRENT_ADDR as i32
+    }
+
+    // sends in_size bytes of packet and waits for up to out_size back. the sizes share a register,
+    // in_size << 16 | out_size. blocks until a debugger connects, the guest's stub has stopped it anyway
+    fn gdbpacket(&mut self, memory: &mut GuestMemory, input: u32, sizes: u32, output: u32) -> i32 {
+        let Some(listener) = &self.gdb else {
+            return -1;
+        };
+
+        if self.gdb_stream.is_none() {
+            println!(
+                "dcload: waiting for gdb on port {}",
+                listener.local_addr().map_or(0, |a| a.port())
+            );
+            match listener.accept() {
+                Ok((stream, _)) => self.gdb_stream = Some(stream),
+                Err(_) => return -1,
+            }
+        }
+
+        let stream = self.gdb_stream.as_mut().unwrap();
+        let (in_size, out_size) = ((sizes >> 16) as usize, (sizes & 0xffff) as usize);
+
+        if in_size > 0 && stream.write_all(&memory.read(input, in_size)).is_err() {
+            self.gdb_stream = None;
+            return -1;
+        }
+
+        if out_size == 0 {
+            return 0;
+        }
+
+        let mut data = vec![0; out_size];
+        match stream.read(&mut data) {
+            Ok(0) | Err(_) => {
+                self.gdb_stream = None;
+                -1
+            }
+            Ok(received) => {
+                memory.write(output, &data[..received]);
+                received as i32
+            }
+        }
+    }
+}
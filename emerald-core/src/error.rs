@@ -0,0 +1,207 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Subsystem {
+    G1Bus,
+    Gdrom,
+    Maple,
+    Ccn,
+    Bsc,
+    BootRom,
+    Framebuffer,
+}
+
+impl fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Subsystem::G1Bus => "g1",
+            Subsystem::Gdrom => "gdrom",
+            Subsystem::Maple => "maple",
+            Subsystem::Ccn => "ccn",
+            Subsystem::Bsc => "bsc",
+            Subsystem::BootRom => "boot rom",
+            Subsystem::Framebuffer => "framebuffer",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+// something the guest did that we can't emulate. device code only knows the subsystem and address,
+// pc and cycle are stamped on by the machine (see at) before it halts.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum EmulatorError {
+    UnknownRead {
+        subsystem: Subsystem,
+        addr: u32,
+        size: u8, // in bits
+        pc: u32,
+        cycle: u64,
+    },
+    UnknownWrite {
+        subsystem: Subsystem,
+        addr: u32,
+        value: u32,
+        size: u8,
+        pc: u32,
+        cycle: u64,
+    },
+    UnimplementedCommand {
+        subsystem: Subsystem,
+        addr: u32, // register or dma address the command came in through
+        command: u32,
+        pc: u32,
+        cycle: u64,
+    },
+    Unsupported {
+        subsystem: Subsystem,
+        addr: u32,
+        reason: String,
+        pc: u32,
+        cycle: u64,
+    },
+}
+
+impl EmulatorError {
+    pub fn unknown_read(subsystem: Subsystem, addr: u32, size: u8) -> Self {
+        EmulatorError::UnknownRead {
+            subsystem,
+            addr,
+            size,
+            pc: 0,
+            cycle: 0,
+        }
+    }
+
+    pub fn unknown_write(subsystem: Subsystem, addr: u32, value: u32, size: u8) -> Self {
+        EmulatorError::UnknownWrite {
+            subsystem,
+            addr,
+            value,
+            size,
+            pc: 0,
+            cycle: 0,
+        }
+    }
+
+    pub fn unimplemented_command(subsystem: Subsystem, addr: u32, command: u32) -> Self {
+        EmulatorError::UnimplementedCommand {
+            subsystem,
+            addr,
+            command,
+            pc: 0,
+            cycle: 0,
+        }
+    }
+
+    pub fn unsupported(subsystem: Subsystem, addr: u32, reason: impl Into<String>) -> Self {
+        EmulatorError::Unsupported {
+            subsystem,
+            addr,
+            reason: reason.into(),
+            pc: 0,
+            cycle: 0,
+        }
+    }
+
+    // stamps the sh4 pc and scheduler cycle the error was raised at
+    pub fn at(mut self, at_pc: u32, at_cycle: u64) -> Self {
+        match &mut self {
+            EmulatorError::UnknownRead { pc, cycle, .. }
+            | EmulatorError::UnknownWrite { pc, cycle, .. }
+            | EmulatorError::UnimplementedCommand { pc, cycle, .. }
+            | EmulatorError::Unsupported { pc, cycle, .. } => {
+                *pc = at_pc;
+                *cycle = at_cycle;
+            }
+        }
+
+        self
+    }
+
+    pub fn subsystem(&self) -> Subsystem {
+        match self {
+            EmulatorError::UnknownRead { subsystem, .. }
+            | EmulatorError::UnknownWrite { subsystem, .. }
+            | EmulatorError::UnimplementedCommand { subsystem, .. }
+            | EmulatorError::Unsupported { subsystem, .. } => *subsystem,
+        }
+    }
+
+    pub fn addr(&self) -> u32 {
+        match self {
+            EmulatorError::UnknownRead { addr, .. }
+            | EmulatorError::UnknownWrite { addr, .. }
+            | EmulatorError::UnimplementedCommand { addr, .. }
+            | EmulatorError::Unsupported { addr, .. } => *addr,
+        }
+    }
+
+    pub fn pc(&self) -> u32 {
+        match self {
+            EmulatorError::UnknownRead { pc, .. }
+            | EmulatorError::UnknownWrite { pc, .. }
+            | EmulatorError::UnimplementedCommand { pc, .. }
+            | EmulatorError::Unsupported { pc, .. } => *pc,
+        }
+    }
+
+    pub fn cycle(&self) -> u64 {
+        match self {
+            EmulatorError::UnknownRead { cycle, .. }
+            | EmulatorError::UnknownWrite { cycle, .. }
+            | EmulatorError::UnimplementedCommand { cycle, .. }
+            | EmulatorError::Unsupported { cycle, .. } => *cycle,
+        }
+    }
+}
+
+impl fmt::Display for EmulatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmulatorError::UnknownRead {
+                subsystem,
+                addr,
+                size,
+                ..
+            } => write!(
+                f,
+                "{}: unknown mmio read ({}-bit) @ 0x{:08x}",
+                subsystem, size, addr
+            ),
+            EmulatorError::UnknownWrite {
+                subsystem,
+                addr,
+                value,
+                size,
+                ..
+            } => write!(
+                f,
+                "{}: unknown mmio write ({}-bit) @ 0x{:08x} with value 0x{:08x}",
+                subsystem, size, addr, value
+            ),
+            EmulatorError::UnimplementedCommand {
+                subsystem,
+                addr,
+                command,
+                ..
+            } => write!(
+                f,
+                "{}: unimplemented command 0x{:02x} @ 0x{:08x}",
+                subsystem, command, addr
+            ),
+            EmulatorError::Unsupported {
+                subsystem,
+                addr,
+                reason,
+                ..
+            } => write!(f, "{}: {} @ 0x{:08x}", subsystem, reason, addr),
+        }?;
+
+        write!(f, " (pc 0x{:08x}, cycle {})", self.pc(), self.cycle())
+    }
+}
+
+impl std::error::Error for EmulatorError {}
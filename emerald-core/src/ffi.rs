@@ -1,14 +1,470 @@
-use crate::emulator::Emulator;
+// c abi for embedding the core in non-rust hosts. include/emerald.h is generated from this file, after a change
+// here run `cbindgen --config cbindgen.toml --output include/emerald.h src/ffi.rs` in emerald-core and commit the
+// header with it. cargo test fails while the two disagree.
+//
+// every function takes the handle returned by emerald_create. functions returning int32_t return
+// EMERALD_OK on success, or EMERALD_ERROR with a message available from emerald_last_error.
+// pointers must be null or valid for the lengths passed alongside them, strings are nul-terminated utf-8.
 
-/*
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    ptr, slice,
+};
+
+use crate::{
+    config::ConfigError,
+    hw::holly::g1::gdi::GdiParser,
+    machine::{Machine, MachineEvent},
+    ControllerButton, EmulatorFrontendRequest,
+};
+
+pub const EMERALD_OK: i32 = 0;
+pub const EMERALD_ERROR: i32 = -1;
+/// the machine is paused, stopped on something it can't emulate or exited, emerald_last_error says which
+pub const EMERALD_HALTED: i32 = 1;
+
+pub const EMERALD_BUTTON_A: u32 = 0;
+pub const EMERALD_BUTTON_B: u32 = 1;
+pub const EMERALD_BUTTON_X: u32 = 2;
+pub const EMERALD_BUTTON_Y: u32 = 3;
+pub const EMERALD_BUTTON_START: u32 = 4;
+pub const EMERALD_BUTTON_LEFT: u32 = 5;
+pub const EMERALD_BUTTON_RIGHT: u32 = 6;
+pub const EMERALD_BUTTON_UP: u32 = 7;
+pub const EMERALD_BUTTON_DOWN: u32 = 8;
+
+// opaque to c
+pub struct EmeraldHandle {
+    machine: Machine,
+    framebuffer: Vec<u8>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    audio: Vec<i16>,
+    last_error: CString,
+}
+
+impl EmeraldHandle {
+    fn set_last_error(&mut self, message: String) {
+        // interior nuls can't go through a c string
+        self.last_error = CString::new(message.replace('\0', " ")).unwrap();
+    }
+
+    fn fail(&mut self, message: String) -> i32 {
+        self.set_last_error(message);
+        EMERALD_ERROR
+    }
+
+    fn fail_config(&mut self, errors: Vec<ConfigError>) -> i32 {
+        let message = errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        self.fail(message)
+    }
+
+    // device code still panics on a lot of unhandled accesses, don't let that unwind into the host
+    fn guard<F: FnOnce(&mut Self) -> i32>(&mut self, f: F) -> i32 {
+        match panic::catch_unwind(AssertUnwindSafe(|| f(self))) {
+            Ok(status) => status,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "unknown panic".to_owned());
+
+                self.fail(format!("emulator panicked: {}", message))
+            }
+        }
+    }
+}
+
+unsafe fn handle_mut<'a>(handle: *mut EmeraldHandle) -> Option<&'a mut EmeraldHandle> {
+    handle.as_mut()
+}
+
+unsafe fn path_arg(path: *const c_char) -> Result<PathBuf, String> {
+    if path.is_null() {
+        return Err("path is null".to_owned());
+    }
+
+    CStr::from_ptr(path)
+        .to_str()
+        .map(PathBuf::from)
+        .map_err(|_| "path is not valid utf-8".to_owned())
+}
+
+unsafe fn buffer_arg<'a>(data: *const u8, len: usize) -> &'a [u8] {
+    if data.is_null() || len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    }
+}
+
+fn button_arg(button: u32) -> Option<ControllerButton> {
+    Some(match button {
+        EMERALD_BUTTON_A => ControllerButton::A,
+        EMERALD_BUTTON_B => ControllerButton::B,
+        EMERALD_BUTTON_X => ControllerButton::X,
+        EMERALD_BUTTON_Y => ControllerButton::Y,
+        EMERALD_BUTTON_START => ControllerButton::Start,
+        EMERALD_BUTTON_LEFT => ControllerButton::Left,
+        EMERALD_BUTTON_RIGHT => ControllerButton::Right,
+        EMERALD_BUTTON_UP => ControllerButton::Up,
+        EMERALD_BUTTON_DOWN => ControllerButton::Down,
+        _ => return None,
+    })
+}
+
+/// a machine with no bios loaded. load one with emerald_load_bios(_buffer) before running
 #[no_mangle]
-pub extern "C" fn emulator_alloc() -> *mut EmulatorHandle {
-    let emulator = Arc::new(Mutex::new(Emulator::new()));
-    let bus = Arc::new(Mutex::new(CpuBus::new()));
-    Box::into_raw(Box::new(EmulatorHandle {
-        emulator,
-        bus,
-        receiver: ptr::null_mut(),
+pub extern "C" fn emerald_create() -> *mut EmeraldHandle {
+    Box::into_raw(Box::new(EmeraldHandle {
+        machine: Machine::new(),
+        framebuffer: Vec::new(),
+        framebuffer_width: 0,
+        framebuffer_height: 0,
+        audio: Vec::new(),
+        last_error: CString::default(),
     }))
 }
-*/
+
+#[no_mangle]
+pub unsafe extern "C" fn emerald_destroy(handle: *mut EmeraldHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// the message for the last EMERALD_ERROR, valid until the next call on this handle
+#[no_mangle]
+pub unsafe extern "C" fn emerald_last_error(handle: *const EmeraldHandle) -> *const c_char {
+    match handle.as_ref() {
+        Some(h) => h.last_error.as_ptr(),
+        None => ptr::null(),
+    }
+}
+
+/// checksums are verified against known dumps unless verify is false
+#[no_mangle]
+pub unsafe extern "C" fn emerald_set_verify_checksums(handle: *mut EmeraldHandle, verify: bool) {
+    if let Some(h) = handle_mut(handle) {
+        h.machine.config.verify_checksums = verify;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn emerald_load_bios(
+    handle: *mut EmeraldHandle,
+    bios_path: *const c_char,
+    flash_path: *const c_char,
+) -> i32 {
+    let Some(h) = handle_mut(handle) else {
+        return EMERALD_ERROR;
+    };
+
+    let paths = path_arg(bios_path).and_then(|bios| Ok((bios, path_arg(flash_path)?)));
+    let (bios_path, flash_path) = match paths {
+        Ok(paths) => paths,
+        Err(message) => return h.fail(message),
+    };
+
+    let mut errors = Vec::new();
+    let bios = std::fs::read(&bios_path)
+        .map_err(|error| {
+            errors.push(ConfigError::Io {
+                path: bios_path.clone(),
+                error,
+            })
+        })
+        .ok();
+    let flash = std::fs::read(&flash_path)
+        .map_err(|error| {
+            errors.push(ConfigError::Io {
+                path: flash_path.clone(),
+                error,
+            })
+        })
+        .ok();
+
+    let (Some(bios), Some(flash)) = (bios, flash) else {
+        return h.fail_config(errors);
+    };
+
+    h.machine.config.bios_path = bios_path;
+    h.machine.config.flash_path = flash_path;
+
+    match h.machine.load_roms(bios, flash) {
+        Ok(()) => EMERALD_OK,
+        Err(errors) => h.fail_config(errors),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn emerald_load_bios_buffer(
+    handle: *mut EmeraldHandle,
+    bios: *const u8,
+    bios_len: usize,
+    flash: *const u8,
+    flash_len: usize,
+) -> i32 {
+    let Some(h) = handle_mut(handle) else {
+        return EMERALD_ERROR;
+    };
+
+    h.machine.config.bios_path = PathBuf::from("<bios buffer>");
+    h.machine.config.flash_path = PathBuf::from("<flash buffer>");
+
+    match h.machine.load_roms(
+        buffer_arg(bios, bios_len).to_vec(),
+        buffer_arg(flash, flash_len).to_vec(),
+    ) {
+        Ok(()) => EMERALD_OK,
+        Err(errors) => h.fail_config(errors),
+    }
+}
+
+/// a .gdi sheet, its tracks are read relative to it
+#[no_mangle]
+pub unsafe extern "C" fn emerald_load_disc(
+    handle: *mut EmeraldHandle,
+    gdi_path: *const c_char,
+) -> i32 {
+    let Some(h) = handle_mut(handle) else {
+        return EMERALD_ERROR;
+    };
+
+    match path_arg(gdi_path) {
+        Ok(path) => h.guard(|h| match h.machine.load_disc(&path) {
+            Ok(()) => EMERALD_OK,
+            Err(e) => h.fail(e.to_string()),
+        }),
+        Err(message) => h.fail(message),
+    }
+}
+
+/// the contents of a .gdi sheet plus one buffer per track, in the order the sheet lists them.
+/// the buffers are copied, the caller keeps ownership
+#[no_mangle]
+pub unsafe extern "C" fn emerald_load_disc_buffer(
+    handle: *mut EmeraldHandle,
+    gdi: *const c_char,
+    tracks: *const *const u8,
+    track_lens: *const usize,
+    track_count: usize,
+) -> i32 {
+    let Some(h) = handle_mut(handle) else {
+        return EMERALD_ERROR;
+    };
+
+    if gdi.is_null() || (track_count > 0 && (tracks.is_null() || track_lens.is_null())) {
+        return h.fail("gdi or track list is null".to_owned());
+    }
+
+    let Ok(gdi) = CStr::from_ptr(gdi).to_str() else {
+        return h.fail("gdi is not valid utf-8".to_owned());
+    };
+
+    let tracks = slice::from_raw_parts(tracks, track_count);
+    let track_lens = slice::from_raw_parts(track_lens, track_count);
+
+    h.guard(|h| {
+        let mut missing = None;
+        let gdi_image = GdiParser::load_from_str(gdi, |index, track_file| {
+            if index >= track_count {
+                missing.get_or_insert_with(|| track_file.to_owned());
+                return Vec::new();
+            }
+
+            buffer_arg(tracks[index], track_lens[index]).to_vec()
+        });
+
+        if let Some(track_file) = missing {
+            return h.fail(format!("no buffer passed for track {}", track_file));
+        }
+
+        h.machine.insert_disc(gdi_image);
+        EMERALD_OK
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn emerald_load_elf(
+    handle: *mut EmeraldHandle,
+    elf_path: *const c_char,
+) -> i32 {
+    let Some(h) = handle_mut(handle) else {
+        return EMERALD_ERROR;
+    };
+
+    match path_arg(elf_path) {
+        Ok(path) => h.guard(|h| match h.machine.load_elf(&path) {
+            Ok(()) => EMERALD_OK,
+            Err(e) => h.fail(e.to_string()),
+        }),
+        Err(message) => h.fail(message),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn emerald_load_elf_buffer(
+    handle: *mut EmeraldHandle,
+    elf: *const u8,
+    elf_len: usize,
+) -> i32 {
+    let Some(h) = handle_mut(handle) else {
+        return EMERALD_ERROR;
+    };
+
+    let elf = buffer_arg(elf, elf_len);
+    h.guard(
+        |h| match h.machine.load_elf_bytes(elf, Path::new("<elf buffer>")) {
+            Ok(()) => EMERALD_OK,
+            Err(e) => h.fail(e.to_string()),
+        },
+    )
+}
+
+/// runs until the next vblank
+#[no_mangle]
+pub unsafe extern "C" fn emerald_run_frame(handle: *mut EmeraldHandle) -> i32 {
+    let Some(h) = handle_mut(handle) else {
+        return EMERALD_ERROR;
+    };
+
+    h.guard(|h| {
+        let mut status = EMERALD_OK;
+
+        for event in h.machine.run_frame() {
+            match event {
+                MachineEvent::BlitFramebuffer(rgba, width, height) => {
+                    h.framebuffer = rgba;
+                    h.framebuffer_width = width;
+                    h.framebuffer_height = height;
+                }
+                // fixme: the hw rasterizer needs a window, so ta renders aren't available here yet.
+                // tell the guest we're done so it doesn't stall waiting on the end-of-render interrupts
+                MachineEvent::FrameReady(_) => {
+                    h.machine
+                        .queue_input(EmulatorFrontendRequest::RenderingDone);
+                }
+                MachineEvent::Error(error) => {
+                    h.set_last_error(error.to_string());
+                }
+                MachineEvent::Exited(code) => {
+                    h.set_last_error(format!("exited with status {}", code));
+                }
+                MachineEvent::Halted => status = EMERALD_HALTED,
+                MachineEvent::VBlank | MachineEvent::DebugStop(_) => {}
+            }
+        }
+
+        status
+    })
+}
+
+/// the last frame scanned out of the framebuffer as rgba8888, or null if there hasn't been one yet.
+/// valid until the next emerald_run_frame or emerald_destroy
+#[no_mangle]
+pub unsafe extern "C" fn emerald_get_framebuffer(
+    handle: *const EmeraldHandle,
+    width: *mut u32,
+    height: *mut u32,
+) -> *const u8 {
+    let Some(h) = handle.as_ref() else {
+        return ptr::null();
+    };
+
+    if !width.is_null() {
+        *width = h.framebuffer_width;
+    }
+
+    if !height.is_null() {
+        *height = h.framebuffer_height;
+    }
+
+    if h.framebuffer.is_empty() {
+        ptr::null()
+    } else {
+        h.framebuffer.as_ptr()
+    }
+}
+
+/// button is one of the EMERALD_BUTTON_* values
+#[no_mangle]
+pub unsafe extern "C" fn emerald_set_button(
+    handle: *mut EmeraldHandle,
+    button: u32,
+    pressed: bool,
+) -> i32 {
+    let Some(h) = handle_mut(handle) else {
+        return EMERALD_ERROR;
+    };
+
+    let Some(button) = button_arg(button) else {
+        return h.fail(format!("unknown button {}", button));
+    };
+
+    h.machine.queue_input(if pressed {
+        EmulatorFrontendRequest::ButtonPressed(button)
+    } else {
+        EmulatorFrontendRequest::ButtonReleased(button)
+    });
+
+    EMERALD_OK
+}
+
+/// copies up to max_frames interleaved stereo s16 frames at 44100hz into samples and returns how many were
+/// written. anything that doesn't fit is kept for the next call
+#[no_mangle]
+pub unsafe extern "C" fn emerald_get_audio_samples(
+    handle: *mut EmeraldHandle,
+    samples: *mut i16,
+    max_frames: usize,
+) -> usize {
+    let Some(h) = handle_mut(handle) else {
+        return 0;
+    };
+
+    if samples.is_null() {
+        return 0;
+    }
+
+    h.machine.drain_audio(&mut h.audio);
+
+    let frames = (h.audio.len() / 2).min(max_frames);
+    ptr::copy_nonoverlapping(h.audio.as_ptr(), samples, frames * 2);
+    h.audio.drain(..frames * 2);
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn header_is_current() {
+        let crate_dir = env!("CARGO_MANIFEST_DIR");
+        let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir)).unwrap();
+
+        let mut generated = Vec::new();
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{}/src/ffi.rs", crate_dir))
+            .generate()
+            .unwrap()
+            .write(&mut generated);
+
+        let checked_in = std::fs::read(format!("{}/include/emerald.h", crate_dir)).unwrap();
+        assert!(
+            generated == checked_in,
+            "include/emerald.h is out of date with src/ffi.rs, regenerate it with cbindgen"
+        );
+    }
+}
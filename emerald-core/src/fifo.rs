@@ -1,8 +1,9 @@
 use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
 
 const DEFAULT_FIFO_SIZE: usize = 16;
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Fifo<T, const N: usize = DEFAULT_FIFO_SIZE> {
     queue: VecDeque<T>,
 }
@@ -0,0 +1,228 @@
+// the aica's arm7di. gdb's arm layout puts the fpa registers at 16-24 and cpsr at 25, we have no fpa so
+// the target description leaves them out. the banked copies of r8-r14 and the spsrs follow from 26.
+//
+// addresses are the arm's view of the world, wave ram at 0 and the aica registers from 0x800000.
+
+use crate::{
+    emulator::EmulatorState,
+    hw::holly::g2::aica::{arm::Cpu, arm_bus::ArmBus},
+    machine::{Machine, MachineEvent},
+};
+
+const PC: usize = 15;
+const CPSR: usize = 25;
+const LAST_BANKED: usize = 46;
+
+const REGISTER_SPACE: u32 = 0x800000;
+
+pub const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <architecture>arm</architecture>
+  <feature name="org.gnu.gdb.arm.core">
+    <reg name="r0" bitsize="32" regnum="0"/>
+    <reg name="r1" bitsize="32"/>
+    <reg name="r2" bitsize="32"/>
+    <reg name="r3" bitsize="32"/>
+    <reg name="r4" bitsize="32"/>
+    <reg name="r5" bitsize="32"/>
+    <reg name="r6" bitsize="32"/>
+    <reg name="r7" bitsize="32"/>
+    <reg name="r8" bitsize="32"/>
+    <reg name="r9" bitsize="32"/>
+    <reg name="r10" bitsize="32"/>
+    <reg name="r11" bitsize="32"/>
+    <reg name="r12" bitsize="32"/>
+    <reg name="sp" bitsize="32" type="data_ptr"/>
+    <reg name="lr" bitsize="32"/>
+    <reg name="pc" bitsize="32" type="code_ptr"/>
+    <reg name="cpsr" bitsize="32" regnum="25"/>
+  </feature>
+  <feature name="org.emerald.arm7.banked">
+    <reg name="r8_usr" bitsize="32" regnum="26" group="banked"/>
+    <reg name="r9_usr" bitsize="32" group="banked"/>
+    <reg name="r10_usr" bitsize="32" group="banked"/>
+    <reg name="r11_usr" bitsize="32" group="banked"/>
+    <reg name="r12_usr" bitsize="32" group="banked"/>
+    <reg name="r13_usr" bitsize="32" group="banked"/>
+    <reg name="r14_usr" bitsize="32" group="banked"/>
+    <reg name="r8_fiq" bitsize="32" group="banked"/>
+    <reg name="r9_fiq" bitsize="32" group="banked"/>
+    <reg name="r10_fiq" bitsize="32" group="banked"/>
+    <reg name="r11_fiq" bitsize="32" group="banked"/>
+    <reg name="r12_fiq" bitsize="32" group="banked"/>
+    <reg name="r13_fiq" bitsize="32" group="banked"/>
+    <reg name="r14_fiq" bitsize="32" group="banked"/>
+    <reg name="r13_irq" bitsize="32" group="banked"/>
+    <reg name="r14_irq" bitsize="32" group="banked"/>
+    <reg name="r13_svc" bitsize="32" group="banked"/>
+    <reg name="r14_svc" bitsize="32" group="banked"/>
+    <reg name="spsr_fiq" bitsize="32" group="banked"/>
+    <reg name="spsr_irq" bitsize="32" group="banked"/>
+    <reg name="spsr_svc" bitsize="32" group="banked"/>
+  </feature>
+</target>
+"#;
+
+// the order the 'g' packet carries them in, the same as the description above
+pub fn registers() -> impl Iterator<Item = usize> {
+    (0..=PC).chain(CPSR..=LAST_BANKED)
+}
+
+// none while the arm is held in reset, it isn't executing anything to stop at
+pub fn pc(machine: &Machine) -> Option<u32> {
+    let cpu = &machine.bus.holly.arm7tdmi;
+    cpu.running.then(|| cpu.registers.r15.wrapping_sub(8))
+}
+
+// the sh4 clocks the arm, so this is up to 8 sh4 instructions. breakpoints are checked before the arm's
+// instruction the same way Machine::step_instruction does for the sh4's
+pub fn step(machine: &mut Machine) -> Vec<MachineEvent> {
+    let mut events = Vec::new();
+
+    if let Some(pc) = pc(machine) {
+        if let Some(stop) = machine.bus.holly.arm7_debugger.check_breakpoint(pc) {
+            machine.state = EmulatorState::Paused;
+            events.push(MachineEvent::DebugStop(stop));
+            return events;
+        }
+    }
+
+    loop {
+        let step_events = machine.step_instruction();
+        let halted = step_events.contains(&MachineEvent::Halted);
+        events.extend(step_events);
+
+        if halted || machine.bus.holly.arm7tdmi.at_instruction_boundary() {
+            return events;
+        }
+    }
+}
+
+fn banked_register(cpu: &mut Cpu, n: usize) -> Option<&mut u32> {
+    let registers = &mut cpu.registers;
+    let register = match n {
+        26 => &mut registers.r8,
+        27 => &mut registers.r9,
+        28 => &mut registers.r10,
+        29 => &mut registers.r11,
+        30 => &mut registers.r12,
+        31 => &mut registers.r13,
+        32 => &mut registers.r14,
+        33 => &mut registers.r8_fiq,
+        34 => &mut registers.r9_fiq,
+        35 => &mut registers.r10_fiq,
+        36 => &mut registers.r11_fiq,
+        37 => &mut registers.r12_fiq,
+        38 => &mut registers.r13_fiq,
+        39 => &mut registers.r14_fiq,
+        40 => &mut registers.r13_irq,
+        41 => &mut registers.r14_irq,
+        42 => &mut registers.r13_svc,
+        43 => &mut registers.r14_svc,
+        44 => &mut registers.spsr_fiq,
+        45 => &mut registers.spsr_irq,
+        46 => &mut registers.spsr_svc,
+        _ => return None,
+    };
+
+    Some(register)
+}
+
+pub fn read_register(machine: &mut Machine, n: usize) -> Option<u32> {
+    let cpu = &mut machine.bus.holly.arm7tdmi;
+    match n {
+        0..=14 => Some(cpu.get_register_by_index(n)),
+        PC => Some(cpu.registers.r15.wrapping_sub(8)),
+        CPSR => Some(cpu.registers.cpsr),
+        _ => banked_register(cpu, n).map(|register| *register),
+    }
+}
+
+// r8-r14 land in whichever bank the current mode uses, the same as an instruction writing them
+pub fn write_register(machine: &mut Machine, n: usize, value: u32) -> bool {
+    let holly = &mut machine.bus.holly;
+    let cpu = &mut holly.arm7tdmi;
+
+    match n {
+        0..=14 => cpu.set_register_by_index(n, value),
+        PC => cpu.set_pc(
+            value,
+            &ArmBus {
+                aica: &mut holly.aica,
+            },
+        ),
+        CPSR => cpu.registers.cpsr = value,
+        _ => match banked_register(cpu, n) {
+            Some(register) => *register = value,
+            None => return false,
+        },
+    }
+
+    true
+}
+
+// cpsr first, it picks the bank r8-r14 go to
+pub fn write_registers(machine: &mut Machine, values: &[u32]) {
+    let values: Vec<(usize, u32)> = registers().zip(values.iter().copied()).collect();
+
+    for (n, value) in values.iter().filter(|(n, _)| *n == CPSR) {
+        write_register(machine, *n, *value);
+    }
+
+    for (n, value) in values.iter().filter(|(n, _)| *n != CPSR) {
+        write_register(machine, *n, *value);
+    }
+}
+
+pub fn read_memory(machine: &mut Machine, addr: u32, len: usize) -> Option<Vec<u8>> {
+    let bus = ArmBus {
+        aica: &mut machine.bus.holly.aica,
+    };
+
+    let mut bytes = Vec::with_capacity(len);
+    let end = addr.checked_add(len as u32)?;
+    let mut current = addr;
+
+    while current < end {
+        if current & 3 == 0 && end - current >= 4 {
+            bytes.extend(bus.read_32(current).to_le_bytes());
+            current += 4;
+        } else {
+            bytes.push(bus.read_8(current));
+            current += 1;
+        }
+    }
+
+    Some(bytes)
+}
+
+// wave ram takes anything, the registers only whole words
+pub fn write_memory(machine: &mut Machine, addr: u32, bytes: &[u8]) -> bool {
+    let mut bus = ArmBus {
+        aica: &mut machine.bus.holly.aica,
+    };
+
+    if addr < REGISTER_SPACE {
+        if addr as usize + bytes.len() > REGISTER_SPACE as usize {
+            return false;
+        }
+
+        for (offset, byte) in bytes.iter().enumerate() {
+            bus.write_8(addr + offset as u32, *byte);
+        }
+
+        return true;
+    }
+
+    if addr & 3 != 0 || bytes.len() % 4 != 0 {
+        return false;
+    }
+
+    for (offset, word) in bytes.chunks_exact(4).enumerate() {
+        let value = u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        bus.write_32(addr.wrapping_add(offset as u32 * 4), value);
+    }
+
+    true
+}
@@ -0,0 +1,632 @@
+// gdb remote serial protocol stub, `target remote :2159` from sh-elf-gdb (or arm-none-eabi-gdb for the
+// aica's arm7, see GdbTarget).
+//
+// the stub sits between the run loop and the machine. while the debugger has the target stopped the
+// run loop blocks here servicing packets, when it's running we step instruction by instruction so
+// breakpoints land exactly and poll the socket for ctrl-c between timeslices. breakpoints and watchpoints
+// live in the target cpu's Debugger, the stub only reports the stops it raises.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    emulator::EmulatorState,
+    hw::sh4::debug::{AddressSpace, DebugStop, Debugger, WatchAction, WatchId, Watchpoint},
+    machine::{Machine, MachineEvent},
+};
+
+pub mod arm7;
+pub mod sh4;
+
+pub const GDB_DEFAULT_PORT: u16 = 2159;
+
+const SIGINT: u8 = 2;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// which cpu the debugger drives. the other one keeps running in lockstep underneath it
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum GdbTarget {
+    Sh4,
+    Arm7,
+}
+
+impl FromStr for GdbTarget {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sh4" => Ok(GdbTarget::Sh4),
+            "arm7" | "arm" => Ok(GdbTarget::Arm7),
+            _ => Err(()),
+        }
+    }
+}
+
+impl GdbTarget {
+    // where the next instruction is, none if the cpu isn't executing
+    fn pc(&self, machine: &Machine) -> Option<u32> {
+        match self {
+            GdbTarget::Sh4 => Some(machine.cpu.registers.current_pc),
+            GdbTarget::Arm7 => arm7::pc(machine),
+        }
+    }
+
+    fn set_pc(&self, machine: &mut Machine, pc: u32) {
+        match self {
+            GdbTarget::Sh4 => machine.cpu.registers.current_pc = pc,
+            GdbTarget::Arm7 => {
+                arm7::write_register(machine, 15, pc);
+            }
+        }
+    }
+
+    // one instruction of this cpu, and whatever the rest of the machine does meanwhile
+    fn step(&self, machine: &mut Machine) -> Vec<MachineEvent> {
+        match self {
+            GdbTarget::Sh4 => machine.step_instruction(),
+            GdbTarget::Arm7 => arm7::step(machine),
+        }
+    }
+
+    // the registers the 'g' packet carries, in order
+    fn registers(&self) -> Vec<usize> {
+        match self {
+            GdbTarget::Sh4 => (0..sh4::REGISTER_COUNT).collect(),
+            GdbTarget::Arm7 => arm7::registers().collect(),
+        }
+    }
+
+    fn read_register(&self, machine: &mut Machine, n: usize) -> Option<u32> {
+        match self {
+            GdbTarget::Sh4 => sh4::read_register(machine, n),
+            GdbTarget::Arm7 => arm7::read_register(machine, n),
+        }
+    }
+
+    fn write_register(&self, machine: &mut Machine, n: usize, value: u32) -> bool {
+        match self {
+            GdbTarget::Sh4 => sh4::write_register(machine, n, value),
+            GdbTarget::Arm7 => arm7::write_register(machine, n, value),
+        }
+    }
+
+    fn write_registers(&self, machine: &mut Machine, values: &[u32]) {
+        match self {
+            GdbTarget::Sh4 => sh4::write_registers(machine, values),
+            GdbTarget::Arm7 => arm7::write_registers(machine, values),
+        }
+    }
+
+    fn read_memory(&self, machine: &mut Machine, addr: u32, len: usize) -> Option<Vec<u8>> {
+        match self {
+            GdbTarget::Sh4 => sh4::read_memory(machine, addr, len),
+            GdbTarget::Arm7 => arm7::read_memory(machine, addr, len),
+        }
+    }
+
+    fn write_memory(&self, machine: &mut Machine, addr: u32, bytes: &[u8]) -> bool {
+        match self {
+            GdbTarget::Sh4 => sh4::write_memory(machine, addr, bytes),
+            GdbTarget::Arm7 => arm7::write_memory(machine, addr, bytes),
+        }
+    }
+
+    // where this cpu's breakpoints go
+    fn debugger<'a>(&self, machine: &'a mut Machine) -> &'a mut Debugger {
+        match self {
+            GdbTarget::Sh4 => &mut machine.bus.debugger,
+            GdbTarget::Arm7 => &mut machine.bus.holly.arm7_debugger,
+        }
+    }
+
+    // gdb's z2 (write), z3 (read) and z4 (access). only the sh4 bus has watchpoints
+    fn add_watchpoint(
+        &self,
+        machine: &mut Machine,
+        kind: u8,
+        addr: u32,
+        len: u32,
+    ) -> Option<WatchId> {
+        match self {
+            GdbTarget::Sh4 => Some(machine.bus.debugger.add_watchpoint(Watchpoint {
+                space: AddressSpace::Logical,
+                range: addr..=addr.saturating_add(len.max(1) - 1),
+                read: kind != 2,
+                write: kind != 3,
+                value: None,
+                action: WatchAction::Stop,
+            })),
+            GdbTarget::Arm7 => None,
+        }
+    }
+
+    // gdb knows the sh4's layout already, the arm's has gaps it needs telling about
+    fn target_xml(&self) -> Option<&'static str> {
+        match self {
+            GdbTarget::Sh4 => None,
+            GdbTarget::Arm7 => Some(arm7::TARGET_XML),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum StubState {
+    Stopped,
+    Running,
+}
+
+pub struct GdbStub {
+    target: GdbTarget,
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    state: StubState,
+    watchpoints: BTreeMap<(u8, u32, u32), WatchId>, // keyed by gdb's kind, addr and length
+}
+
+impl GdbStub {
+    pub fn listen(port: u16, target: GdbTarget) -> io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            target,
+            listener,
+            stream: None,
+            state: StubState::Stopped,
+            watchpoints: BTreeMap::new(),
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.listener.local_addr().map(|a| a.port()).unwrap_or(0)
+    }
+
+    // blocks until a debugger connects, the machine starts out stopped so nothing runs before then
+    pub fn wait_for_client(&mut self) -> io::Result<()> {
+        self.listener.set_nonblocking(false)?;
+        let (stream, _) = self.listener.accept()?;
+        self.listener.set_nonblocking(true)?;
+
+        self.attach(stream)
+    }
+
+    // picks up a debugger that connected while the machine was free running
+    pub fn poll_client(&mut self) -> bool {
+        if self.stream.is_none() {
+            if let Ok((stream, _)) = self.listener.accept() {
+                if self.attach(stream).is_err() {
+                    self.stream = None;
+                }
+            }
+        }
+
+        self.stream.is_some()
+    }
+
+    fn attach(&mut self, stream: TcpStream) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_nodelay(true)?;
+
+        println!("gdb: debugger attached");
+        self.stream = Some(stream);
+        self.state = StubState::Stopped;
+        Ok(())
+    }
+
+    fn detach(&mut self, machine: &mut Machine) {
+        if self.stream.take().is_some() {
+            println!("gdb: debugger detached");
+        }
+
+        for (_, id) in std::mem::take(&mut self.watchpoints) {
+            machine.bus.debugger.remove_watchpoint(id);
+        }
+
+        self.target.debugger(machine).clear_breakpoints();
+        self.state = StubState::Running;
+    }
+
+    pub fn attached(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    // runs the machine for up to `cycles` on behalf of the debugger. returns early whenever the target
+    // stops, and while it's stopped this only services one packet per call.
+    pub fn run(&mut self, machine: &mut Machine, cycles: u64) -> Vec<MachineEvent> {
+        if !self.poll_client() {
+            return machine.run_cycles(cycles);
+        }
+
+        let result = match self.state {
+            StubState::Stopped => self.service_packet(machine),
+            StubState::Running => self.resume(machine, cycles),
+        };
+
+        match result {
+            Ok(events) => events,
+            Err(e) => {
+                println!("gdb: connection lost, {}", e);
+                self.detach(machine);
+                Vec::new()
+            }
+        }
+    }
+
+    fn resume(&mut self, machine: &mut Machine, cycles: u64) -> io::Result<Vec<MachineEvent>> {
+        let mut events = Vec::new();
+        let target = machine.total_cycles + cycles;
+
+        while machine.total_cycles < target {
+            let step_events = self.target.step(machine);
+            let halted = self.report_halt(&step_events)?;
+            events.extend(step_events);
+
+            if halted {
+                return Ok(events);
+            }
+        }
+
+        if self.interrupted()? {
+            self.stop(SIGINT)?;
+        }
+
+        Ok(events)
+    }
+
+    // the machine stopped by itself (an emulation error, or the frontend paused it)
+    fn report_halt(&mut self, events: &[MachineEvent]) -> io::Result<bool> {
+        for event in events {
+            if let MachineEvent::Error(error) = event {
+                self.send_packet(&format!(
+                    "O{}",
+                    hex_encode(format!("{}\n", error).as_bytes())
+                ))?;
+                self.stop(SIGSEGV)?;
+                return Ok(true);
+            }
+        }
+
+        for event in events {
+            if let MachineEvent::DebugStop(stop) = event {
+                self.state = StubState::Stopped;
+                let reply = match stop {
+                    DebugStop::Watchpoint { id, access } => {
+                        let kind = self.watchpoints.iter().find(|(_, w)| *w == id);
+                        let name = match kind.map(|((kind, _, _), _)| *kind) {
+                            Some(3) => "rwatch",
+                            Some(4) => "awatch",
+                            _ => "watch",
+                        };
+
+                        format!("T{:02x}{}:{:x};", SIGTRAP, name, access.addr)
+                    }
+                    DebugStop::Breakpoint { .. } => format!("T{:02x}swbreak:;", SIGTRAP),
+                };
+
+                self.send_packet(&reply)?;
+                return Ok(true);
+            }
+        }
+
+        if events.contains(&MachineEvent::Halted) {
+            self.stop(SIGINT)?;
+            return Ok(true);
+        }
+
+        Ok(false)
+    }
+
+    fn stop(&mut self, signal: u8) -> io::Result<()> {
+        self.state = StubState::Stopped;
+        self.send_packet(&format!("S{:02x}", signal))
+    }
+
+    // ctrl-c arrives as a bare 0x03 outside of any packet
+    fn interrupted(&mut self) -> io::Result<bool> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(false);
+        };
+
+        stream.set_nonblocking(true)?;
+        let mut byte = [0u8; 1];
+        let result = stream.read(&mut byte);
+        stream.set_nonblocking(false)?;
+
+        match result {
+            Ok(0) => Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(_) => Ok(byte[0] == 0x03),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<u8> {
+        let Some(stream) = &mut self.stream else {
+            return Err(io::ErrorKind::NotConnected.into());
+        };
+
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    // $<data>#<checksum>, acked with + (or nacked with - on a bad checksum)
+    fn read_packet(&mut self) -> io::Result<String> {
+        loop {
+            while self.read_byte()? != b'$' {}
+
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    b'#' => break,
+                    b => data.push(b),
+                }
+            }
+
+            let checksum = [self.read_byte()?, self.read_byte()?];
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|c| u8::from_str_radix(c, 16).ok());
+
+            let stream = self.stream.as_mut().unwrap();
+            if expected == Some(checksum_of(&data)) {
+                stream.write_all(b"+")?;
+                return Ok(String::from_utf8_lossy(&data).into_owned());
+            }
+
+            stream.write_all(b"-")?;
+        }
+    }
+
+    fn send_packet(&mut self, data: &str) -> io::Result<()> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+
+        let packet = format!("${}#{:02x}", data, checksum_of(data.as_bytes()));
+        stream.write_all(packet.as_bytes())?;
+
+        // wait for the ack, anything other than a nack means it got there
+        loop {
+            match self.read_byte()? {
+                b'-' => self.stream.as_mut().unwrap().write_all(packet.as_bytes())?,
+                b'+' => return Ok(()),
+                _ => {}
+            }
+        }
+    }
+
+    fn service_packet(&mut self, machine: &mut Machine) -> io::Result<Vec<MachineEvent>> {
+        let packet = self.read_packet()?;
+        let mut events = Vec::new();
+
+        let (command, args) = packet.split_at(packet.len().min(1));
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => self
+                .target
+                .registers()
+                .into_iter()
+                .map(|n| self.target.read_register(machine, n).unwrap_or(0))
+                .map(|value| hex_encode(&value.to_le_bytes()))
+                .collect(),
+            "G" => match hex_decode(args) {
+                Some(bytes) => {
+                    let values: Vec<u32> = bytes
+                        .chunks_exact(4)
+                        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                        .collect();
+
+                    self.target.write_registers(machine, &values);
+                    "OK".to_owned()
+                }
+                None => "E01".to_owned(),
+            },
+            "p" => match usize::from_str_radix(args, 16)
+                .ok()
+                .and_then(|n| self.target.read_register(machine, n))
+            {
+                Some(value) => hex_encode(&value.to_le_bytes()),
+                None => "E01".to_owned(),
+            },
+            "P" => {
+                let parsed = args.split_once('=').and_then(|(n, value)| {
+                    let n = usize::from_str_radix(n, 16).ok()?;
+                    let value = hex_decode(value)?;
+                    Some((n, u32::from_le_bytes(value.try_into().ok()?)))
+                });
+
+                match parsed {
+                    Some((n, value)) if self.target.write_register(machine, n, value) => {
+                        "OK".to_owned()
+                    }
+                    _ => "E01".to_owned(),
+                }
+            }
+            "m" => match parse_address_length(args) {
+                Some((addr, len)) => match self.target.read_memory(machine, addr, len) {
+                    Some(bytes) => hex_encode(&bytes),
+                    None => "E14".to_owned(),
+                },
+                None => "E01".to_owned(),
+            },
+            "M" => {
+                let parsed = args.split_once(':').and_then(|(range, data)| {
+                    let (addr, len) = parse_address_length(range)?;
+                    let bytes = hex_decode(data)?;
+                    (bytes.len() == len).then_some((addr, bytes))
+                });
+
+                match parsed {
+                    Some((addr, bytes)) => match self.target.write_memory(machine, addr, &bytes) {
+                        true => "OK".to_owned(),
+                        false => "E14".to_owned(),
+                    },
+                    None => "E01".to_owned(),
+                }
+            }
+            "c" => {
+                if let Ok(addr) = u32::from_str_radix(args, 16) {
+                    self.target.set_pc(machine, addr);
+                }
+
+                resume_machine(machine);
+                self.state = StubState::Running;
+                return Ok(events);
+            }
+            "s" => {
+                if let Ok(addr) = u32::from_str_radix(args, 16) {
+                    self.target.set_pc(machine, addr);
+                }
+
+                resume_machine(machine);
+                events = self.target.step(machine);
+                if !self.report_halt(&events)? {
+                    self.stop(SIGTRAP)?;
+                }
+
+                return Ok(events);
+            }
+            "Z" | "z" => {
+                // 0 = software, 1 = hardware. both are just a pc we stop at
+                let mut fields = args.split(',');
+                let kind = fields.next().and_then(|k| k.parse::<u8>().ok());
+                let addr = fields.next().and_then(|a| u32::from_str_radix(a, 16).ok());
+                let len = fields.next().and_then(|l| u32::from_str_radix(l, 16).ok());
+
+                match (kind, addr, len) {
+                    (Some(0 | 1), Some(addr), _) => {
+                        let debugger = self.target.debugger(machine);
+                        if command == "Z" {
+                            debugger.add_breakpoint(addr);
+                        } else {
+                            debugger.remove_breakpoint(addr);
+                        }
+
+                        "OK".to_owned()
+                    }
+                    (Some(kind @ 2..=4), Some(addr), Some(len)) if command == "Z" => {
+                        match self.target.add_watchpoint(machine, kind, addr, len) {
+                            Some(id) => {
+                                self.watchpoints.insert((kind, addr, len), id);
+                                "OK".to_owned()
+                            }
+                            None => String::new(),
+                        }
+                    }
+                    (Some(2..=4), Some(addr), Some(len)) => {
+                        let key = (kind.unwrap(), addr, len);
+                        if let Some(id) = self.watchpoints.remove(&key) {
+                            machine.bus.debugger.remove_watchpoint(id);
+                        }
+
+                        "OK".to_owned()
+                    }
+                    _ => String::new(),
+                }
+            }
+            "D" => {
+                self.send_packet("OK")?;
+                self.detach(machine);
+                return Ok(events);
+            }
+            "k" => {
+                self.detach(machine);
+                return Ok(events);
+            }
+            "H" => "OK".to_owned(),
+            "q" if args.starts_with("Supported") => match self.target.target_xml() {
+                Some(_) => "PacketSize=4000;swbreak+;hwbreak+;qXfer:features:read+".to_owned(),
+                None => "PacketSize=4000;swbreak+;hwbreak+".to_owned(),
+            },
+            "q" if args.starts_with("Xfer:features:read:target.xml:") => {
+                let range = &args["Xfer:features:read:target.xml:".len()..];
+                match (self.target.target_xml(), parse_address_length(range)) {
+                    (Some(xml), Some((offset, len))) => {
+                        // m = there's more after this chunk, l = last one
+                        let chunk = xml.get(offset as usize..).unwrap_or("");
+                        match chunk.len() > len {
+                            true => format!("m{}", &chunk[..len]),
+                            false => format!("l{}", chunk),
+                        }
+                    }
+                    _ => "E00".to_owned(),
+                }
+            }
+            "q" if args == "Attached" => "1".to_owned(),
+            "q" if args == "C" => "QC1".to_owned(),
+            "q" if args == "fThreadInfo" => "m1".to_owned(),
+            "q" if args == "sThreadInfo" => "l".to_owned(),
+            "q" if args.starts_with("Symbol") => "OK".to_owned(),
+            "q" if args.starts_with("Rcmd,") => {
+                // `monitor symbol <addr>` names an address with the emulator's symbols, which can come from
+                // map files gdb knows nothing about
+                let command = hex_decode(&args["Rcmd,".len()..])
+                    .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                    .unwrap_or_default();
+
+                let output = match command.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    ["symbol", addr] => {
+                        match u32::from_str_radix(addr.trim_start_matches("0x"), 16) {
+                            Ok(addr) => format!("{}\n", machine.cpu.symbolicate(addr)),
+                            Err(_) => format!("not an address: {}\n", addr),
+                        }
+                    }
+                    _ => "usage: monitor symbol <hex address>\n".to_owned(),
+                };
+
+                hex_encode(output.as_bytes())
+            }
+            "v" if args.starts_with("Kill") => {
+                self.send_packet("OK")?;
+                self.detach(machine);
+                return Ok(events);
+            }
+            _ => String::new(), // empty means unsupported, gdb falls back to something simpler
+        };
+
+        self.send_packet(&reply)?;
+        Ok(events)
+    }
+}
+
+// a watchpoint stop pauses the machine, once a debugger is attached it decides when that ends
+fn resume_machine(machine: &mut Machine) {
+    if machine.state == EmulatorState::Paused {
+        machine.state = EmulatorState::Running;
+    }
+}
+
+fn checksum_of(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hex_decode(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_address_length(args: &str) -> Option<(u32, usize)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        u32::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
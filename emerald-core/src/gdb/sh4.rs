@@ -0,0 +1,151 @@
+// registers are numbered the way gdb's sh4 architecture numbers them, the 'g' packet carries 0-58.
+// xf0-xf15 aren't part of gdb's sh4 layout so they're only reachable with 'p'/'P' as 59-74.
+
+use crate::{
+    hw::{extensions::BitManipulation, sh4::cpu::Cpu},
+    machine::Machine,
+};
+
+pub const REGISTER_COUNT: usize = 59; // r0-r15 .. r7b1, see sh_sh4_register_name in gdb's sh-tdep.c
+const XF_REGISTER_BASE: usize = 59;
+
+// r0-r7 of the given bank, whichever of r/r_bank currently holds it
+fn bank_register(cpu: &Cpu, bank: bool, index: usize) -> u32 {
+    if cpu.registers.sr.check_bit(29) == bank {
+        cpu.registers.r[index]
+    } else {
+        cpu.registers.r_bank[index]
+    }
+}
+
+fn set_bank_register(cpu: &mut Cpu, bank: bool, index: usize, value: u32) {
+    if cpu.registers.sr.check_bit(29) == bank {
+        cpu.registers.r[index] = value;
+    } else {
+        cpu.registers.r_bank[index] = value;
+    }
+}
+
+pub fn read_register(machine: &Machine, n: usize) -> Option<u32> {
+    let cpu = &machine.cpu;
+    let registers = &cpu.registers;
+    let value = match n {
+        0..=15 => registers.r[n],
+        16 => registers.current_pc,
+        17 => registers.pr,
+        18 => registers.gbr,
+        19 => registers.vbr,
+        20 => registers.mach,
+        21 => registers.macl,
+        22 => registers.sr,
+        23 => registers.fpul,
+        24 => registers.fpscr,
+        25..=40 => cpu.get_fr_register_by_index(n - 25).to_bits(),
+        41 => registers.ssr,
+        42 => registers.spc,
+        43..=50 => bank_register(cpu, false, n - 43),
+        51..=58 => bank_register(cpu, true, n - 51),
+        XF_REGISTER_BASE..=74 => cpu.get_xf_register_by_index(n - XF_REGISTER_BASE).to_bits(),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+// sr and fpscr go through the setters so changing rb or fr swaps banks the way the hardware would
+pub fn write_register(machine: &mut Machine, n: usize, value: u32) -> bool {
+    let cpu = &mut machine.cpu;
+    match n {
+        0..=15 => cpu.registers.r[n] = value,
+        16 => cpu.registers.current_pc = value,
+        17 => cpu.registers.pr = value,
+        18 => cpu.registers.gbr = value,
+        19 => cpu.registers.vbr = value,
+        20 => cpu.registers.mach = value,
+        21 => cpu.registers.macl = value,
+        22 => cpu.set_sr(value),
+        23 => cpu.registers.fpul = value,
+        24 => cpu.set_fpscr(value),
+        25..=40 => cpu.set_fr_register_by_index(n - 25, f32::from_bits(value)),
+        41 => cpu.registers.ssr = value,
+        42 => cpu.registers.spc = value,
+        43..=50 => set_bank_register(cpu, false, n - 43, value),
+        51..=58 => set_bank_register(cpu, true, n - 51, value),
+        XF_REGISTER_BASE..=74 => {
+            cpu.set_xf_register_by_index(n - XF_REGISTER_BASE, f32::from_bits(value))
+        }
+        _ => return false,
+    }
+
+    true
+}
+
+// sr and fpscr first, they decide which bank everything after them lands in
+pub fn write_registers(machine: &mut Machine, values: &[u32]) {
+    for n in [22, 24] {
+        if let Some(value) = values.get(n) {
+            write_register(machine, n, *value);
+        }
+    }
+
+    for (n, value) in values.iter().enumerate().take(REGISTER_COUNT) {
+        if n != 22 && n != 24 {
+            write_register(machine, n, *value);
+        }
+    }
+}
+
+// the store queues aren't readable, everything else goes through the bus like a guest access would.
+// that includes mmio side effects, so peeking at a fifo from the debugger will pop it. watchpoints are the
+// exception, see CpuBus::untracked
+fn is_store_queue(addr: u32) -> bool {
+    (0xe0000000..=0xe3ffffff).contains(&addr)
+}
+
+pub fn read_memory(machine: &mut Machine, addr: u32, len: usize) -> Option<Vec<u8>> {
+    let context = &mut machine.context;
+    let mut bytes = Vec::with_capacity(len);
+    let end = addr.checked_add(len as u32)?;
+    let mut current = addr;
+
+    while current < end {
+        if is_store_queue(current) {
+            return None;
+        }
+
+        if current & 3 == 0 && end - current >= 4 {
+            let value = machine.bus.untracked(|bus| bus.read_32(current, context));
+            bytes.extend(value.to_le_bytes());
+            current += 4;
+        } else {
+            bytes.push(
+                machine
+                    .bus
+                    .untracked(|bus| bus.read_8(current, false, context)),
+            );
+            current += 1;
+        }
+    }
+
+    // an access the core couldn't emulate isn't something the guest did, don't halt over it
+    match context.error.take() {
+        Some(_) => None,
+        None => Some(bytes),
+    }
+}
+
+pub fn write_memory(machine: &mut Machine, addr: u32, bytes: &[u8]) -> bool {
+    for (offset, byte) in bytes.iter().enumerate() {
+        let current = addr.wrapping_add(offset as u32);
+        if is_store_queue(current) {
+            return false;
+        }
+
+        let context = &mut machine.context;
+        machine
+            .bus
+            .untracked(|bus| bus.write_8(current, *byte, context));
+    }
+
+    machine.context.error.take().is_none()
+}
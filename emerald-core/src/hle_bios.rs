@@ -0,0 +1,412 @@
+// high level emulation of the bios's syscalls, for running without a dump of dc_boot.bin.
+//
+// the direct boot environment points the syscall vectors at where the bios's own code would be (see boot.rs),
+// with --hle-bios an rts sits at each of those instead and the machine services the call when the sh4 gets
+// there. that's the gd-rom's command queue, sysinfo, the flash partitions, the font and the system vector's
+// exit to the menu, which stops the machine since there isn't one.
+//
+// gd-rom commands complete as soon as they're queued, reads copy straight into the guest's buffer. only mode 1
+// data reads are supported, like the drive itself. the font lives in the bios, so without a dump the font
+// syscall hands out the right address with nothing behind it.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boot::{
+        self, FLASHROM_ENTRY, GDROM_DIRECT_ENTRY, GDROM_ENTRY, ROMFONT_ENTRY, SYSCALL_VECTORS,
+        SYSINFO_ADDR, SYSINFO_ENTRY, SYSTEM_ENTRY,
+    },
+    context::Context,
+    hw::{
+        holly::g1::gdi::{GdiImage, Track},
+        sh4::{
+            bus::{CpuBus, GuestMemory, PhysicalAddress},
+            cpu::Cpu,
+        },
+    },
+    trace::TraceCategory,
+};
+
+// gdGdcReqCmd's commands
+const CMD_PIOREAD: u32 = 16;
+const CMD_DMAREAD: u32 = 17;
+const CMD_GETTOC: u32 = 18;
+const CMD_GETTOC2: u32 = 19;
+const CMD_PLAY: u32 = 20;
+const CMD_PLAY2: u32 = 21;
+const CMD_PAUSE: u32 = 22;
+const CMD_RELEASE: u32 = 23;
+const CMD_INIT: u32 = 24;
+const CMD_SEEK: u32 = 27;
+const CMD_STOP: u32 = 33;
+
+// gdGdcGetCmdStat's results
+const NO_ACTIVE: i32 = 0;
+const COMPLETED: i32 = 2;
+const FAILED: i32 = -1;
+
+// the sense keys left in the first word of a failed command's status
+const ERR_NOT_READY: u32 = 2;
+const ERR_ILLEGAL_REQUEST: u32 = 5;
+
+// gdGdcGetDrvStat's drive states and disc types
+const DRIVE_PAUSED: u32 = 1;
+const DRIVE_NO_DISC: u32 = 7;
+const DISC_GDROM: u32 = 0x80;
+
+// where the font is in the rom
+const FONT_ADDR: u32 = 0xa0100020;
+
+// offset and size of each flash partition, by number
+const FLASH_PARTITIONS: [(u32, u32); 5] = [
+    (0x1a000, 0x2000), // factory settings
+    (0x18000, 0x2000), // reserved
+    (0x1c000, 0x4000), // block 1
+    (0x10000, 0x8000), // block 2, the system settings
+    (0x00000, 0x10000),
+];
+
+const FLASH_BASE: u32 = 0x00200000;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Request {
+    id: u32,
+    result: i32,
+    status: [u32; 4], // error, extended error, bytes transferred, ata status
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HleBios {
+    next_request: u32,
+    finished: Option<Request>, // waiting for gdGdcGetCmdStat to collect it
+    sector_size: u32,          // set by gdGdcChangeDataType
+}
+
+impl HleBios {
+    pub fn new() -> Self {
+        Self {
+            next_request: 1,
+            finished: None,
+            sector_size: 2048,
+        }
+    }
+
+    // an rts at each syscall, the machine traps the call before it runs
+    pub fn install(&self, bus: &mut CpuBus, context: &mut Context) {
+        for (_, entry) in SYSCALL_VECTORS {
+            bus.write_16(entry, 0x000b, context); // rts
+            bus.write_16(entry + 2, 0x0009, context); // nop
+        }
+    }
+
+    // services the syscall at pc if there is one, some(status) if the program asked for the bios menu
+    pub fn syscall(
+        &mut self,
+        pc: u32,
+        cpu: &mut Cpu,
+        bus: &mut CpuBus,
+        context: &mut Context,
+    ) -> Option<i32> {
+        let [r1, r4, r5, r6, r7] = [1, 4, 5, 6, 7].map(|i| cpu.get_register_by_index(i));
+        let mut memory = GuestMemory::new(bus, context);
+
+        let (name, result) = match 0x80000000 | (pc & 0x1fffffff) {
+            SYSINFO_ENTRY => ("sysinfo", self.sysinfo(&mut memory, r7)),
+            ROMFONT_ENTRY => ("romfont", Self::romfont(r1)),
+            FLASHROM_ENTRY => ("flashrom", Self::flashrom(&mut memory, r4, r5, r6, r7)),
+            GDROM_ENTRY | GDROM_DIRECT_ENTRY => ("gdrom", self.gdrom(&mut memory, r4, r5, r6, r7)),
+            SYSTEM_ENTRY => match r4 as i32 {
+                // back to the menu, or a reset to get there
+                1 | -1 => return Some(0),
+                _ => ("system", 0),
+            },
+            _ => return None,
+        };
+
+        if context.tracer.enabled(TraceCategory::Bios) {
+            context.tracer.message(
+                TraceCategory::Bios,
+                format!(
+                    "hle {}({:x}, {:x}, {:x}, {:x}) = {}",
+                    name, r4, r5, r6, r7, result
+                ),
+            );
+        }
+
+        cpu.set_register_by_index(0, result as u32);
+        None
+    }
+
+    fn sysinfo(&mut self, memory: &mut GuestMemory, function: u32) -> i32 {
+        match function {
+            // SYSINFO_INIT
+            0 => {
+                boot::load_sysinfo(memory.bus, memory.context);
+                0
+            }
+            // SYSINFO_ID, a pointer to the unique id
+            3 => SYSINFO_ADDR as i32,
+            // SYSINFO_ICON, the icons are in the bios too
+            _ => -1,
+        }
+    }
+
+    fn romfont(function: u32) -> i32 {
+        match function {
+            0 => FONT_ADDR as i32,
+            1 | 2 => 0, // lock and unlock, nothing else ever wants it
+            _ => -1,
+        }
+    }
+
+    fn flashrom(memory: &mut GuestMemory, r4: u32, r5: u32, r6: u32, function: u32) -> i32 {
+        let flash_len = memory.bus.holly.g1_bus.boot_rom.flash.len() as u32;
+        let in_flash =
+            |offset: u32, len: u32| offset.checked_add(len).is_some_and(|end| end <= flash_len);
+
+        match function {
+            // FLASHROM_INFO(partition, [offset, size])
+            0 => match FLASH_PARTITIONS.get(r4 as usize) {
+                Some((offset, size)) => {
+                    let mut info = offset.to_le_bytes().to_vec();
+                    info.extend_from_slice(&size.to_le_bytes());
+                    memory.write(r5, &info);
+                    0
+                }
+                None => -1,
+            },
+            // FLASHROM_READ(offset, buffer, len), through the boot rom so the configured region shows
+            1 if in_flash(r4, r6) => {
+                let boot_rom = &memory.bus.holly.g1_bus.boot_rom;
+                let data: Vec<u8> = (r4..r4 + r6)
+                    .map(|offset| {
+                        boot_rom
+                            .read_8(PhysicalAddress(FLASH_BASE + offset))
+                            .unwrap_or(0xff)
+                    })
+                    .collect();
+
+                memory.write(r5, &data);
+                r6 as i32
+            }
+            // FLASHROM_WRITE(offset, data, len), programming can only clear bits
+            2 if in_flash(r4, r6) => {
+                let data = memory.read(r5, r6 as usize);
+                let flash = &mut memory.bus.holly.g1_bus.boot_rom.flash;
+                for (byte, new) in flash[r4 as usize..].iter_mut().zip(data) {
+                    *byte &= new;
+                }
+
+                r6 as i32
+            }
+            // FLASHROM_DELETE(offset), erases the whole partition it starts
+            3 => match FLASH_PARTITIONS.iter().find(|(offset, _)| *offset == r4) {
+                Some(&(offset, size)) => {
+                    let flash = &mut memory.bus.holly.g1_bus.boot_rom.flash;
+                    flash[offset as usize..(offset + size) as usize].fill(0xff);
+                    0
+                }
+                None => -1,
+            },
+            _ => -1,
+        }
+    }
+
+    // r6 picks the group, 0 is the gd-rom's and r7 the function in it
+    fn gdrom(&mut self, memory: &mut GuestMemory, r4: u32, r5: u32, r6: u32, r7: u32) -> i32 {
+        if r6 != 0 {
+            unsupported(memory, format_args!("gdrom syscall {}/{}", r6 as i32, r7));
+            return -1;
+        }
+
+        match r7 {
+            // gdGdcReqCmd(command, parameters)
+            0 => self.request(memory, r4, r5),
+            // gdGdcGetCmdStat(request, status)
+            1 => {
+                let finished = self.finished.take_if(|request| request.id == r4);
+                let (result, status) =
+                    finished.map_or((NO_ACTIVE, [0; 4]), |r| (r.result, r.status));
+
+                memory.write(r5, &words(&status));
+                result
+            }
+            // gdGdcExecServer, everything is already done
+            2 => 0,
+            // gdGdcInitSystem
+            3 => {
+                *self = Self::new();
+                0
+            }
+            // gdGdcGetDrvStat([state, disc type])
+            4 => {
+                let status = match &memory.bus.holly.g1_bus.gd_rom.gdi_image {
+                    Some(_) => [DRIVE_PAUSED, DISC_GDROM],
+                    None => [DRIVE_NO_DISC, 0],
+                };
+
+                memory.write(r4, &words(&status));
+                0
+            }
+            // gdGdcAbortCmd and gdGdcReset, nothing is ever in flight
+            8 | 9 => {
+                self.finished = None;
+                0
+            }
+            // gdGdcChangeDataType([read/write, ?, sector part, sector size])
+            10 => {
+                let parameters = memory.read(r4, 16);
+                self.sector_size = u32::from_le_bytes(parameters[12..16].try_into().unwrap());
+                0
+            }
+            _ => {
+                unsupported(memory, format_args!("gdrom syscall {}", r7));
+                -1
+            }
+        }
+    }
+
+    fn request(&mut self, memory: &mut GuestMemory, command: u32, parameters: u32) -> i32 {
+        let parameters: Vec<u32> = memory
+            .read(parameters, 16)
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+
+        let outcome = match command {
+            CMD_PIOREAD | CMD_DMAREAD => {
+                self.read_sectors(memory, parameters[0], parameters[1], parameters[2])
+            }
+            CMD_GETTOC | CMD_GETTOC2 => Self::toc(memory, parameters[0], parameters[1]),
+            CMD_INIT | CMD_PLAY | CMD_PLAY2 | CMD_PAUSE | CMD_RELEASE | CMD_SEEK | CMD_STOP => {
+                match memory.bus.holly.g1_bus.gd_rom.gdi_image {
+                    Some(_) => Ok(0),
+                    None => Err(ERR_NOT_READY),
+                }
+            }
+            _ => {
+                unsupported(memory, format_args!("gdrom command {}", command));
+                Err(ERR_ILLEGAL_REQUEST)
+            }
+        };
+
+        let id = self.next_request;
+        self.next_request = self.next_request.wrapping_add(1).max(1);
+
+        self.finished = Some(match outcome {
+            Ok(transferred) => Request {
+                id,
+                result: COMPLETED,
+                status: [0, 0, transferred, 0],
+            },
+            Err(error) => Request {
+                id,
+                result: FAILED,
+                status: [error, 0, 0, 0],
+            },
+        });
+
+        id as i32
+    }
+
+    // the bytes read, or the sense key it failed with
+    fn read_sectors(
+        &self,
+        memory: &mut GuestMemory,
+        fad: u32,
+        count: u32,
+        buffer: u32,
+    ) -> Result<u32, u32> {
+        let image = memory
+            .bus
+            .holly
+            .g1_bus
+            .gd_rom
+            .gdi_image
+            .as_ref()
+            .ok_or(ERR_NOT_READY)?;
+
+        if self.sector_size != 2048 || !contains(image, fad, count) {
+            return Err(ERR_ILLEGAL_REQUEST);
+        }
+
+        let mut data = vec![0; 2352 * count as usize];
+        let copied = image.load_sectors(fad, count, &mut data) as usize;
+
+        memory.write(buffer, &data[..copied]);
+        Ok(copied as u32)
+    }
+
+    // an area's toc, in the bios's format. 99 tracks, then the first, last and the lead out
+    fn toc(memory: &mut GuestMemory, area: u32, buffer: u32) -> Result<u32, u32> {
+        let image = memory
+            .bus
+            .holly
+            .g1_bus
+            .gd_rom
+            .gdi_image
+            .as_ref()
+            .ok_or(ERR_NOT_READY)?;
+
+        // the single density area is the first two tracks, the high density one everything after
+        let tracks = match area {
+            0 => &image.tracks[..image.tracks.len().min(2)],
+            1 => image.tracks.get(2..).unwrap_or_default(),
+            _ => &[],
+        };
+
+        let (Some(first), Some(last)) = (tracks.first(), tracks.last()) else {
+            return Err(ERR_ILLEGAL_REQUEST);
+        };
+
+        let control = |track: &Track| ((track.control as u32) << 28) | ((track.adr as u32) << 24);
+
+        let mut toc = [0xffffffff_u32; 102];
+        for track in tracks {
+            toc[track.number - 1] = control(track) | track.offset as u32;
+        }
+
+        toc[99] = control(first) | ((first.number as u32) << 16);
+        toc[100] = control(last) | ((last.number as u32) << 16);
+        toc[101] = control(last) | (last.offset + last.data.len() / last.sector_size) as u32;
+
+        let toc = words(&toc);
+        memory.write(buffer, &toc);
+        Ok(toc.len() as u32)
+    }
+}
+
+// whether the image has count sectors starting at fad, all in one track
+fn contains(image: &GdiImage, fad: u32, count: u32) -> bool {
+    let Some(track) = image
+        .tracks
+        .iter()
+        .rev()
+        .find(|track| track.offset as u32 <= fad)
+    else {
+        return false;
+    };
+
+    // count comes straight from the guest, so it can be anything
+    let sectors = (track.data.len() / track.sector_size) as u32;
+    let end = (fad - track.offset as u32).checked_add(count);
+    !track.is_audo_track && end.is_some_and(|end| end <= sectors)
+}
+
+// something the guest called that isn't emulated, shown with --trace bios
+fn unsupported(memory: &mut GuestMemory, what: std::fmt::Arguments) {
+    let tracer = &mut memory.context.tracer;
+    if tracer.enabled(TraceCategory::Bios) {
+        tracer.message(
+            TraceCategory::Bios,
+            format!("hle bios: unsupported {}", what),
+        );
+    }
+}
+
+fn words(values: &[u32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
@@ -1,34 +1,58 @@
-use crate::hw::sh4::bus::PhysicalAddress;
+use crate::{
+    config::{Language, Region, BIOS_SIZE, FLASH_SIZE},
+    error::{EmulatorError, Subsystem},
+    hw::sh4::bus::PhysicalAddress,
+};
 
-pub struct BootROM {}
-pub const BIOS_DATA: &[u8] = include_bytes!("../../../../roms/dc_boot.bin");
-pub const BIOS_FLASH: &[u8] = include_bytes!("../../../../roms/dc_flash.bin");
+pub struct BootROM {
+    pub bios: Vec<u8>,
+    pub flash: Vec<u8>,
+    pub region: Region,
+    pub language: Language,
+}
 
 impl BootROM {
     pub fn new() -> Self {
-        BootROM {}
+        BootROM {
+            bios: vec![0; BIOS_SIZE],
+            flash: vec![0; FLASH_SIZE],
+            region: Region::Usa,
+            language: Language::English,
+        }
+    }
+
+    pub fn load(&mut self, bios: Vec<u8>, flash: Vec<u8>) {
+        assert_eq!(bios.len(), BIOS_SIZE);
+        assert_eq!(flash.len(), FLASH_SIZE);
+
+        self.bios = bios;
+        self.flash = flash;
     }
 
-    pub fn read_8(&self, addr: PhysicalAddress) -> u8 {
+    pub fn read_8(&self, addr: PhysicalAddress) -> Result<u8, EmulatorError> {
         let raw = addr.0;
-        match raw {
-            0x00000000..=0x001fffff => {
-                return BIOS_DATA[raw as usize];
-            }
+        let value = match raw {
+            0x00000000..=0x001fffff => self.bios[raw as usize],
             0x00200000..=0x0021ffff => {
                 assert_eq!((raw - 0x00200000) as usize, (raw & 0x1FFFF) as usize);
 
+                // the factory partition, overridden so the configured region is what the bios sees
                 match (raw & 0x1FFFF) {
-                    0x1a002 | 0x1a0a2 => '0' as u8 + 1, // ??? region
-                    0x1a003 | 0x1a0a3 => '0' as u8 + 1, // ??? language
-                    0x1a004 | 0x1a0a4 => '0' as u8 + 0,
-                    _ => return BIOS_FLASH[(raw - 0x00200000) as usize],
+                    0x1a002 | 0x1a0a2 => self.region.flash_value(),
+                    0x1a003 | 0x1a0a3 => self.language.flash_value(),
+                    0x1a004 | 0x1a0a4 => self.region.broadcast_value(),
+                    _ => self.flash[(raw - 0x00200000) as usize],
                 }
             }
             _ => {
-                panic!("out of bounds read in bios @ {:08x}", addr.0);
-                0
+                return Err(EmulatorError::unsupported(
+                    Subsystem::BootRom,
+                    addr.0,
+                    "out of bounds read",
+                ))
             }
-        }
+        };
+
+        Ok(value)
     }
 }
@@ -99,7 +99,18 @@ impl GdiParser {
     pub fn load_from_file(path: &str) -> GdiImage {
         let gdi_path = Path::new(path);
         let gdi_contents = fs::read_to_string(gdi_path).unwrap();
-        let successful_parse = GdiParser::parse(Rule::gdi, &gdi_contents)
+
+        Self::load_from_str(&gdi_contents, |_, track_file| {
+            fs::read(gdi_path.parent().unwrap().join(Path::new(track_file))).unwrap()
+        })
+    }
+
+    // parses a .gdi sheet, asking the caller for each track's contents (by index and the file name in the sheet)
+    pub fn load_from_str<F: FnMut(usize, &str) -> Vec<u8>>(
+        gdi_contents: &str,
+        mut read_track: F,
+    ) -> GdiImage {
+        let successful_parse = GdiParser::parse(Rule::gdi, gdi_contents)
             .unwrap()
             .next()
             .unwrap();
@@ -116,9 +127,7 @@ impl GdiParser {
                     let beginning_lba = fields.next().unwrap().as_str().parse::<usize>().unwrap();
                     let track_type = fields.next().unwrap().as_str().parse::<usize>().unwrap();
                     let sector_size = fields.next().unwrap().as_str().parse::<usize>().unwrap();
-                    let pathpath = gdi_path.parent().unwrap().join(Path::new(
-                        &fields.next().unwrap().as_str().trim_matches('"'),
-                    ));
+                    let track_file = fields.next().unwrap().as_str().trim_matches('"');
 
                     tracks.push(Track {
                         number: i,
@@ -127,7 +136,7 @@ impl GdiParser {
                         control: if track_type == 4 { 4 } else { 0 },
                         adr: 1,
                         sector_size,
-                        data: fs::read(pathpath).unwrap(),
+                        data: read_track(i - 1, track_file),
                     });
                     i += 1;
                 }
@@ -2,18 +2,21 @@ use std::cell::{Cell, RefCell};
 
 use crate::{
     context::Context,
+    error::{EmulatorError, Subsystem},
     fifo::Fifo,
     hw::{
         extensions::BitManipulation,
         holly::{sb::SystemBlock, HollyEventData},
         sh4::bus::PhysicalAddress,
     },
-    scheduler::Scheduler,
+    scheduler::{EventHandle, Scheduler},
+    trace::TraceCategory,
 };
 
 use super::gdi::GdiImage;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum GdromEventData {
     AckCommand,
     AckCommandWithStat(u32),
@@ -21,7 +24,7 @@ pub enum GdromEventData {
     ProcessSPICommand(Vec<u8>),
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct SectorReadContext {
     pub sector_start: u32,      // starting sector
     pub remaining_sectors: u32, // remaining sectors
@@ -32,7 +35,7 @@ pub struct SectorReadContext {
     pub cache_index: usize,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum GdromState {
     WaitingForCommand,
     ProcessingCommand,
@@ -60,7 +63,7 @@ impl<'a> std::fmt::Debug for HexSlice<'a> {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Gdrom {
     pub registers: GdromRegisters,
     pub pending_cmd: Option<u8>,
@@ -69,12 +72,15 @@ pub struct Gdrom {
     pub pending_err: bool,
     pub pending_ack: Option<u32>,
     pub output_fifo: RefCell<Fifo<u8, 4294967295>>,
+    #[serde(skip)] // the disc stays in the drive across save states
     pub gdi_image: Option<GdiImage>,
     pub pending_state: Option<GdromState>,
     pub read_context: SectorReadContext,
+    pub lower_int_event: Cell<Option<EventHandle>>,
+    pub completion_event: Option<EventHandle>, // the interrupt for the command in flight
 }
 
-#[derive(Clone, Default, Debug)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize)]
 pub struct GdromRegisters {
     pub status: Cell<u32>,
     control: u32,
@@ -98,6 +104,10 @@ impl GdromRegisters {
     }
 }
 
+// where commands and spi packets are written
+const COMMAND_REGISTER: u32 = 0x005f709c;
+const DATA_REGISTER: u32 = 0x005f7080;
+
 const REQ_MODE_TABLE: [u16; 16] = [
     0x0000_u16, 0x0000, 0xb400, 0x0019, 0x0800, 0x4553, 0x2020, 0x2020, 0x2020, 0x6552, 0x2076,
     0x2e36, 0x3334, 0x3939, 0x3430, 0x3830,
@@ -116,6 +126,8 @@ impl Gdrom {
             pending_clear: Cell::new(false),
             pending_state: None,
             read_context: Default::default(),
+            lower_int_event: Cell::new(None),
+            completion_event: None,
         }
     }
 
@@ -123,7 +135,11 @@ impl Gdrom {
         self.gdi_image = Some(gdi_image);
     }
 
-    pub fn transition(&mut self, scheduler: &mut Scheduler, state: GdromState) {
+    pub fn transition(
+        &mut self,
+        context: &mut Context,
+        state: GdromState,
+    ) -> Result<(), EmulatorError> {
         let status = self.registers.status.get();
 
         match state {
@@ -141,13 +157,13 @@ impl Gdrom {
                     .clear_bit(6); // DRDY goes to 0
 
                 self.registers.status.set(new_status);
-                self.process_cmd(cmd, scheduler)
+                self.process_cmd(cmd, context)?;
             }
             GdromState::PioEnd => {
                 let new_status = status.clear_bit(3); // DRQ goes to 0
 
                 self.registers.status.set(new_status);
-                self.transition(scheduler, GdromState::FinishedProcessingPacket);
+                self.transition(context, GdromState::FinishedProcessingPacket)?;
             }
             GdromState::WaitingForPacket => {
                 let new_status = status
@@ -164,7 +180,7 @@ impl Gdrom {
                 self.registers.status.set(new_status);
 
                 let parameters = std::mem::take(&mut self.pending_data);
-                self.process_spi_cmd(&parameters, scheduler);
+                self.process_spi_cmd(&parameters, context)?;
             }
             GdromState::FinishedProcessingPacket => {
                 let new_status = status
@@ -174,14 +190,9 @@ impl Gdrom {
 
                 self.registers.status.set(new_status);
 
-                scheduler.schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                    deadline: 400, // fixme: timing
-                    event_data: HollyEventData::RaiseInterruptExternal {
-                        istext: 0.set_bit(0),
-                    },
-                });
+                self.raise_completion(context);
 
-                self.transition(scheduler, GdromState::WaitingForCommand);
+                self.transition(context, GdromState::WaitingForCommand)?;
             }
             GdromState::ReceivingData | GdromState::SendingData => {
                 let new_status = status
@@ -191,15 +202,33 @@ impl Gdrom {
                 self.registers.status.set(new_status);
 
                 // println!("after init sending status went to {:08x}", new_status);
-                scheduler.schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                    deadline: 400, // fixme: timing
-                    event_data: HollyEventData::RaiseInterruptExternal {
-                        istext: 0.set_bit(0),
-                    },
-                });
+                self.raise_completion(context);
+            }
+            _ => {
+                return Err(EmulatorError::unsupported(
+                    Subsystem::Gdrom,
+                    COMMAND_REGISTER,
+                    format!("no transition to {:?}", state),
+                ))
             }
-            _ => panic!("not implemented yet {:#?}", state),
         }
+
+        Ok(())
+    }
+
+    fn raise_completion(&mut self, context: &mut Context) {
+        if let Some(handle) = self.completion_event.take() {
+            context.scheduler.cancel(handle);
+        }
+
+        self.completion_event = Some(context.scheduler.schedule(
+            crate::scheduler::ScheduledEvent::HollyEvent {
+                deadline: 400, // fixme: timing
+                event_data: HollyEventData::RaiseInterruptExternal {
+                    istext: 0.set_bit(0),
+                },
+            },
+        ));
     }
 
     pub fn write_32(&mut self, addr: PhysicalAddress, value: u32) {
@@ -213,7 +242,12 @@ impl Gdrom {
         }
     }
 
-    pub fn write_16(&mut self, addr: PhysicalAddress, value: u16, context: &mut Context) {
+    pub fn write_16(
+        &mut self,
+        addr: PhysicalAddress,
+        value: u16,
+        context: &mut Context,
+    ) -> Result<(), EmulatorError> {
         match addr.0 {
             0x005f7080 => {
                 // data
@@ -223,33 +257,41 @@ impl Gdrom {
                 self.pending_data.push(bytes[1]);
 
                 if self.pending_data.len() == 12 {
-                    self.transition(context.scheduler, GdromState::ProcessingPacket);
+                    self.transition(context, GdromState::ProcessingPacket)?;
                 }
+
+                Ok(())
             }
-            _ => {
-                panic!(
-                    "gdrom: unimplemented write (16-bit) @ 0x{:08x} with 0x{:04x}",
-                    addr.0, value
-                );
-            }
+            _ => Err(EmulatorError::unknown_write(
+                Subsystem::Gdrom,
+                addr.0,
+                value as u32,
+                16,
+            )),
         }
     }
 
-    pub fn read_16(&self, addr: PhysicalAddress, context: &mut Context) -> u16 {
+    pub fn read_16(
+        &self,
+        addr: PhysicalAddress,
+        context: &mut Context,
+    ) -> Result<u16, EmulatorError> {
         match addr.0 {
             0x005f7080 => {
-                // read output data
-                let lo = self.output_fifo.borrow_mut().pop().unwrap();
-                let hi = self.output_fifo.borrow_mut().pop().unwrap();
-
-                let val = lo as u16 | ((hi as u16) << 8);
+                // read output data, an empty fifo reads as zero
+                let mut fifo = self.output_fifo.borrow_mut();
+                let (lo, hi) = (fifo.pop(), fifo.pop());
+
+                if (lo.is_none() || hi.is_none()) && context.tracer.enabled(TraceCategory::Io) {
+                    context.tracer.message(
+                        TraceCategory::Io,
+                        "gdrom: data register read with nothing queued".to_owned(),
+                    );
+                }
 
-                //   println!("reading output fifo..{:08x}", val);
-                val
-            }
-            _ => {
-                panic!("gdrom: unimplemented read (16-bit) @ 0x{:08x}", addr.0);
+                Ok(lo.unwrap_or(0) as u16 | ((hi.unwrap_or(0) as u16) << 8))
             }
+            _ => Err(EmulatorError::unknown_read(Subsystem::Gdrom, addr.0, 16)),
         }
     }
 
@@ -278,14 +320,22 @@ impl Gdrom {
                 // );
 
                 let status = self.registers.status.get().set_bit(4) as u8;
-                context
-                    .scheduler
-                    .schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                        deadline: 400,
-                        event_data: HollyEventData::LowerExternalInterrupt {
-                            istext: 0.set_bit(0),
+
+                // drivers poll this, one pending lower is enough
+                if !self
+                    .lower_int_event
+                    .get()
+                    .is_some_and(|handle| context.scheduler.is_scheduled(handle))
+                {
+                    self.lower_int_event.set(Some(context.scheduler.schedule(
+                        crate::scheduler::ScheduledEvent::HollyEvent {
+                            deadline: 400,
+                            event_data: HollyEventData::LowerExternalInterrupt {
+                                istext: 0.set_bit(0),
+                            },
                         },
-                    });
+                    )));
+                }
 
                 status
             }
@@ -300,7 +350,12 @@ impl Gdrom {
         val
     }
 
-    pub fn write_8(&mut self, addr: PhysicalAddress, value: u8, context: &mut Context) {
+    pub fn write_8(
+        &mut self,
+        addr: PhysicalAddress,
+        value: u8,
+        context: &mut Context,
+    ) -> Result<(), EmulatorError> {
         match addr.0 {
             0x005f7018 => {}
             0x005f7098 => {}
@@ -309,9 +364,14 @@ impl Gdrom {
             0x005f7090 => self.registers.byte_count_lo = value,
             0x005f7094 => self.registers.byte_count_hi = value,
             0x005f709c => {
+                // a new command takes back the last one's interrupt if it hasn't gone off yet
+                if let Some(handle) = self.completion_event.take() {
+                    context.scheduler.cancel(handle);
+                }
+
                 // fixme: move pending_cmd into ProcessingCommand
                 self.pending_cmd = Some(value);
-                self.transition(context.scheduler, GdromState::ProcessingCommand);
+                return self.transition(context, GdromState::ProcessingCommand);
             }
             _ => {
                 println!(
@@ -320,6 +380,8 @@ impl Gdrom {
                 );
             }
         }
+
+        Ok(())
     }
 
     pub fn on_scheduled_event(
@@ -327,13 +389,17 @@ impl Gdrom {
         scheduler: &mut Scheduler,
         sb: &mut SystemBlock,
         event_data: GdromEventData,
-    ) {
+    ) -> Result<(), EmulatorError> {
         match event_data {
-            _ => panic!("gdrom: unimplemented event {:#?}", event_data),
+            _ => Err(EmulatorError::unsupported(
+                Subsystem::Gdrom,
+                COMMAND_REGISTER,
+                format!("unimplemented event {:?}", event_data),
+            )),
         }
     }
 
-    pub fn process_cmd(&mut self, cmd: u8, scheduler: &mut Scheduler) {
+    pub fn process_cmd(&mut self, cmd: u8, context: &mut Context) -> Result<(), EmulatorError> {
         if self.registers.sns_key != 0 {
             self.registers
                 .status
@@ -345,42 +411,67 @@ impl Gdrom {
         }
 
         match cmd {
-            0x00 => {
-                self.transition(scheduler, GdromState::WaitingForCommand);
-            }
-            0x08 => {
-                self.transition(scheduler, GdromState::WaitingForCommand);
-            }
-            0xa0 => {
-                self.transition(scheduler, GdromState::WaitingForPacket);
-            }
+            0x00 => self.transition(context, GdromState::WaitingForCommand),
+            0x08 => self.transition(context, GdromState::WaitingForCommand),
+            0xa0 => self.transition(context, GdromState::WaitingForPacket),
             0xef => {
                 self.registers
                     .status
                     .set(self.registers.status.get().clear_bit(0));
-                self.transition(scheduler, GdromState::WaitingForPacket);
+                self.transition(context, GdromState::WaitingForPacket)
             }
-            _ => panic!("gdrom: unimplemented command {:02x}", cmd),
+            _ => Err(EmulatorError::unimplemented_command(
+                Subsystem::Gdrom,
+                COMMAND_REGISTER,
+                cmd as u32,
+            )),
         }
     }
 
-    fn finalize_spi_cmd(&mut self, len: usize, scheduler: &mut Scheduler, next_state: GdromState) {
+    fn finalize_spi_cmd(
+        &mut self,
+        len: usize,
+        context: &mut Context,
+        next_state: GdromState,
+    ) -> Result<(), EmulatorError> {
         let status = self.registers.status.get();
         if len > 0 {
             let output_len = u16::to_le_bytes(len as u16);
             self.registers.byte_count_lo = output_len[0];
             self.registers.byte_count_hi = output_len[1];
 
-            assert_eq!(self.output_fifo.borrow().len(), len);
+            let queued = self.output_fifo.borrow().len();
+            if queued != len {
+                return Err(EmulatorError::unsupported(
+                    Subsystem::Gdrom,
+                    DATA_REGISTER,
+                    format!("{} bytes queued for a {} byte transfer", queued, len),
+                ));
+            }
 
             self.pending_state = Some(next_state);
-            self.transition(scheduler, GdromState::SendingData);
+            self.transition(context, GdromState::SendingData)
         } else {
-            self.transition(scheduler, next_state);
+            self.transition(context, next_state)
+        }
+    }
+
+    // the guest asks for alloc_len bytes, it gets at most what the command produced
+    fn queue_output(
+        &mut self,
+        data: &[u8],
+        alloc_len: usize,
+        context: &mut Context,
+    ) -> Result<(), EmulatorError> {
+        let data = &data[..alloc_len.min(data.len())];
+        for b in data {
+            self.output_fifo.borrow_mut().push(*b).unwrap();
         }
+
+        self.finalize_spi_cmd(data.len(), context, GdromState::PioEnd)
     }
 
-    pub fn reload_sector_cache(&mut self) {
+    pub fn reload_sector_cache(&mut self) -> Result<(), EmulatorError> {
         let mut count = self.read_context.remaining_sectors;
         if count > 32 {
             count = 32;
@@ -391,11 +482,11 @@ impl Gdrom {
         self.output_fifo.borrow_mut().clear();
 
         let mut buffer: Vec<u8> = vec![0; 2352 * count as usize];
-        let bytes_copied = self.gdi_image.as_ref().unwrap().load_sectors(
-            self.read_context.sector_start,
-            count,
-            &mut buffer,
-        );
+        let bytes_copied = self
+            .gdi_image
+            .as_ref()
+            .ok_or_else(Self::no_disc)?
+            .load_sectors(self.read_context.sector_start, count, &mut buffer);
 
         for b in &buffer[0..bytes_copied as usize] {
             self.output_fifo.borrow_mut().push(*b).unwrap();
@@ -403,21 +494,28 @@ impl Gdrom {
 
         self.read_context.sector_start += count;
         self.read_context.remaining_sectors -= count;
+        Ok(())
     }
 
-    pub fn process_spi_cmd(&mut self, parameters: &[u8], scheduler: &mut Scheduler) {
+    pub fn process_spi_cmd(
+        &mut self,
+        parameters: &[u8],
+        context: &mut Context,
+    ) -> Result<(), EmulatorError> {
         let cmd = parameters[0];
 
-        //  #[cfg(feature = "log_io")]
-        //
-        //
-        //
-        // println!(
-        //    "Sense: {:02x} 00 {:02x}",
-        //  self.registers.sns_asc, self.registers.sns_key
-        //);
-
-        //println!("SPI command {:02x};Params: {:?}", cmd, HexSlice(parameters));
+        if context.tracer.enabled(TraceCategory::Io) {
+            context.tracer.message(
+                TraceCategory::Io,
+                format!(
+                    "gdrom: spi command {:02x} params [{:?}] sense {:02x} 00 {:02x}",
+                    cmd,
+                    HexSlice(parameters),
+                    self.registers.sns_asc,
+                    self.registers.sns_key
+                ),
+            );
+        }
 
         if self.registers.sns_key != 0 {
             self.registers
@@ -438,12 +536,12 @@ impl Gdrom {
                         .eval_bit(0, (self.registers.sector_num_status & 0xf) == 0x0),
                 );
 
-                self.transition(scheduler, GdromState::FinishedProcessingPacket);
+                self.transition(context, GdromState::FinishedProcessingPacket)?;
             }
             0x70 => {
                 // 0x70 - undocumented SPI command
                 // we can safely treat this as a nop and ack the command
-                self.transition(scheduler, GdromState::FinishedProcessingPacket);
+                self.transition(context, GdromState::FinishedProcessingPacket)?;
             }
             0x71 => {
                 // 0x71 - undocumented SPI command
@@ -511,75 +609,76 @@ impl Gdrom {
                 self.registers.sector_num_status =
                     (self.registers.sector_num_status & !0xf) | (0x1 & 0xf);
 
-                self.finalize_spi_cmd(output.len() * 2, scheduler, GdromState::PioEnd);
+                self.finalize_spi_cmd(output.len() * 2, context, GdromState::PioEnd)?;
             }
             0x13 => {
                 // REQ_ERROR
                 let len = parameters[4] as usize;
 
-                self.pending_err = false;
-                if len > 0 {
-                    self.output_fifo.borrow_mut().push(0xf0).unwrap();
-                    self.output_fifo.borrow_mut().push(0x00).unwrap();
-                    self.output_fifo
-                        .borrow_mut()
-                        .push(self.registers.sns_key as u8)
-                        .unwrap();
-
-                    self.output_fifo.borrow_mut().push(0x00).unwrap();
-
-                    self.output_fifo.borrow_mut().push(0x00).unwrap();
-                    self.output_fifo.borrow_mut().push(0x00).unwrap();
-                    self.output_fifo.borrow_mut().push(0x00).unwrap();
-                    self.output_fifo.borrow_mut().push(0x00).unwrap();
-                    self.output_fifo
-                        .borrow_mut()
-                        .push(self.registers.sns_asc)
-                        .unwrap();
-                    self.output_fifo.borrow_mut().push(0x00).unwrap();
-                }
+                let output = [
+                    0xf0,
+                    0x00,
+                    self.registers.sns_key as u8,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x00,
+                    self.registers.sns_asc,
+                    0x00,
+                ];
 
+                self.pending_err = false;
                 self.registers.sns_key = 0;
                 self.registers.sns_asc = 0;
 
-                self.finalize_spi_cmd(len, scheduler, GdromState::PioEnd);
+                self.queue_output(&output, len, context)?;
             }
             0x11 => {
                 // REQ_MODE
                 let start_addr = parameters[2] as usize;
                 let len = parameters[4] as usize;
 
-                if len > 0 {
-                    let start_idx = start_addr / 2;
-                    let end_idx = start_idx + (len / 2);
-
-                    // use the start and end indices to read out of the REQ_MODE table
-                    for b in &REQ_MODE_TABLE[start_idx..end_idx] {
-                        let bytes = u16::to_le_bytes(*b);
-                        self.output_fifo.borrow_mut().push(bytes[0]).unwrap();
-                        self.output_fifo.borrow_mut().push(bytes[1]).unwrap();
-                    }
-
-                    let output_len = u16::to_le_bytes(len as u16);
-                    self.registers.byte_count_lo = output_len[0];
-                    self.registers.byte_count_hi = output_len[1];
-                }
+                // the table is read a word at a time
+                let table: Vec<u8> = REQ_MODE_TABLE
+                    .iter()
+                    .flat_map(|w| w.to_le_bytes())
+                    .collect();
+                let Some(output) = table.get(start_addr & !1..) else {
+                    return Err(EmulatorError::unsupported(
+                        Subsystem::Gdrom,
+                        DATA_REGISTER,
+                        format!("req_mode from offset {}", start_addr),
+                    ));
+                };
 
-                self.finalize_spi_cmd(len, scheduler, GdromState::PioEnd);
+                self.queue_output(output, len & !1, context)?;
             }
             0x14 => {
                 // REQ_TOC
                 let area = (parameters[1] & 0x1) as usize; // select bit
                 let len = u16::from_le_bytes([parameters[4], parameters[3]]);
-                let img = self.gdi_image.as_ref().unwrap();
+                let img = self.gdi_image.as_ref().ok_or_else(Self::no_disc)?;
                 let mut dest: [u8; 408] = [0xff; 408];
 
                 let (start_track, end_track) = match area {
-                    1 => (2, img.tracks.len() - 1),
+                    1 => (2, img.tracks.len().saturating_sub(1)),
                     0 => (0, 2),
                     _ => unreachable!(),
                 };
 
+                if start_track > end_track || end_track >= img.tracks.len() {
+                    return Err(EmulatorError::unsupported(
+                        Subsystem::Gdrom,
+                        DATA_REGISTER,
+                        format!(
+                            "req_toc for area {} of a {} track disc",
+                            area,
+                            img.tracks.len()
+                        ),
+                    ));
+                }
+
                 dest[0..396].fill(0xFF);
 
                 for i in start_track..=end_track {
@@ -611,44 +710,25 @@ impl Gdrom {
 
                 dest[404..408].copy_from_slice(&leadout_info);
 
-                for b in &dest[0..(408 as usize).min(len as usize)] {
-                    self.output_fifo.borrow_mut().push(*b);
-                }
-
-                self.finalize_spi_cmd(
-                    (parameters[4] as usize | (parameters[3] as usize) << 8) as usize,
-                    scheduler,
-                    GdromState::PioEnd,
-                );
+                self.queue_output(&dest, len as usize, context)?;
             }
             0x15 => {
                 //  REQ_SESSION
                 let session_number = parameters[2];
                 let len = parameters[4];
                 let tno = 1;
-                let fad = Self::lba_to_fad(self.get_leadout() as usize);
-
-                self.output_fifo
-                    .borrow_mut()
-                    .push((self.registers.sector_num_status & 0xf) as u8)
-                    .unwrap();
-                self.output_fifo.borrow_mut().push(0x0).unwrap();
-                self.output_fifo.borrow_mut().push(tno).unwrap();
-                self.output_fifo
-                    .borrow_mut()
-                    .push(((fad >> 16) & 0xff) as u8)
-                    .unwrap();
-                self.output_fifo
-                    .borrow_mut()
-                    .push(((fad >> 8) & 0xff) as u8)
-                    .unwrap();
-                self.output_fifo
-                    .borrow_mut()
-                    .push((fad & 0xff) as u8)
-                    .unwrap();
-
-                //    panic!("req_session is likely bugged");
-                self.finalize_spi_cmd(len as usize, scheduler, GdromState::PioEnd);
+                let fad = Self::lba_to_fad(self.get_leadout()?);
+
+                let output = [
+                    (self.registers.sector_num_status & 0xf) as u8,
+                    0x0,
+                    tno,
+                    ((fad >> 16) & 0xff) as u8,
+                    ((fad >> 8) & 0xff) as u8,
+                    (fad & 0xff) as u8,
+                ];
+
+                self.queue_output(&output, len as usize, context)?;
             }
             0x30 => {
                 // CD_READ
@@ -670,9 +750,14 @@ impl Gdrom {
                     | (parameters[9] as u32) << 8
                     | (parameters[10] as u32);
 
-                assert!(parameter_type == 0); // start_addr is a FAD
-                assert!(expected_data_type == 0b010); // Mode 1
-                assert!(data_select == 0b0010); // Data (no header or subheader)
+                // only fad addressed mode 1 data reads (no header or subheader) are supported
+                if parameter_type != 0 || expected_data_type != 0b010 || data_select != 0b0010 {
+                    return Err(EmulatorError::unsupported(
+                        Subsystem::Gdrom,
+                        DATA_REGISTER,
+                        format!("cd_read with parameters {:02x}", parameters[1]),
+                    ));
+                }
 
                 if (start_addr < 45000) {
                     //      start_addr += 150;
@@ -688,14 +773,18 @@ impl Gdrom {
                     cache_size: transfer_length * 2048,
                     cache_index: 0,
                 };
-                #[cfg(feature = "log_io")]
-                println!(
-                    "SPI_CD_READ - Sector={} Size={}/{} DMA={}",
-                    self.read_context.sector_start,
-                    transfer_length,
-                    self.read_context.sector_type,
-                    if is_dma { 1 } else { 0 },
-                );
+                if context.tracer.enabled(TraceCategory::Gdrom) {
+                    context.tracer.message(
+                        TraceCategory::Gdrom,
+                        format!(
+                            "SPI_CD_READ - Sector={} Size={}/{} DMA={}",
+                            self.read_context.sector_start,
+                            transfer_length,
+                            self.read_context.sector_type,
+                            if is_dma { 1 } else { 0 },
+                        ),
+                    );
+                }
 
                 let mut buffer: Vec<u8> = vec![0; 2352 * transfer_length as usize];
                 let mut count = transfer_length as u32;
@@ -703,13 +792,17 @@ impl Gdrom {
                     //   count = 32;
                 }
 
-                let bytes_copied =
-                    self.gdi_image
-                        .as_ref()
-                        .unwrap()
-                        .load_sectors(start_addr, count, &mut buffer);
-                #[cfg(feature = "log_io")]
-                println!("SPI_CD_READ - copied {} bytes", bytes_copied);
+                let bytes_copied = self
+                    .gdi_image
+                    .as_ref()
+                    .ok_or_else(Self::no_disc)?
+                    .load_sectors(start_addr, count, &mut buffer);
+                if context.tracer.enabled(TraceCategory::Gdrom) {
+                    context.tracer.message(
+                        TraceCategory::Gdrom,
+                        format!("SPI_CD_READ - copied {} bytes", bytes_copied),
+                    );
+                }
 
                 if is_dma {
                     self.read_context.cache_size = count * self.read_context.sector_type;
@@ -723,30 +816,30 @@ impl Gdrom {
 
                 let flen = bytes_copied;
                 if !is_dma {
-                    self.finalize_spi_cmd(flen as usize, scheduler, GdromState::PioEnd)
+                    self.finalize_spi_cmd(flen as usize, context, GdromState::PioEnd)?;
                 }
             }
-            0x12 => self.transition(scheduler, GdromState::FinishedProcessingPacket),
+            0x12 => self.transition(context, GdromState::FinishedProcessingPacket)?,
             0x40 => {
                 let alloc_len = (parameters[4] as usize | (parameters[3] as usize) << 8) as usize;
 
-                self.output_fifo.borrow_mut().push(0);
-                self.output_fifo.borrow_mut().push(0x15);
-                for _ in 0..alloc_len - 2 {
-                    self.output_fifo.borrow_mut().push(0);
-                }
+                // audio status "no status", the rest is zero padding
+                let mut output = vec![0; alloc_len.max(2)];
+                output[1] = 0x15;
 
-                self.finalize_spi_cmd(
-                    (parameters[4] as usize | (parameters[3] as usize) << 8) as usize,
-                    scheduler,
-                    GdromState::PioEnd,
-                );
+                self.queue_output(&output, alloc_len, context)?;
             }
             _ => {
-                self.transition(scheduler, GdromState::FinishedProcessingPacket);
+                self.transition(context, GdromState::FinishedProcessingPacket)?;
                 println!("gdrom unimplemented spi command {:02x}", cmd)
             }
         }
+
+        Ok(())
+    }
+
+    fn no_disc() -> EmulatorError {
+        EmulatorError::unsupported(Subsystem::Gdrom, DATA_REGISTER, "no disc inserted")
     }
 
     pub fn lba_to_fad(lba: usize) -> usize {
@@ -761,11 +854,15 @@ impl Gdrom {
         return (minutes as u32) * 60 * 75 + (seconds as u32) * 75 + (frame as u32);
     }
 
-    fn get_leadout(&self) -> usize {
-        let last_track = self.gdi_image.as_ref().unwrap().tracks.last().unwrap();
+    fn get_leadout(&self) -> Result<usize, EmulatorError> {
+        let last_track = self
+            .gdi_image
+            .as_ref()
+            .and_then(|image| image.tracks.last())
+            .ok_or_else(Self::no_disc)?;
         let sector_size = last_track.sector_size;
 
         let offset = Self::fad_to_lba(last_track.offset);
-        return (last_track.data.len() / sector_size) + offset;
+        Ok((last_track.data.len() / sector_size) + offset)
     }
 }
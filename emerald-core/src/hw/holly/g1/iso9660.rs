@@ -0,0 +1,100 @@
+// just enough iso9660 to find a file in a data track's root directory, which is all booting a disc needs.
+// extents are lbas from the start of the disc rather than the track, on gd-roms and multisession cds alike.
+
+use super::gdi::Track;
+
+pub const SECTOR_SIZE: usize = 2048;
+
+const PRIMARY_VOLUME_DESCRIPTOR: u32 = 16; // sectors into the track
+const ROOT_DIRECTORY_RECORD: usize = 156;
+
+pub struct Filesystem<'a> {
+    track: &'a Track,
+}
+
+impl<'a> Filesystem<'a> {
+    // none if the track doesn't start with an iso9660 volume
+    pub fn new(track: &'a Track) -> Option<Self> {
+        let filesystem = Self { track };
+        let descriptor = filesystem.read_sector(track.offset as u32 + PRIMARY_VOLUME_DESCRIPTOR)?;
+
+        if &descriptor[..6] != b"\x01CD001" {
+            return None;
+        }
+
+        Some(filesystem)
+    }
+
+    // the user data of one mode 1 or mode 2 form 1 sector
+    pub fn read_sector(&self, fad: u32) -> Option<[u8; SECTOR_SIZE]> {
+        let track = self.track;
+        let index = (fad as usize).checked_sub(track.offset)?;
+        let start = index * track.sector_size;
+        let sector = track.data.get(start..start + track.sector_size)?;
+
+        // mode 2 sectors have an 8 byte subheader before the data
+        let data = match (track.sector_size, sector.get(0x0f)) {
+            (2048, _) => sector,
+            (2352, Some(1)) => &sector[0x10..],
+            (2352, Some(2)) => &sector[0x18..],
+            _ => return None,
+        };
+
+        Some(data[..SECTOR_SIZE].try_into().unwrap())
+    }
+
+    pub fn read_sectors(&self, fad: u32, count: u32) -> Option<Vec<u8>> {
+        let mut data = Vec::with_capacity(count as usize * SECTOR_SIZE);
+        for i in 0..count {
+            data.extend_from_slice(&self.read_sector(fad + i)?);
+        }
+
+        Some(data)
+    }
+
+    // a file in the root directory, matched ignoring case and the ;1 version suffix
+    pub fn read_file(&self, name: &str) -> Option<Vec<u8>> {
+        let descriptor = self.read_sector(self.track.offset as u32 + PRIMARY_VOLUME_DESCRIPTOR)?;
+        let (root, root_size) = extent(&descriptor[ROOT_DIRECTORY_RECORD..])?;
+        let directory = self.read_sectors(root, sectors(root_size))?;
+
+        let mut offset = 0;
+        while offset < directory.len() {
+            let length = directory[offset] as usize;
+
+            // records don't cross sectors, a zero length pads out to the next one
+            if length == 0 {
+                offset = (offset / SECTOR_SIZE + 1) * SECTOR_SIZE;
+                continue;
+            }
+
+            let record = directory.get(offset..offset + length)?;
+            let is_directory = record.get(25)? & 2 != 0;
+            let name_length = *record.get(32)? as usize;
+            let record_name = record.get(33..33 + name_length)?;
+            let record_name = record_name.split(|&b| b == b';').next().unwrap_or_default();
+
+            if !is_directory && record_name.eq_ignore_ascii_case(name.as_bytes()) {
+                let (fad, size) = extent(record)?;
+                let mut data = self.read_sectors(fad, sectors(size))?;
+                data.truncate(size as usize);
+                return Some(data);
+            }
+
+            offset += length;
+        }
+
+        None
+    }
+}
+
+// where a directory record's data starts, as a fad, and its size in bytes
+fn extent(record: &[u8]) -> Option<(u32, u32)> {
+    let lba = u32::from_le_bytes(record.get(2..6)?.try_into().unwrap());
+    let size = u32::from_le_bytes(record.get(10..14)?.try_into().unwrap());
+    Some((lba + 150, size))
+}
+
+fn sectors(size: u32) -> u32 {
+    size.div_ceil(SECTOR_SIZE as u32)
+}
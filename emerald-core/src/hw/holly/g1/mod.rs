@@ -1,13 +1,18 @@
 use self::{boot_rom::BootROM, gdrom::Gdrom};
-use crate::{context::Context, hw::sh4::bus::PhysicalAddress};
+use crate::{
+    context::Context,
+    error::{EmulatorError, Subsystem},
+    hw::sh4::bus::PhysicalAddress,
+};
 
 pub mod boot_rom;
 pub mod cdi;
 pub mod gdi;
 pub mod gdrom;
+pub mod iso9660;
 
 pub struct G1Bus {
-    boot_rom: BootROM,
+    pub boot_rom: BootROM,
     pub gd_rom: Gdrom,
 }
 
@@ -19,55 +24,82 @@ impl G1Bus {
         }
     }
 
-    pub fn write_32(&mut self, addr: PhysicalAddress, value: u32) {
+    pub fn write_32(&mut self, addr: PhysicalAddress, value: u32) -> Result<(), EmulatorError> {
         match addr.0 {
-            0x005f7018..=0x005f709c => self.gd_rom.write_32(addr, value),
-            _ => panic!(
-                "g1: unknown mmio write (32-bit) @ 0x{:08x} with value 0x{:08x}",
-                addr.0, value
-            ),
+            0x005f7018..=0x005f709c => {
+                self.gd_rom.write_32(addr, value);
+                Ok(())
+            }
+            _ => Err(EmulatorError::unknown_write(
+                Subsystem::G1Bus,
+                addr.0,
+                value,
+                32,
+            )),
         }
     }
 
-    pub fn write_16(&mut self, addr: PhysicalAddress, value: u16, context: &mut Context) {
+    pub fn write_16(
+        &mut self,
+        addr: PhysicalAddress,
+        value: u16,
+        context: &mut Context,
+    ) -> Result<(), EmulatorError> {
         match addr.0 {
             0x005f7018..=0x005f709c => self.gd_rom.write_16(addr, value, context),
-            _ => panic!(
-                "g1: unknown mmio write (16-bit) @ 0x{:08x} with value 0x{:08x}",
-                addr.0, value
-            ),
+            _ => Err(EmulatorError::unknown_write(
+                Subsystem::G1Bus,
+                addr.0,
+                value as u32,
+                16,
+            )),
         }
     }
 
-    pub fn write_8(&mut self, addr: PhysicalAddress, value: u8, context: &mut Context) {
+    pub fn write_8(
+        &mut self,
+        addr: PhysicalAddress,
+        value: u8,
+        context: &mut Context,
+    ) -> Result<(), EmulatorError> {
         match addr.0 {
             // gd-rom
             0x005f7018..=0x005f709c => self.gd_rom.write_8(addr, value, context),
-            _ => panic!(
-                "g1: unknown mmio write (8-bit) @ 0x{:08x} with value 0x{:08x}",
-                addr.0, value
-            ),
+            _ => Err(EmulatorError::unknown_write(
+                Subsystem::G1Bus,
+                addr.0,
+                value as u32,
+                8,
+            )),
         }
     }
 
-    pub fn read_32(&self, addr: PhysicalAddress) -> u32 {
+    pub fn read_32(&self, addr: PhysicalAddress) -> Result<u32, EmulatorError> {
         match addr.0 {
-            _ => panic!("g1: unknown mmio read (32-bit) @ 0x{:08x}", addr.0),
+            _ => Err(EmulatorError::unknown_read(Subsystem::G1Bus, addr.0, 32)),
         }
     }
 
-    pub fn read_16(&self, addr: PhysicalAddress, context: &mut Context) -> u16 {
+    pub fn read_16(
+        &self,
+        addr: PhysicalAddress,
+        context: &mut Context,
+    ) -> Result<u16, EmulatorError> {
         match addr.0 {
             0x005f7018..=0x005f709c => self.gd_rom.read_16(addr, context),
-            _ => panic!("g1: unknown mmio read (16-bit) @ 0x{:08x}", addr.0),
+            _ => Err(EmulatorError::unknown_read(Subsystem::G1Bus, addr.0, 16)),
         }
     }
 
-    pub fn read_8(&self, addr: PhysicalAddress, context: &mut Context) -> u8 {
+    pub fn read_8(
+        &self,
+        addr: PhysicalAddress,
+        context: &mut Context,
+    ) -> Result<u8, EmulatorError> {
         match addr.0 {
             0..=0x0023ffff => self.boot_rom.read_8(addr),
-            0x005f7018..=0x005f709c => self.gd_rom.read_8(addr, context),
-            _ => panic!("g1: unknown mmio read (8-bit) @ 0x{:08x}", addr.0),
+            0x005f7018..=0x005f709c => Ok(self.gd_rom.read_8(addr, context)),
+            _ => Err(EmulatorError::unknown_read(Subsystem::G1Bus, addr.0, 8)),
         }
     }
 }
@@ -4,8 +4,9 @@ use crate::{
     hw::extensions::{BarrelShifter, BitManipulation, ShiftType},
     scheduler::Scheduler,
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Serialize, Deserialize)]
 pub struct CpuRegisters {
     pub r0: u32,
     pub r1: u32,
@@ -80,6 +81,7 @@ impl Default for CpuRegisters {
 
 type ArmInstructionHandler<'b> = fn(&mut Cpu, u32, &'b mut ArmBus);
 
+#[derive(Serialize, Deserialize)]
 pub struct Cpu {
     pub registers: CpuRegisters,
     pub running: bool,
@@ -133,6 +135,11 @@ impl Cpu {
         self.registers.r15 - 8
     }
 
+    // true between instructions, the sh4 clocks us 8 times per arm instruction
+    pub fn at_instruction_boundary(&self) -> bool {
+        self.cyc == 0
+    }
+
     pub fn set_spsr(&mut self, spsr: u32) {
         match self.registers.cpsr & 0x1F {
             0x11 => self.registers.spsr_fiq = spsr,
@@ -276,6 +283,13 @@ impl Cpu {
         self.flushed = true;
     }
 
+    // redirects the cpu between instructions. flush_pipeline is for branches, which step accounts for
+    pub fn set_pc<'b>(&mut self, pc: u32, bus: &ArmBus<'b>) {
+        self.registers.r15 = pc;
+        self.flush_pipeline(bus);
+        self.flushed = false;
+    }
+
     pub fn set_mode(&mut self, mode: u32) {
         self.registers.cpsr &= !0x1F;
         self.registers.cpsr |= mode & 0x1F;
@@ -288,8 +302,7 @@ impl Cpu {
 
             self.set_mode(0x13);
             self.registers.cpsr = (self.registers.cpsr.clear_bit(5).set_bit(6).set_bit(7));
-            self.registers.r15 = 0x00;
-            self.flush_pipeline(bus);
+            self.set_pc(0x00, bus);
         }
 
         self.running = enable;
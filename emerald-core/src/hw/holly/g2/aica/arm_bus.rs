@@ -2,6 +2,9 @@ use crate::scheduler::Scheduler;
 
 use super::Aica;
 
+#[cfg(feature = "json_tests")]
+use crate::hw::sh4::test_bus::BusCycleKind;
+
 pub struct ArmBus<'a> {
     pub aica: &'a mut Aica,
 }
@@ -11,6 +14,11 @@ impl<'a> ArmBus<'a> {
     const EXTERNAL_THRESHOLD: usize = 0x800000;
 
     pub fn read_32(&self, physical_addr: u32) -> u32 {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &self.aica.test_bus {
+            return test_bus.read(physical_addr, 32, BusCycleKind::Read) as u32;
+        }
+
         let physical_addr = physical_addr as usize;
 
         match physical_addr {
@@ -30,6 +38,11 @@ impl<'a> ArmBus<'a> {
     }
 
     pub fn fetch_32(&self, physical_addr: u32) -> u32 {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &self.aica.test_bus {
+            return test_bus.read(physical_addr, 32, BusCycleKind::Fetch) as u32;
+        }
+
         let physical_addr = physical_addr as usize;
 
         let addr_base = physical_addr & Self::MASK as usize;
@@ -42,6 +55,11 @@ impl<'a> ArmBus<'a> {
     }
 
     pub fn read_16(&self, physical_addr: u32) -> u16 {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &self.aica.test_bus {
+            return test_bus.read(physical_addr, 16, BusCycleKind::Read) as u16;
+        }
+
         let physical_addr = physical_addr as usize;
         match physical_addr {
             0..Self::EXTERNAL_THRESHOLD => {
@@ -61,6 +79,11 @@ impl<'a> ArmBus<'a> {
     }
 
     pub fn read_8(&self, physical_addr: u32) -> u8 {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &self.aica.test_bus {
+            return test_bus.read(physical_addr, 8, BusCycleKind::Read) as u8;
+        }
+
         let physical_addr = physical_addr as usize;
         match physical_addr {
             0..=Self::EXTERNAL_THRESHOLD => {
@@ -76,6 +99,11 @@ impl<'a> ArmBus<'a> {
     }
 
     pub fn write_32(&mut self, physical_addr: u32, value: u32) {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &mut self.aica.test_bus {
+            return test_bus.write(physical_addr, 32, value as u64);
+        }
+
         let physical_addr = physical_addr as usize;
         match physical_addr {
             0..=Self::EXTERNAL_THRESHOLD => {
@@ -96,6 +124,11 @@ impl<'a> ArmBus<'a> {
     }
 
     pub fn write_16(&mut self, physical_addr: u32, value: u16) {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &mut self.aica.test_bus {
+            return test_bus.write(physical_addr, 16, value as u64);
+        }
+
         let physical_addr = physical_addr as usize;
         match physical_addr {
             0..=Self::EXTERNAL_THRESHOLD => {
@@ -112,6 +145,11 @@ impl<'a> ArmBus<'a> {
     }
 
     pub fn write_8(&mut self, physical_addr: u32, value: u8) {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &mut self.aica.test_bus {
+            return test_bus.write(physical_addr, 8, value as u64);
+        }
+
         let physical_addr = physical_addr as usize;
         match physical_addr {
             0..=Self::EXTERNAL_THRESHOLD => {
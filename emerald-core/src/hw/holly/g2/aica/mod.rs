@@ -1,19 +1,24 @@
 use rtc::Rtc;
 
 use crate::hw::{extensions::BitManipulation, sh4::bus::PhysicalAddress};
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+#[cfg(feature = "json_tests")]
+use crate::hw::sh4::test_bus::TestBus;
 
 pub mod arm;
 pub mod arm_bus;
 pub mod rtc;
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 #[repr(C)]
 pub struct ChannelRegisters {
     pub r: [u32; 18],
     pub inactive: [u32; 14], // inactive registers
 }
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
 #[repr(C)]
 pub struct TimerControlRegister(pub u32);
 
@@ -41,12 +46,18 @@ impl TimerControlRegister {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Aica {
     pub wave_ram: Vec<u8>,
     pub rtc: Rtc,
+    #[serde(with = "BigArray")]
     pub channels: [ChannelRegisters; 64],
     pub timers: [TimerControlRegister; 3],
     pub sound_cpu_interrupts_enabled: bool,
+
+    #[cfg(feature = "json_tests")]
+    #[serde(skip)]
+    pub test_bus: Option<TestBus>, // takes every arm access when set, see sh4/test_bus.rs
 }
 
 impl Aica {
@@ -57,6 +68,8 @@ impl Aica {
             channels: [Default::default(); 64],
             timers: [Default::default(); 3],
             sound_cpu_interrupts_enabled: false,
+            #[cfg(feature = "json_tests")]
+            test_bus: None,
         }
     }
 
@@ -1,8 +1,10 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::hw::holly::HollyEventData;
+use serde::{Deserialize, Serialize};
 
 pub struct RtcRegisters {}
+#[derive(Serialize, Deserialize)]
 pub struct Rtc {
     pub timestamp: u32,
 }
@@ -39,6 +41,6 @@ impl Rtc {
         scheduler.schedule(crate::scheduler::ScheduledEvent::HollyEvent {
             deadline: 200 * 1000 * 1000,
             event_data: HollyEventData::Rtc,
-        })
+        });
     }
 }
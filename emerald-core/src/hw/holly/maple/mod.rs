@@ -1,17 +1,22 @@
 use std::mem;
 
+use crate::error::{EmulatorError, Subsystem};
 use crate::hw::extensions::BitManipulation;
 use crate::scheduler::Scheduler;
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Clone, Debug, Eq, PartialEq)]
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct MapleRegisters {}
 
+#[derive(Serialize, Deserialize)]
 pub struct Maple {
     registers: MapleRegisters,
     pub is_a_pressed: bool,
     pub is_start_pressed: bool,
     pub is_x_pressed: bool,
     pub is_b_pressed: bool,
+    pub is_y_pressed: bool,
+    pub is_left_pressed: bool,
     pub is_right_pressed: bool,
     pub is_up_pressed: bool,
     pub is_down_pressed: bool,
@@ -95,6 +100,8 @@ impl Maple {
             },
             is_a_pressed: false,
             is_b_pressed: false,
+            is_y_pressed: false,
+            is_left_pressed: false,
             is_right_pressed: false,
             is_start_pressed: false,
             is_x_pressed: false,
@@ -107,7 +114,13 @@ impl Maple {
         unsafe { std::slice::from_raw_parts(bytes.as_ptr() as *const u32, bytes.len() / 4) }
     }
 
-    pub fn process_maple_frame(&mut self, tx_frame: &MapleFrame, rx_frame: &mut MapleFrame) -> u8 {
+    // addr is where the frame was read from, for errors
+    pub fn process_maple_frame(
+        &mut self,
+        addr: u32,
+        tx_frame: &MapleFrame,
+        rx_frame: &mut MapleFrame,
+    ) -> Result<u8, EmulatorError> {
         let cmd_id = tx_frame.cmd;
         // println!("maple: got command {:08x}", cmd_id);
 
@@ -166,17 +179,20 @@ impl Maple {
                     );
                 }
 
-                return (mem::size_of::<MapleDeviceInfo>() >> 2) as u8;
+                Ok((mem::size_of::<MapleDeviceInfo>() >> 2) as u8)
             }
             0x09 => {
                 let condition_response = MapleConditionResponse {
                     func: (0x01000000_u32),
                     buttons: 0xffff
+                        .eval_bit(1, !self.is_b_pressed)
                         .eval_bit(2, !self.is_a_pressed)
                         .eval_bit(3, !self.is_start_pressed)
                         .eval_bit(4, !self.is_up_pressed)
                         .eval_bit(5, !self.is_down_pressed)
+                        .eval_bit(6, !self.is_left_pressed)
                         .eval_bit(7, !self.is_right_pressed)
+                        .eval_bit(9, !self.is_y_pressed)
                         .eval_bit(10, !self.is_x_pressed),
                     right_trigger: 0,
                     left_trigger: 0,
@@ -200,12 +216,14 @@ impl Maple {
                 }
 
                 // println!("{}", self.is_right_pressed);
-                return (mem::size_of::<MapleConditionResponse>() >> 2) as u8;
-            }
-            _ => {
-                panic!("maple: got an unimplemented command {:08x}", cmd_id);
+                Ok((mem::size_of::<MapleConditionResponse>() >> 2) as u8)
             }
-        };
+            _ => Err(EmulatorError::unimplemented_command(
+                Subsystem::Maple,
+                addr,
+                cmd_id as u32,
+            )),
+        }
     }
 
     pub fn perform_maple_transfer(
@@ -213,7 +231,7 @@ impl Maple {
         start_offset: usize,
         scheduler: &mut Scheduler,
         system_ram: &mut [u8],
-    ) {
+    ) -> Result<(), EmulatorError> {
         let mut send_offset = start_offset;
         loop {
             let command_header = u32::from_le_bytes([
@@ -247,6 +265,7 @@ impl Maple {
                     send_offset += 4;
 
                     // read out the send frame
+                    let frame_addr = 0x0c000000 + send_offset as u32;
                     let tx_frame = {
                         let command_data = Self::as_u32_slice(&system_ram[send_offset..]);
                         unsafe { &*(command_data.as_ptr() as *const MapleFrame) }
@@ -262,7 +281,7 @@ impl Maple {
                     };
 
                     // process cmd + write out the response
-                    let size = self.process_maple_frame(tx_frame, &mut rx_frame);
+                    let size = self.process_maple_frame(frame_addr, tx_frame, &mut rx_frame)?;
                     rx_frame.length = size;
 
                     system_ram[recv_offset] = rx_frame.cmd;
@@ -281,7 +300,13 @@ impl Maple {
                     send_offset += transfer_len_in_bytes;
                 }
                 0x07 => {}
-                _ => panic!("maple: got an unrecognized pattern {:08x}", pattern),
+                _ => {
+                    return Err(EmulatorError::unsupported(
+                        Subsystem::Maple,
+                        0x0c000000 + send_offset as u32 - 4,
+                        format!("unrecognized pattern {:08x}", pattern),
+                    ))
+                }
             };
 
             if command_header.check_bit(31) {
@@ -294,6 +319,8 @@ impl Maple {
             event_data: super::HollyEventData::RaiseInterruptNormal {
                 istnrm: 0.set_bit(12),
             },
-        })
+        });
+
+        Ok(())
     }
 }
@@ -14,14 +14,18 @@ use self::{
 };
 use crate::{
     context::Context,
+    error::{EmulatorError, Subsystem},
     hw::{
         extensions::{BitManipulation, SliceExtensions},
         holly::g1::gdrom::GdromState,
     },
     scheduler::{ScheduledEvent, Scheduler},
+    trace::TraceCategory,
 };
 
-use super::sh4::{bus::PhysicalAddress, dmac::Dmac, intc::InterruptKind};
+use super::sh4::{bus::PhysicalAddress, debug::Debugger, dmac::Dmac, intc::InterruptKind};
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
 pub mod g1;
 pub mod g2;
 pub mod maple;
@@ -29,7 +33,7 @@ pub mod pvr;
 pub mod sb;
 pub mod spg;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone, Serialize, Deserialize)]
 pub enum HollyEventData {
     RaiseInterruptNormal { istnrm: u32 },
     RaiseInterruptExternal { istext: u32 },
@@ -41,12 +45,14 @@ pub enum HollyEventData {
     MapleDMA,
     Rtc,
     Ch2DMA,
+    Ch2DMAEnd,
     GdromDMA,
+    GdromDMAEnd,
     AicaDMA,
     VBlank,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct HollyRegisters {
     pub border_col: u32,
     pub video_cfg: u32,
@@ -55,6 +61,7 @@ pub struct HollyRegisters {
     pub fog_table_col: u32,
     pub fog_vertex_col: u32,
     pub fog_density: u32,
+    #[serde(with = "BigArray")]
     pub fog_table: [u32; 0x1fc],
     pub hpos_irq: u32,
 
@@ -113,6 +120,7 @@ pub struct Holly {
     pub framebuffer: Framebuffer,
     pub aica: Aica,
     pub arm7tdmi: Cpu,
+    pub arm7_debugger: Debugger, // breakpoints only, the arm's bus doesn't report accesses
 }
 
 impl Holly {
@@ -127,6 +135,7 @@ impl Holly {
             framebuffer: Default::default(),
             aica: Aica::new(),
             arm7tdmi: Cpu::new(),
+            arm7_debugger: Debugger::new(),
             cyc: 0,
         }
     }
@@ -138,48 +147,50 @@ impl Holly {
 
     pub fn on_scheduled_event(
         &mut self,
-        scheduler: &mut Scheduler,
+        context: &mut Context,
         dmac: &mut Dmac, // less than ideal, for ch2 dma
         ram: &mut [u8],
         target: u64,
         overrun: u64,
         event: HollyEventData,
-    ) {
+    ) -> Result<(), EmulatorError> {
         match event {
             HollyEventData::SpgEvent(spg_event) => self.spg.on_scheduled_event(
-                scheduler,
+                &mut context.scheduler,
                 &mut self.sb,
                 self.framebuffer.registers.read_ctrl.raw,
                 target,
                 overrun,
                 spg_event,
             ),
-            HollyEventData::Rtc => self.aica.rtc.on_scheduled_event(scheduler),
+            HollyEventData::Rtc => self.aica.rtc.on_scheduled_event(&mut context.scheduler),
             HollyEventData::GdromEvent(gdrom_event) => {
-                self.g1_bus
-                    .gd_rom
-                    .on_scheduled_event(scheduler, &mut self.sb, gdrom_event)
+                self.g1_bus.gd_rom.on_scheduled_event(
+                    &mut context.scheduler,
+                    &mut self.sb,
+                    gdrom_event,
+                )?;
             }
             HollyEventData::RecalculateInterrupts => {
-                self.dispatch_sh4_interrupt(scheduler);
+                self.dispatch_sh4_interrupt(&mut context.scheduler);
             }
             HollyEventData::RaiseInterruptNormal { istnrm } => {
                 self.sb.registers.istnrm |= istnrm;
 
-                self.dispatch_sh4_interrupt(scheduler);
+                self.dispatch_sh4_interrupt(&mut context.scheduler);
             }
             HollyEventData::RaiseInterruptExternal { istext } => {
                 self.sb.registers.istext |= istext;
-                self.dispatch_sh4_interrupt(scheduler);
+                self.dispatch_sh4_interrupt(&mut context.scheduler);
             }
             HollyEventData::LowerExternalInterrupt { istext } => {
                 self.sb.registers.istext &= !istext;
-                self.dispatch_sh4_interrupt(scheduler);
+                self.dispatch_sh4_interrupt(&mut context.scheduler);
             }
             HollyEventData::FrameReady(_) => {}
             HollyEventData::AicaDMA => {
                 if self.sb.registers.ad_en == 0 {
-                    return;
+                    return Ok(());
                 }
 
                 let start_addr = self.sb.registers.ad_star;
@@ -212,24 +223,28 @@ impl Holly {
                 self.sb.registers.ad_star += len;
                 self.sb.registers.ad_stag += len;
 
-                scheduler.schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                    deadline: 70000,
-                    event_data: HollyEventData::RaiseInterruptNormal {
-                        istnrm: 0_u32.set_bit(15),
-                    },
-                });
+                context
+                    .scheduler
+                    .schedule(crate::scheduler::ScheduledEvent::HollyEvent {
+                        deadline: 70000,
+                        event_data: HollyEventData::RaiseInterruptNormal {
+                            istnrm: 0_u32.set_bit(15),
+                        },
+                    });
 
-                scheduler.schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                    deadline: 70000, // fixme: timing
-                    event_data: HollyEventData::RaiseInterruptExternal {
-                        istext: 0.set_bit(1),
-                    },
-                });
+                context
+                    .scheduler
+                    .schedule(crate::scheduler::ScheduledEvent::HollyEvent {
+                        deadline: 70000, // fixme: timing
+                        event_data: HollyEventData::RaiseInterruptExternal {
+                            istext: 0.set_bit(1),
+                        },
+                    });
             }
             HollyEventData::MapleDMA => {
                 let start = (self.sb.registers.mdstar - 0x0c000000) as usize;
                 self.maple
-                    .perform_maple_transfer(start, scheduler, &mut ram[0..]);
+                    .perform_maple_transfer(start, &mut context.scheduler, &mut ram[0..])?;
                 self.sb.registers.mdst = 0;
             }
             HollyEventData::VBlank => {}
@@ -239,14 +254,21 @@ impl Holly {
                     let len = self.sb.registers.gd_len as usize;
                     let direction = self.sb.registers.gd_dir;
 
-                    #[cfg(feature = "log_io")]
-                    println!("performing gd-dma {:08x} {}", dest_addr, len);
+                    if context.tracer.enabled(TraceCategory::Dma) {
+                        context.tracer.message(
+                            TraceCategory::Dma,
+                            format!("performing gd-dma {:08x} {}", dest_addr, len),
+                        );
+                    }
 
                     if direction == 0 {
-                        unimplemented!();
+                        return Err(EmulatorError::unsupported(
+                            Subsystem::Gdrom,
+                            dest_addr as u32,
+                            "dma to the drive",
+                        ));
                     }
 
-                    self.sb.registers.gd_st = 1;
                     self.sb.registers.gd_lend = 0;
                     self.sb.registers.gd_stard = dest_addr as u32;
 
@@ -262,21 +284,30 @@ impl Holly {
                     }
 
                     dmac.registers.dar0 = dest_addr as u32;
-                    self.sb.registers.gd_st = 0;
                     self.sb.registers.gd_lend += len as u32;
                     self.sb.registers.gd_stard += len as u32;
 
                     self.g1_bus
                         .gd_rom
-                        .transition(scheduler, GdromState::FinishedProcessingPacket);
-                    scheduler.schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                        deadline: 20000,
-                        event_data: HollyEventData::RaiseInterruptExternal {
-                            istext: 0.set_bit(14),
-                        },
-                    });
+                        .transition(context, GdromState::FinishedProcessingPacket)?;
+
+                    // the data moved at once, gd_st stays up for as long as the transfer would take
+                    self.sb.gd_dma_event =
+                        Some(context.scheduler.schedule(ScheduledEvent::HollyEvent {
+                            deadline: 20000,
+                            event_data: HollyEventData::GdromDMAEnd,
+                        }));
+                } else {
+                    self.sb.gd_dma_event = None;
+                    self.sb.registers.gd_st = 0;
                 }
             }
+            HollyEventData::GdromDMAEnd => {
+                self.sb.gd_dma_event = None;
+                self.sb.registers.gd_st = 0;
+                self.sb.registers.istext |= 0.set_bit(14);
+                self.dispatch_sh4_interrupt(&mut context.scheduler);
+            }
             HollyEventData::Ch2DMA => {
                 dmac.registers.dar2 = self.sb.registers.c2dstat;
 
@@ -284,14 +315,18 @@ impl Holly {
                 let dst = self.sb.registers.c2dstat;
                 let mut len = self.sb.registers.c2dlen as usize;
 
-                #[cfg(feature = "log_dma")]
-                println!(
-                    "ch2: pvr dma from {:08x} to {:08x} len (in bytes) {:08x} {}",
-                    src,
-                    dst,
-                    len,
-                    scheduler.now()
-                );
+                if context.tracer.enabled(TraceCategory::Dma) {
+                    context.tracer.message(
+                        TraceCategory::Dma,
+                        format!(
+                            "ch2: pvr dma from {:08x} to {:08x} len (in bytes) {:08x} {}",
+                            src,
+                            dst,
+                            len,
+                            context.scheduler.now()
+                        ),
+                    );
+                }
 
                 let ram_size = 16 * 1024 * 1024;
                 let ram_mask = ram_size - 1;
@@ -304,8 +339,11 @@ impl Holly {
                                 [ram_offset as usize..(ram_offset as usize + len)]
                                 .as_u32_slice_mut();
                             for &word in sys_buf.iter() {
-                                self.pvr
-                                    .receive_ta_data(scheduler, PhysicalAddress(dst), word);
+                                self.pvr.receive_ta_data(
+                                    &mut context.scheduler,
+                                    PhysicalAddress(dst),
+                                    word,
+                                );
                             }
                             src += len as u32;
                             break;
@@ -357,17 +395,24 @@ impl Holly {
                 dmac.registers.dmatcr2 = 0;
                 dmac.registers.chcr2 &= 0xFFFFFFFE;
 
-                self.sb.registers.c2dst = 0;
                 self.sb.registers.c2dlen = 0;
 
-                scheduler.schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                    deadline: 20000,
-                    event_data: HollyEventData::RaiseInterruptNormal {
-                        istnrm: 0.set_bit(19),
-                    },
-                });
+                // as with gd-dma, c2dst stays up until the transfer would have finished
+                self.sb.ch2_dma_event =
+                    Some(context.scheduler.schedule(ScheduledEvent::HollyEvent {
+                        deadline: 20000,
+                        event_data: HollyEventData::Ch2DMAEnd,
+                    }));
+            }
+            HollyEventData::Ch2DMAEnd => {
+                self.sb.ch2_dma_event = None;
+                self.sb.registers.c2dst = 0;
+                self.sb.registers.istnrm |= 0.set_bit(19);
+                self.dispatch_sh4_interrupt(&mut context.scheduler);
             }
         }
+
+        Ok(())
     }
 
     // fixme: move to system block?
@@ -405,7 +450,7 @@ impl Holly {
         }
     }
 
-    pub fn read_32(&self, addr: PhysicalAddress) -> u32 {
+    pub fn read_32(&self, addr: PhysicalAddress, context: &mut Context) -> u32 {
         match addr.0 {
             // aica wave ram + mirror
             0x00800000..=0x00FFFFFF => self.aica.read_aica_wave_32(addr),
@@ -416,7 +461,10 @@ impl Holly {
 
             0x005F8004 => 0x11, // revision
             0x005f8144 => 0,    // TA_LIST_INIT always reads 0
-            0x005f7018..=0x005f709c => self.g1_bus.read_32(addr),
+            0x005f7018..=0x005f709c => self
+                .g1_bus
+                .read_32(addr)
+                .unwrap_or_else(|error| context.fault(error)),
             0x005f6800..=0x005f7cf8 => self.sb.read_32(addr),
             0x005f810c => {
                 let line = self.spg.current_scanline & 0x3FF;
@@ -445,7 +493,10 @@ impl Holly {
             0x00800000..=0x00FFFFFF => self.aica.write_aica_wave_16(addr, value),
             0x02800000..=0x02FFFFFF => self.aica.write_aica_wave_16(addr, value),
 
-            0x005f7018..=0x005f709c => self.g1_bus.write_16(addr, value, context),
+            0x005f7018..=0x005f709c => self
+                .g1_bus
+                .write_16(addr, value, context)
+                .unwrap_or_else(|error| context.fault(error)),
             0x005f6800..=0x005f7cf8 => self.sb.write_16(addr, value),
             _ => {
                 panic!("holly: unimplemented write (16-bit) @ 0x{:08x}", addr.0);
@@ -464,7 +515,10 @@ impl Holly {
 
             0x005F8000 => {} // ID
             0x005F8004 => {} // revision
-            0x005f7018..=0x005f709c => self.g1_bus.write_32(addr, value),
+            0x005f7018..=0x005f709c => self
+                .g1_bus
+                .write_32(addr, value)
+                .unwrap_or_else(|error| context.fault(error)),
             0x005f6800..=0x005f7cf8 => self.sb.write_32(addr, value, context),
             0x005f8008 => {} // fixme: reset
             0x005f8030 => self.registers.spansort_cfg = value,
@@ -576,7 +630,10 @@ impl Holly {
             0x02800000..=0x02FFFFFF => self.aica.read_aica_wave_16(addr),
 
             // gd-rom
-            0x005f7018..=0x005f709c => self.g1_bus.read_16(addr, context),
+            0x005f7018..=0x005f709c => self
+                .g1_bus
+                .read_16(addr, context)
+                .unwrap_or_else(|error| context.fault(error)),
 
             0x005f80d8 => self.spg.registers.load as u16,
             _ => {
@@ -587,7 +644,10 @@ impl Holly {
 
     pub fn read_8(&self, addr: PhysicalAddress, context: &mut Context) -> u8 {
         match addr.0 {
-            0..=0x0023ffff => self.g1_bus.read_8(addr, context), // bios + flash
+            0..=0x0023ffff => self
+                .g1_bus
+                .read_8(addr, context)
+                .unwrap_or_else(|error| context.fault(error)), // bios + flash
             0x05000000..=0x05800000 => {
                 self.pvr.vram.read().unwrap()[(addr.0 - 0x05000000) as usize]
             } // vram
@@ -597,7 +657,10 @@ impl Holly {
             0x04000000..=0x04800000 => {
                 self.pvr.vram.read().unwrap()[(addr.0 - 0x04000000) as usize]
             } // vram 64-bit
-            0x005f7018..=0x005f709c => self.g1_bus.read_8(addr, context), // gdrom
+            0x005f7018..=0x005f709c => self
+                .g1_bus
+                .read_8(addr, context)
+                .unwrap_or_else(|error| context.fault(error)), // gdrom
             _ => panic!("holly: unimplemented read (8-bit) @ 0x{:08x}", addr.0),
         }
     }
@@ -623,7 +686,10 @@ impl Holly {
                 self.pvr.vram.write().unwrap()[(addr.0 - 0x04000000) as usize] = value;
                 self.pvr.texture_atlas.write().unwrap().notify_write(addr.0);
             } // vram 64-bit
-            0x005f7018..=0x005f709c => self.g1_bus.write_8(addr, value, context), // gd-rom
+            0x005f7018..=0x005f709c => self
+                .g1_bus
+                .write_8(addr, value, context)
+                .unwrap_or_else(|error| context.fault(error)), // gd-rom
             _ => {
                 panic!(
                     "holly: unimplemented write (8-bit) @ 0x{:08x} with {:08x}",
@@ -72,7 +72,7 @@ impl DisplayList {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DisplayListBuilder {
     // the current polygon
     pub current_poly: Option<PolygonDisplayItem>,
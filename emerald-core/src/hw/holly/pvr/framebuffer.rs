@@ -1,4 +1,11 @@
-#[derive(Copy, Clone, Debug, Default)]
+use serde::{Deserialize, Serialize};
+
+use crate::error::{EmulatorError, Subsystem};
+
+const FB_R_CTRL_ADDR: u32 = 0x005f8044;
+const FB_R_SOF1_ADDR: u32 = 0x005f8050;
+
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct FbLineStride {
     pub fb_line_stride: u32,
     pub raw: u32,
@@ -29,18 +36,18 @@ enum FramebufferDepth {
 }
 
 impl FramebufferDepth {
-    pub fn from_u32(data: u32) -> Self {
+    pub fn from_u32(data: u32) -> Option<Self> {
         match data {
-            0 => FramebufferDepth::Fbde_0555,
-            1 => FramebufferDepth::Fbde_565,
-            2 => FramebufferDepth::Fbde_888,
-            3 => FramebufferDepth::Fbde_C888,
-            _ => panic!("Invalid framebuffer depth"),
+            0 => Some(FramebufferDepth::Fbde_0555),
+            1 => Some(FramebufferDepth::Fbde_565),
+            2 => Some(FramebufferDepth::Fbde_888),
+            3 => Some(FramebufferDepth::Fbde_C888),
+            _ => None,
         }
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct FbYClip {
     pub fb_y_clip_max: u32,
     pub fb_y_clip_min: u32,
@@ -65,7 +72,7 @@ impl FbYClip {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct FbXClip {
     pub fb_x_clip_max: u32,
     pub fb_x_clip_min: u32,
@@ -90,7 +97,7 @@ impl FbXClip {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct FbSize {
     pub x_size: u32,
     pub y_size: u32,
@@ -118,7 +125,7 @@ impl FbSize {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct FbWriteCtrl {
     pub fb_alpha_threshold: u32,
     pub fb_kval: u32,
@@ -149,7 +156,7 @@ impl FbWriteCtrl {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct FbReadCtrl {
     pub vclk_div: u8,
     pub fb_strip_buf_en: bool,
@@ -195,7 +202,7 @@ impl FbReadCtrl {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct FramebufferRegisters {
     pub read_ctrl: FbReadCtrl,
     pub write_ctrl: FbWriteCtrl,
@@ -223,7 +230,7 @@ impl Default for FramebufferRegisters {
     }
 }
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Framebuffer {
     pub registers: FramebufferRegisters,
     pub dirty: bool,
@@ -284,23 +291,23 @@ impl Framebuffer {
         }
     }
 
-    pub fn render_framebuffer(&self, vram: &[u8]) -> (Vec<u8>, u32, u32) {
+    pub fn render_framebuffer(&self, vram: &[u8]) -> Result<(Vec<u8>, u32, u32), EmulatorError> {
         let fb_r_ctrl = self.registers.read_ctrl;
         let fb_r_size = self.registers.read_size;
         let fb_r_sof1 = self.registers.base_address;
 
         if fb_r_size.x_size == 0 || fb_r_size.y_size == 0 {
-            return (Vec::new(), 0, 0);
+            return Ok((Vec::new(), 0, 0));
         }
 
         let mut result = Vec::new();
 
         let mut width = (fb_r_size.x_size + 1) << 1; // in 16-bit words
         let height = 480; //fb_r_size.y_size + 1;
-        let mut modulus = (fb_r_size.modulus - 1) << 1;
+        let mut modulus = fb_r_size.modulus.saturating_sub(1) << 1;
 
         let bpp: usize;
-        match Some(FramebufferDepth::from_u32(fb_r_ctrl.fb_depth as u32)) {
+        match FramebufferDepth::from_u32(fb_r_ctrl.fb_depth as u32) {
             Some(FramebufferDepth::Fbde_0555) | Some(FramebufferDepth::Fbde_565) => {
                 bpp = 2;
             }
@@ -315,14 +322,32 @@ impl Framebuffer {
                 modulus /= 2; // in pixels
             }
             _ => {
-                panic!("Invalid framebuffer format");
+                return Err(EmulatorError::unsupported(
+                    Subsystem::Framebuffer,
+                    FB_R_CTRL_ADDR,
+                    format!("invalid framebuffer depth {}", fb_r_ctrl.fb_depth),
+                ))
             }
         }
 
+        // the 24-bit path reads one byte either side of a pixel
+        let pixels = (height * width + (height - 1) * modulus) as u64;
+        let end = fb_r_sof1 as u64 + pixels * bpp as u64 + 1;
+        if end > vram.len() as u64 {
+            return Err(EmulatorError::unsupported(
+                Subsystem::Framebuffer,
+                FB_R_SOF1_ADDR,
+                format!(
+                    "framebuffer at 0x{:08x} runs past the end of vram",
+                    fb_r_sof1
+                ),
+            ));
+        }
+
         let vram_mask = (8 * 1024 * 1024) - 1;
         let mut addr = fb_r_sof1;
 
-        match Some(FramebufferDepth::from_u32(fb_r_ctrl.fb_depth as u32)) {
+        match FramebufferDepth::from_u32(fb_r_ctrl.fb_depth as u32) {
             Some(FramebufferDepth::Fbde_0555) => {
                 // 555 RGB
                 for _ in 0..height {
@@ -432,6 +457,6 @@ impl Framebuffer {
             _ => {}
         }
 
-        (result, width, height)
+        Ok((result, width, height))
     }
 }
@@ -17,7 +17,7 @@ use crate::{
 
 use self::texture_cache::{TextureAtlas, TextureId};
 
-#[derive(Copy, Default, Clone, Debug)]
+#[derive(Copy, Default, Clone, Debug, Serialize, Deserialize)]
 pub struct PvrRegisters {
     pub isp_backgnd_t: u32,
     pub isp_feed_cfg: u32,
@@ -45,6 +45,7 @@ pub struct Pvr {
     pub wireframe: bool,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct DrawingContext {
     list_type: Option<PvrListType>,
 }
@@ -779,6 +779,18 @@ impl std::fmt::Debug for VertexParam {
     }
 }
 
+impl Serialize for VertexParam {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        unsafe { self.full }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for VertexParam {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::new(<[u32; 16]>::deserialize(deserializer)?))
+    }
+}
+
 impl VertexParam {
     pub fn new(value: [u32; 16]) -> Self {
         Self { full: value }
@@ -1,15 +1,16 @@
+use serde::{Deserialize, Serialize};
 use std::cell::Cell;
 
 // system block
 use crate::{
     context::Context,
     hw::{extensions::BitManipulation, sh4::bus::PhysicalAddress},
-    scheduler::ScheduledEvent,
+    scheduler::{EventHandle, ScheduledEvent},
 };
 
 use super::HollyEventData;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SbRegisters {
     pub istnrm: u32,  // interrupt status normal (rw)
     pub iml2nrm: u32, // interrupt mask level 2 normal
@@ -132,9 +133,14 @@ impl Default for SbRegisters {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct SystemBlock {
     pub registers: SbRegisters,
     pub last_addr: u32,
+
+    // the next step of a running gd or ch2 dma (the transfer, then its end), cancelled if the guest aborts it
+    pub gd_dma_event: Option<EventHandle>,
+    pub ch2_dma_event: Option<EventHandle>,
 }
 
 impl SystemBlock {
@@ -142,6 +148,8 @@ impl SystemBlock {
         Self {
             registers: Default::default(),
             last_addr: 0,
+            gd_dma_event: None,
+            ch2_dma_event: None,
         }
     }
 
@@ -416,17 +424,27 @@ impl SystemBlock {
             0x005f7404 => self.registers.gd_star = value,
             0x005f7408 => self.registers.gd_len = value,
             0x005f740c => self.registers.gd_dir = value,
-            0x005f7414 => self.registers.gd_en = value,
+            0x005f7414 => {
+                self.registers.gd_en = value;
+
+                // disabling a running dma aborts it
+                if value & 1 == 0 && self.registers.gd_st == 1 {
+                    if let Some(handle) = self.gd_dma_event.take() {
+                        context.scheduler.cancel(handle);
+                    }
+
+                    self.registers.gd_st = 0;
+                }
+            }
             0x005f6820 => {} // sb_sdst
             0x005f7418 => {
-                self.registers.gd_st |= value & 1;
-                if self.registers.gd_st == 1 {
-                    context
-                        .scheduler
-                        .schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                            deadline: 0 as u64,
+                if value & 1 == 1 && self.registers.gd_st == 0 {
+                    self.registers.gd_st = 1;
+                    self.gd_dma_event =
+                        Some(context.scheduler.schedule(ScheduledEvent::HollyEvent {
+                            deadline: 0,
                             event_data: HollyEventData::GdromDMA,
-                        });
+                        }));
                 }
             }
             0x005f6800 => {
@@ -439,14 +457,24 @@ impl SystemBlock {
                 self.registers.c2dlen = value;
             }
             0x005f6808 => {
-                self.registers.c2dst = value & 1;
-                if self.registers.c2dst == 0x1 {
-                    context
-                        .scheduler
-                        .schedule(crate::scheduler::ScheduledEvent::HollyEvent {
-                            deadline: 0,
-                            event_data: super::HollyEventData::Ch2DMA,
-                        })
+                match (self.registers.c2dst, value & 1) {
+                    (0, 1) => {
+                        self.registers.c2dst = 1;
+                        self.ch2_dma_event =
+                            Some(context.scheduler.schedule(ScheduledEvent::HollyEvent {
+                                deadline: 0,
+                                event_data: HollyEventData::Ch2DMA,
+                            }));
+                    }
+                    // writing 0 while it runs stops it, without the end interrupt
+                    (1, 0) => {
+                        if let Some(handle) = self.ch2_dma_event.take() {
+                            context.scheduler.cancel(handle);
+                        }
+
+                        self.registers.c2dst = 0;
+                    }
+                    _ => {}
                 }
             }
             0x005f6810 => self.registers.sdstaw = value & 0x07FFFFE0,
@@ -1,17 +1,18 @@
 // sync pulse generator
 
+use serde::{Deserialize, Serialize};
 use std::cmp::{max, min};
 
 use crate::{hw::extensions::BitManipulation, scheduler::Scheduler};
 
 use super::{sb::SystemBlock, HollyEventData};
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SpgEventData {
     Sync,
 }
 
-#[derive(Default, Clone, Debug, Eq, PartialEq)]
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct SpgRegisters {
     pub vblank: u32,
     pub hblank: u32,
@@ -20,6 +21,7 @@ pub struct SpgRegisters {
     pub vblank_int: u32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Spg {
     pub registers: SpgRegisters,
     pub in_vblank: bool,
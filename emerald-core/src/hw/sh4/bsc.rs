@@ -1,7 +1,12 @@
 // bus state controller
 use super::bus::PhysicalAddress;
+use crate::{
+    config::CableType,
+    error::{EmulatorError, Subsystem},
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Clone, Debug, Eq, PartialEq)]
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct BscRegisters {
     bcr1: u32,
     bcr2: u16,
@@ -23,8 +28,10 @@ pub struct BscRegisters {
     sdmr3: Vec<u8>,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Bsc {
     registers: BscRegisters,
+    pub cable_type: CableType,
 }
 
 impl Bsc {
@@ -35,10 +42,11 @@ impl Bsc {
                 sdmr3: vec![0; 65535],
                 ..Default::default()
             },
+            cable_type: CableType::Vga,
         }
     }
 
-    pub fn write_32(&mut self, addr: PhysicalAddress, value: u32) {
+    pub fn write_32(&mut self, addr: PhysicalAddress, value: u32) -> Result<(), EmulatorError> {
         match addr.0 {
             0x1f800000 => self.registers.bcr1 = value,
             0x1f800008 => self.registers.wcr1 = value,
@@ -50,24 +58,36 @@ impl Bsc {
             }
             0x1f800040 => self.registers.pctrb = value,
             0x1f80000c => self.registers.wcr2 = value,
-            _ => panic!(
-                "bsc: unknown mmio write (32-bit) @ 0x{:08x} with value 0x{:08x}",
-                addr.0, value
-            ),
+            _ => {
+                return Err(EmulatorError::unknown_write(
+                    Subsystem::Bsc,
+                    addr.0,
+                    value,
+                    32,
+                ))
+            }
         }
+
+        Ok(())
     }
 
-    pub fn write_8(&mut self, addr: PhysicalAddress, value: u8) {
+    pub fn write_8(&mut self, addr: PhysicalAddress, value: u8) -> Result<(), EmulatorError> {
         match addr.0 {
             0x1f940000..=0x1f94ffff => self.registers.sdmr3[(addr.0 - 0x1f940000) as usize] = value,
-            _ => panic!(
-                "bsc: unknown mmio write (8-bit) @ 0x{:08x} with value 0x{:08x}",
-                addr.0, value
-            ),
+            _ => {
+                return Err(EmulatorError::unknown_write(
+                    Subsystem::Bsc,
+                    addr.0,
+                    value as u32,
+                    8,
+                ))
+            }
         }
+
+        Ok(())
     }
 
-    pub fn write_16(&mut self, addr: PhysicalAddress, value: u16) {
+    pub fn write_16(&mut self, addr: PhysicalAddress, value: u16) -> Result<(), EmulatorError> {
         match addr.0 {
             0x1f800004 => self.registers.bcr2 = value,
             0x1f800018 => self.registers.pcr = value,
@@ -77,15 +97,21 @@ impl Bsc {
             0x1f800030 => self.registers.pdtra = value,
             0x1f800044 => self.registers.pdtrb = value,
             0x1f800048 => self.registers.gpioic = value,
-            _ => panic!(
-                "bsc: unknown mmio write (16-bit) @ 0x{:08x} with value 0x{:08x}",
-                addr.0, value
-            ),
+            _ => {
+                return Err(EmulatorError::unknown_write(
+                    Subsystem::Bsc,
+                    addr.0,
+                    value as u32,
+                    16,
+                ))
+            }
         }
+
+        Ok(())
     }
 
-    pub fn read_16(&self, addr: PhysicalAddress) -> u16 {
-        match addr.0 {
+    pub fn read_16(&self, addr: PhysicalAddress) -> Result<u16, EmulatorError> {
+        let value = match addr.0 {
             0x1f800028 => self.registers.rfcr,
             0x1f800030 => {
                 // pdtra
@@ -110,24 +136,27 @@ impl Bsc {
                     tfinal = 3;
                 }
 
-                let cable_type = 0; //vga
-                tfinal |= cable_type << 8;
+                tfinal |= self.cable_type.pdtra_value() << 8;
 
-                return tfinal;
+                tfinal
             }
-            _ => panic!("bsc: unknown mmio read (16-bit) @ 0x{:08x}", addr.0),
-        }
+            _ => return Err(EmulatorError::unknown_read(Subsystem::Bsc, addr.0, 16)),
+        };
+
+        Ok(value)
     }
-    pub fn read_32(&self, addr: PhysicalAddress) -> u32 {
-        match addr.0 {
+    pub fn read_32(&self, addr: PhysicalAddress) -> Result<u32, EmulatorError> {
+        let value = match addr.0 {
             0x1f80002c => self.registers.pctra,
-            _ => panic!("bsc: unknown mmio read (32-bit) @ 0x{:08x}", addr.0),
-        }
+            _ => return Err(EmulatorError::unknown_read(Subsystem::Bsc, addr.0, 32)),
+        };
+
+        Ok(value)
     }
 
-    pub fn read_8(&self, addr: PhysicalAddress) -> u8 {
+    pub fn read_8(&self, addr: PhysicalAddress) -> Result<u8, EmulatorError> {
         match addr.0 {
-            _ => panic!("bsc: unknown mmio read (8-bit) @ 0x{:08x}", addr.0),
+            _ => Err(EmulatorError::unknown_read(Subsystem::Bsc, addr.0, 8)),
         }
     }
 }
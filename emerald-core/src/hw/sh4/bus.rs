@@ -1,59 +1,31 @@
 use std::cell::Cell;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::{bsc::Bsc, ccn::Ccn, cpg::Cpg, dmac::Dmac, intc::Intc, rtc::Rtc, tmu::Tmu};
+use super::{
+    bsc::Bsc,
+    ccn::Ccn,
+    cpg::Cpg,
+    debug::{AccessKind, Debugger},
+    dmac::Dmac,
+    intc::Intc,
+    rtc::Rtc,
+    scif::Scif,
+    tmu::Tmu,
+};
+
+#[cfg(feature = "json_tests")]
+use super::test_bus::{BusCycleKind, TestBus};
 use crate::hw::holly::g2::aica::arm_bus::ArmBus;
 use crate::scheduler::Scheduler;
-use crate::{context::Context, hw::holly::Holly};
-use std::io::{self, Write};
+use crate::{
+    context::Context,
+    hw::holly::Holly,
+    trace::{TraceCategory, TraceEvent},
+};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PhysicalAddress(pub u32);
 
-struct SerialBuffer {
-    buffer: String,
-}
-
-impl SerialBuffer {
-    fn new() -> SerialBuffer {
-        SerialBuffer {
-            buffer: String::new(),
-        }
-    }
-}
-
-impl Write for SerialBuffer {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let str_buf = match std::str::from_utf8(buf) {
-            Ok(str_buf) => str_buf,
-            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
-        };
-
-        for c in str_buf.chars() {
-            if c == '\n' {
-                #[cfg(feature = "log_serial")]
-                println!("{}", self.buffer);
-
-                self.buffer.clear();
-            } else {
-                self.buffer.push(c);
-            }
-        }
-
-        Ok(buf.len())
-    }
-
-    // Flushes the remaining buffer if it's not empty.
-    fn flush(&mut self) -> io::Result<()> {
-        if !self.buffer.is_empty() {
-            #[cfg(feature = "log_serial")]
-            println!("{}", self.buffer);
-            self.buffer.clear();
-        }
-        Ok(())
-    }
-}
-
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum MappedLocation {
     ExternalAddress(PhysicalAddress),
@@ -90,6 +62,7 @@ pub struct CpuBus {
     pub ccn: Ccn,
     pub bsc: Bsc,
     pub tmu: Tmu,
+    pub scif: Scif,
     pub holly: Holly,
     pub rtc: Rtc,
     pub cpg: Cpg,
@@ -108,12 +81,13 @@ pub struct CpuBus {
     pub bara: u32,
     pub barb: u32,
 
-    // fixme: move this to scif
-    pub scfsr2: u16,
     pub unk_val: u32,
     pub unk_val1: u32,
 
-    pub serial_buffer: SerialBuffer,
+    pub debugger: Debugger,
+
+    #[cfg(feature = "json_tests")]
+    pub test_bus: Option<TestBus>, // takes every access when set, see test_bus.rs
 }
 
 impl CpuBus {
@@ -198,6 +172,7 @@ impl CpuBus {
             ccn: Ccn::new(),
             bsc: Bsc::new(),
             tmu: Tmu::new(),
+            scif: Scif::new(),
             holly: Holly::new(),
             rtc: Rtc::new(),
             cpg: Cpg::new(),
@@ -206,16 +181,97 @@ impl CpuBus {
             basra: 0,
             bara: 0,
             barb: 0,
-            serial_buffer: SerialBuffer::new(),
-            scfsr2: 0x60,
             store_queues: [[0; 8]; 2],
             system_ram: vec![0; SYSTEM_RAM_SIZE],
             unk_val: 0,
             unk_val1: 0,
+            debugger: Debugger::new(),
+            #[cfg(feature = "json_tests")]
+            test_bus: None,
+        }
+    }
+
+    // every guest access ends up here once, for tracing and the debugger's watchpoints
+    fn report(&self, kind: AccessKind, addr: u32, size: u8, value: u64, context: &mut Context) {
+        if context.tracer.enabled(TraceCategory::Memory) {
+            let event = match kind {
+                AccessKind::Read => TraceEvent::Read { addr, size, value },
+                AccessKind::Write | AccessKind::StoreQueueFlush => {
+                    TraceEvent::Write { addr, size, value }
+                }
+            };
+
+            context.tracer.record(TraceCategory::Memory, event);
+        }
+
+        if self.debugger.watching() {
+            let physical = self.mapper.translate(LogicalAddress(addr)).phys();
+            self.debugger.record(kind, addr, physical.0, size, value);
+        }
+    }
+
+    // for the host's own accesses, from gdb, the hle bios or loading a program. they go through the bus like
+    // the guest's would, but aren't something the guest did so they shouldn't trip its watchpoints
+    pub fn untracked<T>(&mut self, access: impl FnOnce(&mut Self) -> T) -> T {
+        let hits = self.debugger.pending_hits();
+        let result = access(self);
+        self.debugger.discard_hits(hits);
+        result
+    }
+
+    // ram and the bios only, for looking at code without touching a device or tripping a watchpoint
+    pub fn peek_8(&self, addr: u32) -> Option<u8> {
+        match self.mapper.translate(LogicalAddress(addr)) {
+            MappedLocation::ExternalAddress(physical_addr) => match physical_addr.0 {
+                0..=0x001fffff => Some(self.holly.g1_bus.boot_rom.bios[physical_addr.0 as usize]),
+                0x0c000000..=0x0cffffff => {
+                    Some(self.system_ram[(physical_addr.0 - 0x0c000000) as usize])
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn peek_16(&self, addr: u32) -> Option<u16> {
+        Some(u16::from_le_bytes([
+            self.peek_8(addr)?,
+            self.peek_8(addr.wrapping_add(1))?,
+        ]))
+    }
+
+    pub fn peek_32(&self, addr: u32) -> Option<u32> {
+        Some(u32::from_le_bytes([
+            self.peek_8(addr)?,
+            self.peek_8(addr.wrapping_add(1))?,
+            self.peek_8(addr.wrapping_add(2))?,
+            self.peek_8(addr.wrapping_add(3))?,
+        ]))
+    }
+
+    // pref on a store queue address, the queue goes out to ext_addr as 8 longword writes
+    pub fn flush_store_queue(&mut self, sq: usize, ext_addr: u32, context: &mut Context) {
+        #[cfg(feature = "json_tests")]
+        if self.test_bus.is_some() {
+            return;
+        }
+
+        for i in 0..8 {
+            let addr = ext_addr + 4 * i as u32;
+            let value = self.store_queues[sq][i];
+
+            self.report(AccessKind::StoreQueueFlush, addr, 32, value as u64, context);
+            self.write_32_inner(addr, value, context);
         }
     }
 
     pub fn write_64(&mut self, addr: u32, value: u64, context: &mut Context) {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &mut self.test_bus {
+            return test_bus.write(addr, 64, value);
+        }
+
+        self.report(AccessKind::Write, addr, 64, value, context);
 
         let mapped_location = self.mapper.translate(LogicalAddress(addr));
         match mapped_location {
@@ -230,18 +286,21 @@ impl CpuBus {
             _ => {}
         }
 
-        let tracing = context.tracing;
-        context.tracing = false;
-        self.write_32(addr, (value & 0xffffffff) as u32, context);
-        self.write_32(addr + 4, ((value >> 32) & 0xffffffff) as u32, context);
-        context.tracing = tracing;
+        self.write_32_inner(addr, (value & 0xffffffff) as u32, context);
+        self.write_32_inner(addr + 4, ((value >> 32) & 0xffffffff) as u32, context);
+    }
 
-        if context.tracing {
-            println!(" write64  ({:08x}) {:016x}", addr, value);
+    pub fn write_32(&mut self, addr: u32, value: u32, context: &mut Context) {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &mut self.test_bus {
+            return test_bus.write(addr, 32, value as u64);
         }
+
+        self.report(AccessKind::Write, addr, 32, value as u64, context);
+        self.write_32_inner(addr, value, context);
     }
 
-    pub fn write_32(&mut self, addr: u32, value: u32, context: &mut Context) {
+    fn write_32_inner(&mut self, addr: u32, value: u32, context: &mut Context) {
         let mapped_location = self.mapper.translate(LogicalAddress(addr));
 
         match mapped_location {
@@ -311,12 +370,12 @@ impl CpuBus {
                 0x10000000..=0x10FFFFFF => {
                     self.holly
                         .pvr
-                        .receive_ta_data(context.scheduler, physical_addr, value);
+                        .receive_ta_data(&mut context.scheduler, physical_addr, value);
                 }
                 0x11000000..=0x117FFFFF => {
                     self.holly
                         .pvr
-                        .receive_ta_data(context.scheduler, physical_addr, value);
+                        .receive_ta_data(&mut context.scheduler, physical_addr, value);
                     // self.holly.framebuffer.notify_write(physical_addr.0);
                 }
 
@@ -329,11 +388,20 @@ impl CpuBus {
                 }
             },
             MappedLocation::InternalAddress(physical_addr) => match physical_addr.0 {
-                0x1f000000..=0x1f00003c => self.ccn.write_32(physical_addr, value),
-                0x1f800000..=0x1f999999 => self.bsc.write_32(physical_addr, value),
+                0x1f000000..=0x1f00003c => self
+                    .ccn
+                    .write_32(physical_addr, value)
+                    .unwrap_or_else(|error| context.fault(error)),
+                0x1f800000..=0x1f999999 => self
+                    .bsc
+                    .write_32(physical_addr, value)
+                    .unwrap_or_else(|error| context.fault(error)),
                 0x1fa00000..=0x1fa00040 => self.dmac.write_32(physical_addr, value),
                 0x1fc80000..=0x1fc8003c => self.rtc.write_32(physical_addr, value),
-                0x1fd80000..=0x1fd8002c => self.tmu.write_32(physical_addr, value),
+                0x1fd80000..=0x1fd8002c => {
+                    self.tmu
+                        .write_32(physical_addr, value, &mut context.scheduler)
+                }
                 0x1ffffff8 => self.unk_val = value,
                 0x1ffffff4 => self.unk_val1 = value,
                 0x1f200000 => self.bara = value,
@@ -359,12 +427,14 @@ impl CpuBus {
     }
 
     pub fn write_16(&mut self, addr: u32, value: u16, context: &mut Context) {
-        let mapped_location = self.mapper.translate(LogicalAddress(addr));
-
-        if context.tracing {
-            println!(" write16  ({:08x}) {:04x}", addr, value);
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &mut self.test_bus {
+            return test_bus.write(addr, 16, value as u64);
         }
 
+        self.report(AccessKind::Write, addr, 16, value as u64, context);
+        let mapped_location = self.mapper.translate(LogicalAddress(addr));
+
         match mapped_location {
             MappedLocation::ExternalAddress(physical_addr) => match physical_addr.0 {
                 0x005f6800..=0x005f9fff => self.holly.write_16(physical_addr, value, context),
@@ -407,13 +477,21 @@ impl CpuBus {
                 }
             },
             MappedLocation::InternalAddress(physical_addr) => match physical_addr.0 {
-                0x1f800000..=0x1f999999 => self.bsc.write_16(physical_addr, value),
+                0x1f800000..=0x1f999999 => self
+                    .bsc
+                    .write_16(physical_addr, value)
+                    .unwrap_or_else(|error| context.fault(error)),
                 0x1fc80000..=0x1fc8003c => self.rtc.write_16(physical_addr, value),
                 0x1fd00000..=0x1fd0000c => self.intc.write_16(physical_addr, value),
-                0x1fd80000..=0x1fd8002c => self.tmu.write_16(physical_addr, value),
-                0x1fc00000..=0x1fc00010 => self.cpg.write_16(physical_addr, value), // clock pulse generator
-                0x1fe80010 => {}
-                0x1fe80000..=0x1fe80024 => {} // scif
+                0x1fd80000..=0x1fd8002c => {
+                    self.tmu
+                        .write_16(physical_addr, value, &mut context.scheduler)
+                }
+                0x1fc00000..=0x1fc00010 => self.cpg.write_16(physical_addr, value, context), // clock pulse generator
+                0x1fe80000..=0x1fe80024 => {
+                    self.scif
+                        .write_16(physical_addr, value, &mut context.scheduler, &mut self.intc)
+                }
                 0x1f200000..=0x1f200021 => {} // break controller
 
                 0x1f000084..=0x1f000088 => {}
@@ -433,12 +511,14 @@ impl CpuBus {
     }
 
     pub fn write_8(&mut self, addr: u32, value: u8, context: &mut Context) {
-        let mapped_location = self.mapper.translate(LogicalAddress(addr));
-
-        if context.tracing {
-            println!(" write8   ({:08x}) {:02x}", addr, value);
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &mut self.test_bus {
+            return test_bus.write(addr, 8, value as u64);
         }
 
+        self.report(AccessKind::Write, addr, 8, value as u64, context);
+        let mapped_location = self.mapper.translate(LogicalAddress(addr));
+
         match mapped_location {
             MappedLocation::ExternalAddress(physical_addr) => match physical_addr.0 {
                 // holly
@@ -461,16 +541,22 @@ impl CpuBus {
                 }
             },
             MappedLocation::InternalAddress(physical_addr) => match physical_addr.0 {
-                0x1f800000..=0x1f999999 => self.bsc.write_8(physical_addr, value), // bus state controller
-                0x1fd80000..=0x1fd8002c => self.tmu.write_8(physical_addr, value), // timer
+                0x1f800000..=0x1f999999 => {
+                    self.bsc
+                        .write_8(physical_addr, value)
+                        .unwrap_or_else(|error| context.fault(error)) // bus state controller
+                }
+                0x1fd80000..=0x1fd8002c => {
+                    self.tmu
+                        .write_8(physical_addr, value, &mut context.scheduler) // timer
+                }
                 0x1fc80000..=0x1fc8003c => self.rtc.write_8(physical_addr, value), // rtc
                 0x1fc00000..=0x1fc00010 => self.cpg.write_8(physical_addr, value), // clock pulse generator
 
-                // scif
-                0x1fe8000c => {
-                    write!(self.serial_buffer, "{}", value as char);
+                0x1fe80000..=0x1fe80024 => {
+                    self.scif
+                        .write_8(physical_addr, value, &mut context.scheduler, &mut self.intc)
                 }
-                0x1fe80000..=0x1fe80024 => {} // more scif stuff, ignore for now
                 0x1f200000..=0x1f200021 => {} // break controller
                 0x1f000014 => self.basra = value,
                 0x1f000018 => self.basrb = value,
@@ -489,25 +575,38 @@ impl CpuBus {
     }
 
     pub fn read_64(&self, addr: u32, context: &mut Context) -> u64 {
-        let tracing = context.tracing;
-        context.tracing = false;
-        let valuelo = self.read_32(addr, context) as u64;
-        let valuehi = self.read_32(addr + 4, context) as u64;
-        context.tracing = tracing;
-
-        if context.tracing {
-            println!(
-                " read64   ({:08x}) {:016x}",
-                addr,
-                (valuehi << 32) | valuelo
-            );
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &self.test_bus {
+            return test_bus.read(addr, 64, BusCycleKind::Read);
         }
 
+        let valuelo = self.read_32_inner(addr, context) as u64;
+        let valuehi = self.read_32_inner(addr + 4, context) as u64;
+
+        self.report(
+            AccessKind::Read,
+            addr,
+            64,
+            (valuehi << 32) | valuelo,
+            context,
+        );
+
         // Combine the two halves into a 64-bit value
         (valuehi << 32) | valuelo
     }
 
     pub fn read_32(&self, addr: u32, context: &mut Context) -> u32 {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &self.test_bus {
+            return test_bus.read(addr, 32, BusCycleKind::Read) as u32;
+        }
+
+        let value = self.read_32_inner(addr, context);
+        self.report(AccessKind::Read, addr, 32, value as u64, context);
+        value
+    }
+
+    fn read_32_inner(&self, addr: u32, context: &mut Context) -> u32 {
         let mapped_location = self.mapper.translate(LogicalAddress(addr));
         let value = match mapped_location {
             MappedLocation::ExternalAddress(physical_addr) => match physical_addr.0 {
@@ -518,10 +617,10 @@ impl CpuBus {
                 0x00710004 => (self.holly.aica.rtc.timestamp as u32) & 0x0000FFFFF,
 
                 // aica
-                0x00700000..=0x0070FFFF => self.holly.read_32(physical_addr),
-                0x02700000..=0x0270FFFF => self.holly.read_32(physical_addr),
-                0x00800000..=0x009fffff => self.holly.read_32(physical_addr),
-                0x02800000..=0x02FFFFFf => self.holly.read_32(physical_addr),
+                0x00700000..=0x0070FFFF => self.holly.read_32(physical_addr, context),
+                0x02700000..=0x0270FFFF => self.holly.read_32(physical_addr, context),
+                0x00800000..=0x009fffff => self.holly.read_32(physical_addr, context),
+                0x02800000..=0x02FFFFFf => self.holly.read_32(physical_addr, context),
 
                 0x0c000000..=0x0cffffff => {
                     let addr_base = (physical_addr.0 - 0x0c000000) as usize;
@@ -535,7 +634,7 @@ impl CpuBus {
                     let value = u32::from_le_bytes(bytes);
                     value
                 }
-                0x005f6800..=0x005f9fff => self.holly.read_32(physical_addr), // holly
+                0x005f6800..=0x005f9fff => self.holly.read_32(physical_addr, context), // holly
 
                 _ => {
                     let lower = self.read_16(addr, true, context) as u32;
@@ -545,9 +644,17 @@ impl CpuBus {
                 }
             },
             MappedLocation::InternalAddress(physical_addr) => match physical_addr.0 {
-                0x1f000000..=0x1f00003c => self.ccn.read_32(physical_addr), // ccn
-                0x1f800000..=0x1f999999 => self.bsc.read_32(physical_addr), // bus state controller
-                0x1fd80000..=0x1fd8002c => self.tmu.read_32(physical_addr), // timer
+                0x1f000000..=0x1f00003c => {
+                    self.ccn
+                        .read_32(physical_addr)
+                        .unwrap_or_else(|error| context.fault(error)) // ccn
+                }
+                0x1f800000..=0x1f999999 => {
+                    self.bsc
+                        .read_32(physical_addr)
+                        .unwrap_or_else(|error| context.fault(error)) // bus state controller
+                }
+                0x1fd80000..=0x1fd8002c => self.tmu.read_32(physical_addr, context.scheduler.now()), // timer
                 0x1fa00000..=0x1fa00040 => self.dmac.read_32(physical_addr), // dmac
                 _ => {
                     let lower = self.read_16(addr, true, context) as u32;
@@ -560,14 +667,22 @@ impl CpuBus {
             _ => 0,
         };
 
-        if context.tracing {
-            println!(" read32   ({:08x}) {:08x}", addr, value);
-        }
-
         value
     }
 
     pub fn read_16(&self, addr: u32, fetching: bool, context: &mut Context) -> u16 {
+        // the cpu's instruction fetch is the only caller that sets fetching and gets this far
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &self.test_bus {
+            let kind = if fetching {
+                BusCycleKind::Fetch
+            } else {
+                BusCycleKind::Read
+            };
+
+            return test_bus.read(addr, 16, kind) as u16;
+        }
+
         let mapped_location = self.mapper.translate(LogicalAddress(addr));
 
         let value = match mapped_location {
@@ -591,14 +706,18 @@ impl CpuBus {
                 }
             },
             MappedLocation::InternalAddress(physical_addr) => match physical_addr.0 {
-                0x1f800000..=0x1f999999 => self.bsc.read_16(physical_addr), // bus state controller
+                0x1f800000..=0x1f999999 => {
+                    self.bsc
+                        .read_16(physical_addr)
+                        .unwrap_or_else(|error| context.fault(error)) // bus state controller
+                }
                 0x1fd00000..=0x1fd0000c => self.intc.read_16(physical_addr), // interrupt controller
-                0x1fd80000..=0x1fd8002c => self.tmu.read_16(physical_addr), // timer
+                0x1fd80000..=0x1fd8002c => self.tmu.read_16(physical_addr),  // timer
+
+                0x1fe80000..=0x1fe80024 => self.scif.read_16(physical_addr),
 
                 // fixme: more atrocities in the name of getting traces to match..
-                0x1fe80010 => 0x60,
                 0x1f000084 | 0x1f000088 => 0,
-                0x1fe80014..=0x1fe80024 => 0,
                 _ => {
                     let lower = self.read_8(addr, true, context) as u16;
                     let upper = self.read_8(addr + 1, true, context) as u16;
@@ -614,14 +733,20 @@ impl CpuBus {
             }
         };
 
-        if context.tracing && !fetching {
-            println!(" read16   ({:08x}) {:04x}", addr, value);
+        // fetching also covers the halves of a wider access, which that access reports itself
+        if !fetching {
+            self.report(AccessKind::Read, addr, 16, value as u64, context);
         }
 
         value
     }
 
     pub fn read_8(&self, addr: u32, fetching: bool, context: &mut Context) -> u8 {
+        #[cfg(feature = "json_tests")]
+        if let Some(test_bus) = &self.test_bus {
+            return test_bus.read(addr, 8, BusCycleKind::Read) as u8;
+        }
+
         let mapped_location = self.mapper.translate(LogicalAddress(addr));
 
         let value = match mapped_location {
@@ -644,8 +769,12 @@ impl CpuBus {
                 }
             },
             MappedLocation::InternalAddress(physical_addr) => match physical_addr.0 {
-                0x1FE80004 => 0,
-                0x1f800000..=0x1f999999 => self.bsc.read_8(physical_addr), // bus state controller
+                0x1fe80000..=0x1fe80024 => self.scif.read_8(physical_addr),
+                0x1f800000..=0x1f999999 => {
+                    self.bsc
+                        .read_8(physical_addr)
+                        .unwrap_or_else(|error| context.fault(error)) // bus state controller
+                }
                 0x1fd80000..=0x1fd8002c => self.tmu.read_8(physical_addr), // timer
                 0x1fc0000c => 0,                                           // idk
                 _ => {
@@ -661,8 +790,8 @@ impl CpuBus {
             MappedLocation::StoreQueue(_) => unreachable!(),
         };
 
-        if context.tracing && !fetching {
-            println!(" read8    ({:08x}) {:02x}", addr, value);
+        if !fetching {
+            self.report(AccessKind::Read, addr, 8, value as u64, context);
         }
 
         value
@@ -700,6 +829,68 @@ impl MappedRange {
     }
 }
 
+// the guest's buffers as seen from the host, for the hle bios and dcload. straight out of system ram when
+// that's where they are
+pub struct GuestMemory<'a> {
+    pub bus: &'a mut CpuBus,
+    pub context: &'a mut Context,
+}
+
+impl<'a> GuestMemory<'a> {
+    pub fn new(bus: &'a mut CpuBus, context: &'a mut Context) -> Self {
+        Self { bus, context }
+    }
+
+    // area 3 and its mirrors, through any of p0-p3
+    fn ram_offset(addr: u32) -> Option<usize> {
+        match addr & 0x1fffffff {
+            0x0c000000..=0x0fffffff => Some((addr & 0xffffff) as usize),
+            _ => None,
+        }
+    }
+
+    pub fn read(&mut self, addr: u32, len: usize) -> Vec<u8> {
+        (0..len as u32)
+            .map(|i| {
+                let addr = addr.wrapping_add(i);
+                match Self::ram_offset(addr) {
+                    Some(offset) => self.bus.system_ram[offset],
+                    None => {
+                        let context = &mut *self.context;
+                        self.bus.untracked(|bus| bus.read_8(addr, true, context))
+                    }
+                }
+            })
+            .collect()
+    }
+
+    // a nul terminated string, cut off at max_len bytes
+    pub fn read_string(&mut self, addr: u32, max_len: usize) -> String {
+        let mut bytes = Vec::new();
+        for i in 0..max_len as u32 {
+            match self.read(addr.wrapping_add(i), 1)[0] {
+                0 => break,
+                byte => bytes.push(byte),
+            }
+        }
+
+        String::from_utf8_lossy(&bytes).into_owned()
+    }
+
+    pub fn write(&mut self, addr: u32, data: &[u8]) {
+        for (i, byte) in data.iter().enumerate() {
+            let addr = addr.wrapping_add(i as u32);
+            match Self::ram_offset(addr) {
+                Some(offset) => self.bus.system_ram[offset] = *byte,
+                None => {
+                    let context = &mut *self.context;
+                    self.bus.untracked(|bus| bus.write_8(addr, *byte, context));
+                }
+            }
+        }
+    }
+}
+
 struct MemoryMapper {
     ranges: Vec<MappedRange>,
 }
@@ -1,9 +1,13 @@
 // cache and TLB controller
 
 use super::bus::PhysicalAddress;
-use crate::hw::extensions::BitManipulation;
+use crate::{
+    error::{EmulatorError, Subsystem},
+    hw::extensions::BitManipulation,
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Default, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct CcnRegisters {
     pub pteh: u32,
     pub ptel: u32,
@@ -21,6 +25,7 @@ pub struct CcnRegisters {
     pub qacr1: u32,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Ccn {
     pub registers: CcnRegisters,
     operand_cache_ram: Vec<u8>,
@@ -34,7 +39,7 @@ impl Ccn {
         }
     }
 
-    pub fn write_32(&mut self, addr: PhysicalAddress, value: u32) {
+    pub fn write_32(&mut self, addr: PhysicalAddress, value: u32) -> Result<(), EmulatorError> {
         match addr.0 {
             0x1f000000 => self.registers.pteh = value,
             0x1f000004 => self.registers.ptel = value,
@@ -48,11 +53,17 @@ impl Ccn {
             0x1f000034 => self.registers.ptea = value & 0x0000000F,
             0x1f000038 => self.registers.qacr0 = value,
             0x1f00003c => self.registers.qacr1 = value,
-            _ => panic!(
-                "ccn: unknown mmio write @ 0x{:08x} with value 0x{:08x}",
-                addr.0, value
-            ),
+            _ => {
+                return Err(EmulatorError::unknown_write(
+                    Subsystem::Ccn,
+                    addr.0,
+                    value,
+                    32,
+                ))
+            }
         }
+
+        Ok(())
     }
 
     pub fn write_oc_32(&mut self, addr: PhysicalAddress, value: u32) {
@@ -121,15 +132,17 @@ impl Ccn {
         self.operand_cache_ram[index]
     }
 
-    pub fn read_32(&self, addr: PhysicalAddress) -> u32 {
-        match addr.0 {
+    pub fn read_32(&self, addr: PhysicalAddress) -> Result<u32, EmulatorError> {
+        let value = match addr.0 {
             0x1f000024 => self.registers.expevt,
             // bits 3 and 11 always return 0 when read for ccr
             0x1f00001c => self.registers.ccr.clear_bit(11).clear_bit(3),
             0x1f000028 => self.registers.intevt,
             0x1f000030 => 0x040205c1,
             0x1f000010 => self.registers.mmucr,
-            _ => panic!("ccn: unknown mmio read (32-bit) @ 0x{:08x}", addr.0),
-        }
+            _ => return Err(EmulatorError::unknown_read(Subsystem::Ccn, addr.0, 32)),
+        };
+
+        Ok(value)
     }
 }
@@ -1,7 +1,9 @@
 // clock pulse generator
 use super::bus::PhysicalAddress;
+use crate::{context::Context, trace::TraceCategory};
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Clone, Debug, Eq, PartialEq)]
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct CpgRegisters {
     stbcr: u8,
     stbcr2: u8,
@@ -9,6 +11,7 @@ pub struct CpgRegisters {
     wtcsr: u16,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Cpg {
     pub registers: CpgRegisters,
 }
@@ -45,18 +48,22 @@ impl Cpg {
         }
     }
 
-    pub fn write_16(&mut self, addr: PhysicalAddress, value: u16) {
+    pub fn write_16(&mut self, addr: PhysicalAddress, value: u16, context: &mut Context) {
         match addr.0 {
             0x1fc00008 => {
                 let lower = (value & 0xFF) as u16;
                 self.registers.wtcnt = (self.registers.wtcnt & 0xFF00) | lower;
             },
             _ => {
-                #[cfg(feature = "log_io")]
-                panic!(
-                    "cpg: unknown mmio write (16-bit) @ 0x{:08x} with value 0x{:04x}",
-                    addr.0, value
-                );
+                if context.tracer.enabled(TraceCategory::Io) {
+                    context.tracer.message(
+                        TraceCategory::Io,
+                        format!(
+                            "cpg: unknown mmio write (16-bit) @ 0x{:08x} with value 0x{:04x}",
+                            addr.0, value
+                        ),
+                    );
+                }
             }
         }
     }
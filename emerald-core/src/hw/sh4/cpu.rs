@@ -4,12 +4,16 @@ use crate::Context;
 use crate::CpuBus;
 use ::lending_iterator::prelude::*;
 use std::f128;
-use std::{collections::HashMap, fmt};
+use std::fmt;
+use serde::{Deserialize, Serialize};
 
 use super::bus::LogicalAddress;
 use super::bus::PhysicalAddress;
 use super::decoder::build_opcode_lut;
 use super::decoder::DecodedInstruction;
+use super::disasm::disassemble;
+use crate::symbols::SymbolTable;
+use crate::trace::{Sh4Registers, TraceCategory, TraceEvent, TraceLevel};
 
 pub struct CachedBlockIterator<'a> {
     block: &'a mut CachedBlock,
@@ -148,7 +152,7 @@ impl CachedBlockBuilder {
     }
 }
 
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum CpuState {
     Running,
     Sleeping,
@@ -167,6 +171,22 @@ impl Default for FpuBank {
     }
 }
 
+// serialized as raw bits so nan payloads survive a round trip
+impl Serialize for FpuBank {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get_fr().map(f32::to_bits).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for FpuBank {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bits = <[u32; 16]>::deserialize(deserializer)?;
+        Ok(FpuBank {
+            fr: bits.map(f32::from_bits),
+        })
+    }
+}
+
 impl FpuBank {
     pub fn get_fr(&self) -> [f32; 16] {
         unsafe { self.fr }
@@ -195,13 +215,13 @@ pub struct Cpu {
     pub registers: CpuRegisters,
     pub current_opcode: u16,
     pub cyc: u64,
-    pub symbols_map: HashMap<u32, String>,
+    pub symbols: SymbolTable,
     pub state: CpuState,
     pub opcode_lut: Vec<DecodedInstruction>,
     pub cached_block_builder: CachedBlockBuilder,
 }
 
-#[derive(Copy, Clone, Default, Debug)]
+#[derive(Copy, Clone, Default, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct CpuRegisters {
     pub current_pc: u32,
@@ -247,7 +267,7 @@ impl Cpu {
             cyc: 0,
             registers: CpuRegisters::new(),
             current_opcode: 0,
-            symbols_map: HashMap::new(),
+            symbols: SymbolTable::new(),
             state: CpuState::Running,
             opcode_lut: build_opcode_lut(),
             cached_block_builder: CachedBlockBuilder::new(),
@@ -473,8 +493,8 @@ impl Cpu {
                         0x540, // SCI1_TEI
                         0x700, // SCIF_ERI
                         0x720, // SCIF_RXI
-                        0x740, // SCIF_TXI
-                        0x760, // SCIF_TEI
+                        0x740, // SCIF_BRI
+                        0x760, // SCIF_TXI
                         0x560, // ITI
                         0x580, // RCMI
                         0x5A0, // ROVI
@@ -483,14 +503,12 @@ impl Cpu {
                     bus.ccn.registers.intevt = intevt_table[interrupt as usize];
                     bus.intc.registers.interrupt_requests &= !(1_u64 << int_index as u8);
 
-                    #[cfg(feature = "log_ints")]
-                    println!(
-                        "{:08x} firing interrupt for {:#?} {:04x} {:08x} @ cycle {}",
-                        self.registers.current_pc,
-                        interrupt,
-                        self.current_opcode,
-                        self.get_vbr() + 0x600,
-                        self.cyc
+                    context.tracer.record(
+                        TraceCategory::Interrupts,
+                        TraceEvent::Interrupt {
+                            interrupt: format!("{:?}", interrupt),
+                            vector: self.get_vbr() + 0x600,
+                        },
                     );
 
                     self.state = CpuState::Running;
@@ -515,213 +533,137 @@ impl Cpu {
 
             let decoded = self.opcode_lut[opcode as usize];
 
-            if !context.tracing && self.registers.current_pc == 0x8c010c30 {
-                #[cfg(feature = "trace_instrs")]
-                {
-                    context.tracing = true;
-                }
-            }
-
-
-            #[cfg(feature = "trace_instrs")]
-            if context.tracing {
-                unsafe {
-                    println!("{:08x} {:04x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x} {:08x}",
-                    self.registers.current_pc, opcode,
-                    self.get_register_by_index(0),
-                    self.get_register_by_index(1),
-                    self.get_register_by_index(2), self.get_register_by_index(3),
-                    self.get_register_by_index(4), self.get_register_by_index(5),
-                    self.get_register_by_index(6), self.get_register_by_index(7),
-                    self.get_register_by_index(8), self.get_register_by_index(9),
-                    self.get_register_by_index(10), self.get_register_by_index(11),
-                    self.get_register_by_index(12), self.get_register_by_index(13),
-                    self.get_register_by_index(14), self.get_register_by_index(15),
-                    f32::to_bits(self.get_fr_register_by_index(0)),
-                    f32::to_bits(self.get_fr_register_by_index(1)),
-                    f32::to_bits(self.get_fr_register_by_index(2)),
-                    f32::to_bits(self.get_fr_register_by_index(3)),
-                    f32::to_bits(self.get_fr_register_by_index(4)),
-                    f32::to_bits(self.get_fr_register_by_index(5)),
-                    f32::to_bits(self.get_fr_register_by_index(6)),
-                    f32::to_bits(self.get_fr_register_by_index(7)),
-                    f32::to_bits(self.get_fr_register_by_index(8)),
-                    f32::to_bits(self.get_fr_register_by_index(9)),
-                    f32::to_bits(self.get_fr_register_by_index(10)),
-                    f32::to_bits(self.get_fr_register_by_index(11)),
-                    f32::to_bits(self.get_fr_register_by_index(12)),
-                    f32::to_bits(self.get_fr_register_by_index(13)),
-                    f32::to_bits(self.get_fr_register_by_index(14)),
-                    f32::to_bits(self.get_fr_register_by_index(15)),
-                    self.get_sr(), self.get_fpscr())
-                };
-            }
-
-            // log some well known pc addresses in the bios to help getting the bios running
-            #[cfg(feature = "log_bios")]
-            {
-                // 8c00b6b0
-                let subroutine = match self.registers.current_pc {
-                    0x80000000 => "bios_entry".to_owned(),
-                    0x8c000c3e => "set_interrupts()".to_owned(),
-                    0x8c00b500 => "init_machine()".to_owned(),
-                    0x8c000d1c => "load_boot_file()".to_owned(),
-                    0x80000116 => "system_reset()".to_owned(),
-                    0x8c008300 => "IP.bin".to_owned(),
-                    0x8c000120 => "boot2()".to_owned(),
-                    //     0x0c000600 => "irq_handler()".to_owned(),
-                    0x8c002ff4 => match self.get_register_by_index(4) {
-                        16 => "CMD_PIOREAD".to_owned(),
-                        17 => "CMD_DMAREAD".to_owned(),
-                        18 => "CMD_GETTOC".to_owned(),
-                        19 => "CMD_GETTOC2".to_owned(),
-                        20 => "CMD_PLAY".to_owned(),
-                        24 => "CMD_INIT".to_owned(),
-                        35 => "CMD_GETTRACKS".to_owned(),
-                        _ => format!("syscall CMD_{}unk()", self.get_register_by_index(4)), //.to_owned(),
-                    },
-                    0x8c001c34 | 0x8c001ca8 => "gd_get_toc()".to_owned(),
-                    0x8c003570 => "gd_cmd_main_loop()".to_owned(),
-                    0x8c0011ec => format!("gd_do_cmd({:08x})", self.get_register_by_index(6)),
-                    0x8c0029a8 => "cdrom_response_loop()".to_owned(),
-                    0x8c000e7c => "exec_gdcmd()".to_owned(),
-                    0x8c000800 => {
-                        format!("sysDoBiosCall({})", self.get_register_by_index(4) as i32)
-                    }
-                    0x8c000590 => "check_iso_pvd".to_owned(),
-                    0x8c003450 => "gdc_reset()".to_owned(),
-                    0x8c001890 => format!("gdc_init_system()"),
-                    0x8c000420 => "boot3()".to_owned(),
-                    0x8c000ae4 => "boot4()".to_owned(),
-                    0x8c002b4c => "dispatch_gdrom_cmd()".to_owned(),
-                    0x8c000990 => "syBtCheckDisk()".to_owned(),
-                    0x8c0002c8 => "syBtExit()".to_owned(),
-                    0x8c000820 => "boot5()".to_owned(),
-                    0x8c000772 => "wait_timer()".to_owned(),
-                    0x8c00095c => "check_gdrive_stat()".to_owned(),
-                    0x8c000d02 => "check_disc()".to_owned(),
-                    0x8c00cb2a => "wait_for_new_frame()".to_owned(),
-                    0x8c184000 => "bios_anim_begin".to_owned(),
-                    0x8c00ca78 => format!(
-                        "bios_anim_state_machine({}, {}, {})",
-                        self.get_register_by_index(4),
-                        self.get_register_by_index(5),
-                        self.get_register_by_index(6)
-                    ),
-                    0x8c00c000 => {
-                        format!("bios_anim({:08x})", self.get_register_by_index(4))
-                    }
-                    _ => "".to_owned(),
-                };
-
-                if subroutine != "" {
-                    println!(
-                        "{:08x}: bios: {} @ cyc {}",
-                        self.registers.current_pc, subroutine, cyc
+            let pc = self.registers.current_pc;
+            context.tracer.begin_instruction(pc, cyc);
+
+            match context.tracer.level(TraceCategory::Instructions) {
+                TraceLevel::Off => {}
+                level => {
+                    let registers = (level == TraceLevel::Verbose).then(|| Sh4Registers {
+                        r: self.registers.r,
+                        fr: std::array::from_fn(|i| self.get_fr_register_by_index(i).to_bits()),
+                        sr: self.get_sr(),
+                        fpscr: self.get_fpscr(),
+                    });
+
+                    let disassembly =
+                        disassemble(&decoded, pc, self.get_fpscr(), bus, &self.symbols);
+                    let source = self
+                        .symbols
+                        .source_line(pc)
+                        .map(|(file, line)| format!("{}:{}", file, line));
+
+                    context.tracer.record(
+                        TraceCategory::Instructions,
+                        TraceEvent::Instruction {
+                            opcode,
+                            disassembly,
+                            source,
+                            registers,
+                        },
                     );
                 }
             }
 
-            // KOS symbol mapping to help with debugging
-            #[cfg(feature = "log_kos")]
-            if let Some(sym) = self
-                .symbols_map
-                .get(&(self.registers.current_pc & 0x1FFFFFFF))
-            {
-                println!(
-                    "{:08x}: calling {} @ cyc {}",
-                    self.registers.current_pc, sym, cyc
-                );
+            // well known bios routines, to help getting the bios running
+            if context.tracer.enabled(TraceCategory::Bios) {
+                if let Some(routine) = self.bios_routine() {
+                    context.tracer.message(TraceCategory::Bios, routine);
+                }
             }
 
-            if false  {
-                #[cfg(feature = "log_instrs")]
-                println!(
-                    "{:08x} {:04x}: {}",
-                    self.registers.current_pc,
-                    opcode,
-                    decoded.disasm()
-                );
+            // symbols from the loaded elf or map files, kos builds have plenty
+            if context.tracer.enabled(TraceCategory::Symbols) && self.symbols.get(pc).is_some() {
+                let location = self.symbols.describe(pc);
+                context
+                    .tracer
+                    .message(TraceCategory::Symbols, format!("calling {}", location));
             }
 
             // execute the decoded instruction
             (decoded.handler)(self, &decoded, bus, context);
-
-            //   #[cfg(feature = "log_instrs2")]
-            // writeln!(lock, "{:08x} {:04x}, {}", opcode, self.registers.current_pc, decoded.disassembly).unwrap();
         } else {
             self.process_interrupts(bus, context, 0);
         }
     }
 
-    pub fn symbolicate(&self, addr: u32) -> String {
-        // log some well known pc addresses in the bios to help getting the bios running
-        #[cfg(feature = "log_bios")]
-        {
-            let subroutine = match self.registers.current_pc {
-                0x80000000 => "bios_entry".to_owned(),
-                0x8c000c3e => "set_interrupts()".to_owned(),
-                0x8c00b500 => "init_machine()".to_owned(),
-                0x8c000d1c => "load_boot_file()".to_owned(),
-                0x80000116 => "system_reset()".to_owned(),
-                0x8c008300 => "IP.bin".to_owned(),
-                0x8c000120 => "boot2()".to_owned(),
-                //     0x0c000600 => "irq_handler()".to_owned(),
-                0x8c002ff4 => match self.get_register_by_index(4) {
-                    16 => "CMD_PIOREAD".to_owned(),
-                    17 => "CMD_DMAREAD".to_owned(),
-                    18 => "CMD_GETTOC".to_owned(),
-                    19 => "CMD_GETTOC2".to_owned(),
-                    20 => "CMD_PLAY".to_owned(),
-                    24 => "CMD_INIT".to_owned(),
-                    35 => "CMD_GETTRACKS".to_owned(),
-                    _ => format!("syscall CMD_{}unk()", self.get_register_by_index(4)), //.to_owned(),
-                },
-                0x8c001c34 | 0x8c001ca8 => "gd_get_toc()".to_owned(),
-                0x8c003570 => "gd_cmd_main_loop()".to_owned(),
-                0x8c0011ec => format!("gd_do_cmd({:08x})", self.get_register_by_index(6)),
-                0x8c0029a8 => "cdrom_response_loop()".to_owned(),
-                0x8c000e7c => "exec_gdcmd()".to_owned(),
-                0x8c000800 => {
-                    format!("sysDoBiosCall({})", self.get_register_by_index(4) as i32)
-                }
-                0x8c000590 => "check_iso_pvd".to_owned(),
-                0x8c003450 => "gdc_reset()".to_owned(),
-                0x8c001890 => format!("gdc_init_system()"),
-                0x8c000420 => "boot3()".to_owned(),
-                0x8c000ae4 => "boot4()".to_owned(),
-                0x8c002b4c => "dispatch_gdrom_cmd()".to_owned(),
-                0x8c000990 => "syBtCheckDisk()".to_owned(),
-                0x8c0002c8 => "syBtExit()".to_owned(),
-                0x8c000820 => "boot5()".to_owned(),
-                0x8c000772 => "wait_timer()".to_owned(),
-                0x8c00095c => "check_gdrive_stat()".to_owned(),
-                0x8c000d02 => "check_disc()".to_owned(),
-                0x8c00cb2a => "wait_for_new_frame()".to_owned(),
-                0x8c184000 => "bios_anim_begin".to_owned(),
-                0x8c00ca78 => format!(
-                    "bios_anim_state_machine({}, {}, {})",
-                    self.get_register_by_index(4),
-                    self.get_register_by_index(5),
-                    self.get_register_by_index(6)
-                ),
-                0x8c00c000 => {
-                    format!("bios_anim({:08x})", self.get_register_by_index(4))
-                }
-                _ => "".to_owned(),
-            };
-        }
+    // the bios routine starting at the current pc, if it's one we know
+    fn bios_routine(&self) -> Option<String> {
+        let routine = match self.registers.current_pc {
+            0x80000000 => "bios_entry".to_owned(),
+            0x8c000c3e => "set_interrupts()".to_owned(),
+            0x8c00b500 => "init_machine()".to_owned(),
+            0x8c000d1c => "load_boot_file()".to_owned(),
+            0x80000116 => "system_reset()".to_owned(),
+            0x8c008300 => "IP.bin".to_owned(),
+            0x8c000120 => "boot2()".to_owned(),
+            //     0x0c000600 => "irq_handler()".to_owned(),
+            0x8c002ff4 => match self.get_register_by_index(4) {
+                16 => "CMD_PIOREAD".to_owned(),
+                17 => "CMD_DMAREAD".to_owned(),
+                18 => "CMD_GETTOC".to_owned(),
+                19 => "CMD_GETTOC2".to_owned(),
+                20 => "CMD_PLAY".to_owned(),
+                24 => "CMD_INIT".to_owned(),
+                35 => "CMD_GETTRACKS".to_owned(),
+                _ => format!("syscall CMD_{}unk()", self.get_register_by_index(4)), //.to_owned(),
+            },
+            0x8c001c34 | 0x8c001ca8 => "gd_get_toc()".to_owned(),
+            0x8c003570 => "gd_cmd_main_loop()".to_owned(),
+            0x8c0011ec => format!("gd_do_cmd({:08x})", self.get_register_by_index(6)),
+            0x8c0029a8 => "cdrom_response_loop()".to_owned(),
+            0x8c000e7c => "exec_gdcmd()".to_owned(),
+            0x8c000800 => {
+                format!("sysDoBiosCall({})", self.get_register_by_index(4) as i32)
+            }
+            0x8c000590 => "check_iso_pvd".to_owned(),
+            0x8c003450 => "gdc_reset()".to_owned(),
+            0x8c001890 => format!("gdc_init_system()"),
+            0x8c000420 => "boot3()".to_owned(),
+            0x8c000ae4 => "boot4()".to_owned(),
+            0x8c002b4c => "dispatch_gdrom_cmd()".to_owned(),
+            0x8c000990 => "syBtCheckDisk()".to_owned(),
+            0x8c0002c8 => "syBtExit()".to_owned(),
+            0x8c000820 => "boot5()".to_owned(),
+            0x8c000772 => "wait_timer()".to_owned(),
+            0x8c00095c => "check_gdrive_stat()".to_owned(),
+            0x8c000d02 => "check_disc()".to_owned(),
+            0x8c00cb2a => "wait_for_new_frame()".to_owned(),
+            0x8c184000 => "bios_anim_begin".to_owned(),
+            0x8c00ca78 => format!(
+                "bios_anim_state_machine({}, {}, {})",
+                self.get_register_by_index(4),
+                self.get_register_by_index(5),
+                self.get_register_by_index(6)
+            ),
+            0x8c00c000 => {
+                format!("bios_anim({:08x})", self.get_register_by_index(4))
+            }
+            _ => return None,
+        };
 
-        // KOS symbol mapping to help with debugging
-        if let Some(sym) = self
-            .symbols_map
-            .get(&(self.registers.current_pc & 0x1FFFFFFF))
-        {
-            return sym.clone();
-        }
+        Some(routine)
+    }
 
-        return format!("0x{:08x}", addr);
+    // "func+0x1c (main.c:42)" when the symbols know about addr, otherwise just the address
+    pub fn symbolicate(&self, addr: u32) -> String {
+        self.symbols.describe(addr)
+    }
+
+    // the instruction at addr as the cpu would currently run it, fpscr decides what the fpu ones mean
+    pub fn disassemble(&self, addr: u32, bus: &CpuBus) -> String {
+        match bus.peek_16(addr) {
+            Some(opcode) => disassemble(
+                &self.opcode_lut[opcode as usize],
+                addr,
+                self.get_fpscr(),
+                bus,
+                &self.symbols,
+            ),
+            None => "??".to_owned(),
+        }
     }
+
     pub fn step(&mut self, bus: &mut CpuBus, context: &mut Context, cyc: u64) {
         self.exec_next_opcode(bus, context, cyc);
     }
@@ -2883,13 +2825,7 @@ impl Cpu {
             let ext_addr = (addr & 0x03ffffe0) | ((sq_base & 0x1c) << 24);
             let sq_idx = if sq { 1 } else { 0 };
 
-            for i in 0..8 {
-                bus.write_32(
-                    (ext_addr + (4 * i)) as u32,
-                    bus.store_queues[sq_idx][i as usize],
-                    context,
-                );
-            }
+            bus.flush_store_queue(sq_idx, ext_addr, context);
         }
 
         self.registers.current_pc = self.registers.current_pc.wrapping_add(2);
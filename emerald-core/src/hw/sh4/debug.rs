@@ -0,0 +1,204 @@
+// breakpoints, watchpoints and mmio hooks on the sh4's view of memory.
+//
+// the bus only records accesses that match something while an instruction runs. once it finishes the
+// machine stamps them with the instruction's pc (like device errors, see Context::fault), runs the
+// callbacks and pauses if anything asked to stop. with nothing registered the bus skips all of this.
+
+use std::{cell::RefCell, collections::BTreeSet, ops::RangeInclusive};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AccessKind {
+    Read,
+    Write,
+    StoreQueueFlush, // a pref writing a store queue out, one of these per longword
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AddressSpace {
+    Logical,  // the address the cpu issued, so p1 and p2 mirrors are distinct
+    Physical, // after translation, catches every mirror
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub addr: u32,
+    pub physical: u32,
+    pub size: u8, // in bits
+    pub value: u64,
+    pub pc: u32,
+    pub cycle: u64,
+}
+
+pub enum WatchAction {
+    Stop,
+    Callback(Box<dyn FnMut(&MemoryAccess) + Send>),
+}
+
+pub struct Watchpoint {
+    pub space: AddressSpace,
+    pub range: RangeInclusive<u32>,
+    pub read: bool,
+    pub write: bool,        // store queue flushes count as writes
+    pub value: Option<u64>, // only hit when this is the value read or written
+    pub action: WatchAction,
+}
+
+impl Watchpoint {
+    fn matches(&self, access: &MemoryAccess) -> bool {
+        let wanted = match access.kind {
+            AccessKind::Read => self.read,
+            AccessKind::Write | AccessKind::StoreQueueFlush => self.write,
+        };
+
+        let start = match self.space {
+            AddressSpace::Logical => access.addr,
+            AddressSpace::Physical => access.physical,
+        };
+
+        let end = start.saturating_add(access.size as u32 / 8 - 1);
+        let overlaps = start <= *self.range.end() && end >= *self.range.start();
+
+        wanted && overlaps && self.value.map_or(true, |value| value == access.value)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub struct WatchId(u64);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DebugStop {
+    Breakpoint { pc: u32 },
+    Watchpoint { id: WatchId, access: MemoryAccess },
+}
+
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeSet<u32>,
+    watchpoints: Vec<(WatchId, Watchpoint)>,
+    next_id: u64,
+    hits: RefCell<Vec<(WatchId, MemoryAccess)>>, // reads only have &self
+    resuming_from: Option<u32>,                  // the breakpoint we last stopped on
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn add_breakpoint(&mut self, pc: u32) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn remove_breakpoint(&mut self, pc: u32) -> bool {
+        self.breakpoints.remove(&pc)
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+        self.resuming_from = None;
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> WatchId {
+        let id = WatchId(self.next_id);
+        self.next_id += 1;
+        self.watchpoints.push((id, watchpoint));
+        id
+    }
+
+    pub fn remove_watchpoint(&mut self, id: WatchId) -> bool {
+        let count = self.watchpoints.len();
+        self.watchpoints.retain(|(existing, _)| *existing != id);
+        self.watchpoints.len() != count
+    }
+
+    // calls back on every access to a physical range, whatever the size or value
+    pub fn add_mmio_hook<F>(&mut self, range: RangeInclusive<u32>, callback: F) -> WatchId
+    where
+        F: FnMut(&MemoryAccess) + Send + 'static,
+    {
+        self.add_watchpoint(Watchpoint {
+            space: AddressSpace::Physical,
+            range,
+            read: true,
+            write: true,
+            value: None,
+            action: WatchAction::Callback(Box::new(callback)),
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.breakpoints.clear();
+        self.watchpoints.clear();
+        self.hits.get_mut().clear();
+        self.resuming_from = None;
+    }
+
+    pub fn watching(&self) -> bool {
+        !self.watchpoints.is_empty()
+    }
+
+    pub(crate) fn record(&self, kind: AccessKind, addr: u32, physical: u32, size: u8, value: u64) {
+        let access = MemoryAccess {
+            kind,
+            addr,
+            physical,
+            size,
+            value,
+            pc: 0,
+            cycle: 0,
+        };
+
+        for (id, watchpoint) in &self.watchpoints {
+            if watchpoint.matches(&access) {
+                self.hits.borrow_mut().push((*id, access));
+            }
+        }
+    }
+
+    // hits recorded since the last dispatch, see CpuBus::untracked
+    pub(crate) fn pending_hits(&self) -> usize {
+        self.hits.borrow().len()
+    }
+
+    pub(crate) fn discard_hits(&mut self, from: usize) {
+        self.hits.get_mut().truncate(from);
+    }
+
+    // called before executing pc. stopping on a breakpoint and then stepping again executes it
+    pub fn check_breakpoint(&mut self, pc: u32) -> Option<DebugStop> {
+        if self.breakpoints.is_empty() {
+            return None;
+        }
+
+        if self.resuming_from.take() != Some(pc) && self.breakpoints.contains(&pc) {
+            self.resuming_from = Some(pc);
+            return Some(DebugStop::Breakpoint { pc });
+        }
+
+        None
+    }
+
+    // runs the callbacks for everything the last instruction hit, returns the first stop
+    pub fn dispatch(&mut self, pc: u32, cycle: u64) -> Option<DebugStop> {
+        let mut stop = None;
+
+        for (id, mut access) in self.hits.get_mut().drain(..) {
+            access.pc = pc;
+            access.cycle = cycle;
+
+            let Some((_, watchpoint)) = self.watchpoints.iter_mut().find(|(w, _)| *w == id) else {
+                continue;
+            };
+
+            match &mut watchpoint.action {
+                WatchAction::Stop => {
+                    stop.get_or_insert(DebugStop::Watchpoint { id, access });
+                }
+                WatchAction::Callback(callback) => callback(&access),
+            }
+        }
+
+        stop
+    }
+}
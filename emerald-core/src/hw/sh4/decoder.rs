@@ -42,326 +42,326 @@ pub struct DecodedInstruction {
 type InstructionHandler = fn(&mut Cpu, &DecodedInstruction, &mut CpuBus, &mut Context) -> ();
 
 pub fn build_opcode_lut() -> Vec<DecodedInstruction> {
-    let mut lut = vec![
-        DecodedInstruction {
-            opcode: InstructionOpcode(0),
+    // unknown opcodes still carry their opcode so they can be disassembled as data
+    let mut lut: Vec<DecodedInstruction> = (0..=u16::MAX)
+        .map(|i| DecodedInstruction {
+            opcode: InstructionOpcode(i),
             disassembly: "unk",
             handler: super::cpu::Cpu::unk,
-        };
-        u16::MAX as usize + 1
-    ];
+        })
+        .collect();
 
     let instructions = [
         (
             0b0110000000000011,
             0b0000111111110000,
-            "mov Rm, Rn",
+            "mov Rm,Rn",
             super::cpu::Cpu::mov as InstructionHandler,
         ),
         (
             0b1110000000000000,
             0b0000111111111111,
-            "movi #imm, Rn",
+            "mov #imm,Rn",
             super::cpu::Cpu::movi as InstructionHandler,
         ),
         (
             0b1100011100000000,
             0b0000000011111111,
-            "mova @(disp8, PC), r0",
+            "mova @(disp,PC),R0",
             super::cpu::Cpu::mova as InstructionHandler,
         ),
         (
             0b1001000000000000,
             0b0000111111111111,
-            "movwi",
+            "mov.w @(disp,PC),Rn",
             super::cpu::Cpu::movwi as InstructionHandler,
         ),
         (
             0b1101000000000000,
             0b0000111111111111,
-            "mov.l @(disp8+PC), Rn",
+            "mov.l @(disp,PC),Rn",
             super::cpu::Cpu::movli as InstructionHandler,
         ),
         (
             0b0110000000000000,
             0b0000111111110000,
-            "movbl",
+            "mov.b @Rm,Rn",
             super::cpu::Cpu::movbl as InstructionHandler,
         ),
         (
             0b0110000000000001,
             0b0000111111110000,
-            "movwl",
+            "mov.w @Rm,Rn",
             super::cpu::Cpu::movwl as InstructionHandler,
         ),
         (
             0b0110000000000010,
             0b0000111111110000,
-            "movll",
+            "mov.l @Rm,Rn",
             super::cpu::Cpu::movll as InstructionHandler,
         ),
         (
             0b0010000000000000,
             0b0000111111110000,
-            "movbs",
+            "mov.b Rm,@Rn",
             super::cpu::Cpu::movbs as InstructionHandler,
         ),
         (
             0b0010000000000001,
             0b0000111111110000,
-            "movws",
+            "mov.w Rm,@Rn",
             super::cpu::Cpu::movws as InstructionHandler,
         ),
         (
             0b0010000000000010,
             0b0000111111110000,
-            "mov.l Rm, @Rn",
+            "mov.l Rm,@Rn",
             super::cpu::Cpu::movls as InstructionHandler,
         ),
         (
             0b0110000000000100,
             0b0000111111110000,
-            "mov.b @Rm+, Rn",
+            "mov.b @Rm+,Rn",
             super::cpu::Cpu::movbp as InstructionHandler,
         ),
         (
             0b0110000000000101,
             0b0000111111110000,
-            "movwp",
+            "mov.w @Rm+,Rn",
             super::cpu::Cpu::movwp as InstructionHandler,
         ),
         (
             0b0110000000000110,
             0b0000111111110000,
-            "mov.l @Rm+, Rn",
+            "mov.l @Rm+,Rn",
             super::cpu::Cpu::movlp as InstructionHandler,
         ),
         (
             0b0010000000000100,
             0b0000111111110000,
-            "movbm",
+            "mov.b Rm,@-Rn",
             super::cpu::Cpu::movbm as InstructionHandler,
         ),
         (
             0b0010000000000101,
             0b0000111111110000,
-            "movwm",
+            "mov.w Rm,@-Rn",
             super::cpu::Cpu::movwm as InstructionHandler,
         ),
         (
             0b0010000000000110,
             0b0000111111110000,
-            "movlm",
+            "mov.l Rm,@-Rn",
             super::cpu::Cpu::movlm as InstructionHandler,
         ),
         (
             0b1000010000000000,
             0b0000000011111111,
-            "mov.b @(disp4, Rm), r0",
+            "mov.b @(disp,Rm),R0",
             super::cpu::Cpu::movbl4 as InstructionHandler,
         ),
         (
             0b1000010100000000,
             0b0000000011111111,
-            "movwl4",
+            "mov.w @(disp,Rm),R0",
             super::cpu::Cpu::movwl4 as InstructionHandler,
         ),
         (
             0b0101000000000000,
             0b0000111111111111,
-            "movll4",
+            "mov.l @(disp,Rm),Rn",
             super::cpu::Cpu::movll4 as InstructionHandler,
         ),
         (
             0b1000000000000000,
             0b0000000011111111,
-            "movbs4",
+            "mov.b R0,@(disp,Rm)",
             super::cpu::Cpu::movbs4 as InstructionHandler,
         ),
         (
             0b1000000100000000,
             0b0000000011111111,
-            "movws4",
+            "mov.w R0,@(disp,Rm)",
             super::cpu::Cpu::movws4 as InstructionHandler,
         ),
         (
             0b0001000000000000,
             0b0000111111111111,
-            "movls4",
+            "mov.l Rm,@(disp,Rn)",
             super::cpu::Cpu::movls4 as InstructionHandler,
         ),
         (
             0b0000000000001100,
             0b0000111111110000,
-            "mov.b @(r0, Rm), Rn",
+            "mov.b @(R0,Rm),Rn",
             super::cpu::Cpu::movbl0 as InstructionHandler,
         ),
         (
             0b0000000000001101,
             0b0000111111110000,
-            "movwl0",
+            "mov.w @(R0,Rm),Rn",
             super::cpu::Cpu::movwl0 as InstructionHandler,
         ),
         (
             0b0000000000001110,
             0b0000111111110000,
-            "movll0",
+            "mov.l @(R0,Rm),Rn",
             super::cpu::Cpu::movll0 as InstructionHandler,
         ),
         (
             0b0000000000000100,
             0b0000111111110000,
-            "mov.b Rm, @(r0, Rn)",
+            "mov.b Rm,@(R0,Rn)",
             super::cpu::Cpu::movbs0 as InstructionHandler,
         ),
         (
             0b0000000000000101,
             0b0000111111110000,
-            "movws0",
+            "mov.w Rm,@(R0,Rn)",
             super::cpu::Cpu::movws0 as InstructionHandler,
         ),
         (
             0b0000000000000110,
             0b0000111111110000,
-            "movls0",
+            "mov.l Rm,@(R0,Rn)",
             super::cpu::Cpu::movls0 as InstructionHandler,
         ),
         (
             0b1100010000000000,
             0b0000000011111111,
-            "movblg",
+            "mov.b @(disp,GBR),R0",
             super::cpu::Cpu::movblg as InstructionHandler,
         ),
         (
             0b1100010100000000,
             0b0000000011111111,
-            "movwlg",
+            "mov.w @(disp,GBR),R0",
             super::cpu::Cpu::movwlg as InstructionHandler,
         ),
         (
             0b1100011000000000,
             0b0000000011111111,
-            "mov.l @(disp8, gbr), r0",
+            "mov.l @(disp,GBR),R0",
             super::cpu::Cpu::movllg as InstructionHandler,
         ),
         (
             0b1100000000000000,
             0b0000000011111111,
-            "movbsg",
+            "mov.b R0,@(disp,GBR)",
             super::cpu::Cpu::movbsg as InstructionHandler,
         ),
         (
             0b1100000100000000,
             0b0000000011111111,
-            "movwsg",
+            "mov.w R0,@(disp,GBR)",
             super::cpu::Cpu::movwsg as InstructionHandler,
         ),
         (
             0b1100001000000000,
             0b0000000011111111,
-            "movlsg",
+            "mov.l R0,@(disp,GBR)",
             super::cpu::Cpu::movlsg as InstructionHandler,
         ),
         (
             0b0000000000101001,
             0b0000111100000000,
-            "movt",
+            "movt Rn",
             super::cpu::Cpu::movt as InstructionHandler,
         ),
         (
             0b0110000000001000,
             0b0000111111110000,
-            "swap.b",
+            "swap.b Rm,Rn",
             super::cpu::Cpu::swapb as InstructionHandler,
         ),
         (
             0b0110000000001001,
             0b0000111111110000,
-            "swap.w",
+            "swap.w Rm,Rn",
             super::cpu::Cpu::swapw as InstructionHandler,
         ),
         (
             0b0010000000001101,
             0b0000111111110000,
-            "xtrct",
+            "xtrct Rm,Rn",
             super::cpu::Cpu::xtrct as InstructionHandler,
         ),
         (
             0b0011000000001100,
             0b0000111111110000,
-            "add",
+            "add Rm,Rn",
             super::cpu::Cpu::add as InstructionHandler,
         ),
         (
             0b0111000000000000,
             0b0000111111111111,
-            "add #imm",
+            "add #imm,Rn",
             super::cpu::Cpu::addi as InstructionHandler,
         ),
         (
             0b0011000000001110,
             0b0000111111110000,
-            "addc",
+            "addc Rm,Rn",
             super::cpu::Cpu::addc as InstructionHandler,
         ),
         (
             0b1000100000000000,
             0b0000000011111111,
-            "cmp/eq #imm",
+            "cmp/eq #imm,R0",
             super::cpu::Cpu::cmpimm as InstructionHandler,
         ),
         (
             0b0011000000000000,
             0b0000111111110000,
-            "cmp/eq",
+            "cmp/eq Rm,Rn",
             super::cpu::Cpu::cmpeq as InstructionHandler,
         ),
         (
             0b0011000000000010,
             0b0000111111110000,
-            "cmp/hs",
+            "cmp/hs Rm,Rn",
             super::cpu::Cpu::cmphieq as InstructionHandler,
         ),
         (
             0b0011000000000011,
             0b0000111111110000,
-            "cmp/ge",
+            "cmp/ge Rm,Rn",
             super::cpu::Cpu::cmpge as InstructionHandler,
         ),
         (
             0b0011000000000110,
             0b0000111111110000,
-            "cmp/hi",
+            "cmp/hi Rm,Rn",
             super::cpu::Cpu::cmphi as InstructionHandler,
         ),
         (
             0b0011000000000111,
             0b0000111111110000,
-            "cmp/gt",
+            "cmp/gt Rm,Rn",
             super::cpu::Cpu::cmpgt as InstructionHandler,
         ),
         (
             0b0100000000010101,
             0b0000111100000000,
-            "cmp/pl",
+            "cmp/pl Rn",
             super::cpu::Cpu::cmppl as InstructionHandler,
         ),
         (
             0b0100000000010001,
             0b0000111100000000,
-            "cmp/pz",
+            "cmp/pz Rn",
             super::cpu::Cpu::cmppz as InstructionHandler,
         ),
         (
             0b0010000000001100,
             0b0000111111110000,
-            "cmp/str",
+            "cmp/str Rm,Rn",
             super::cpu::Cpu::cmpstr as InstructionHandler,
         ),
         (
             0b0010000000000111,
             0b0000111111110000,
-            "div0s",
+            "div0s Rm,Rn",
             super::cpu::Cpu::div0s as InstructionHandler,
         ),
         (
@@ -373,49 +373,49 @@ pub fn build_opcode_lut() -> Vec<DecodedInstruction> {
         (
             0b0011000000000100,
             0b0000111111110000,
-            "div1",
+            "div1 Rm,Rn",
             super::cpu::Cpu::div1 as InstructionHandler,
         ),
         (
             0b0011000000001101,
             0b0000111111110000,
-            "dmuls.l",
+            "dmuls.l Rm,Rn",
             super::cpu::Cpu::dmulu2 as InstructionHandler,
         ),
         (
             0b0011000000000101,
             0b0000111111110000,
-            "dmulu.l",
+            "dmulu.l Rm,Rn",
             super::cpu::Cpu::dmulu as InstructionHandler,
         ),
         (
             0b0100000000010000,
             0b0000111100000000,
-            "dt",
+            "dt Rn",
             super::cpu::Cpu::dt as InstructionHandler,
         ),
         (
             0b0110000000001110,
             0b0000111111110000,
-            "exts.b",
+            "exts.b Rm,Rn",
             super::cpu::Cpu::extsb as InstructionHandler,
         ),
         (
             0b0110000000001111,
             0b0000111111110000,
-            "exts.w",
+            "exts.w Rm,Rn",
             super::cpu::Cpu::extsw as InstructionHandler,
         ),
         (
             0b0110000000001100,
             0b0000111111110000,
-            "extu.b",
+            "extu.b Rm,Rn",
             super::cpu::Cpu::extub as InstructionHandler,
         ),
         (
             0b0110000000001101,
             0b0000111111110000,
-            "extu.w",
+            "extu.w Rm,Rn",
             super::cpu::Cpu::extuw as InstructionHandler,
         ),
         (
@@ -433,79 +433,79 @@ pub fn build_opcode_lut() -> Vec<DecodedInstruction> {
         (
             0b0010000000001110,
             0b0000111111110000,
-            "mulu.w",
+            "mulu.w Rm,Rn",
             super::cpu::Cpu::mulu as InstructionHandler,
         ),
         (
             0b0010000000001111,
             0b0000111111110000,
-            "muls",
+            "muls.w Rm,Rn",
             super::cpu::Cpu::muls as InstructionHandler,
         ),
         (
             0b0000000000000111,
             0b0000111111110000,
-            "mull",
+            "mul.l Rm,Rn",
             super::cpu::Cpu::mul as InstructionHandler,
         ),
         (
             0b0110000000001011,
             0b0000111111110000,
-            "neg",
+            "neg Rm,Rn",
             super::cpu::Cpu::neg as InstructionHandler,
         ),
         (
             0b0110000000001010,
             0b0000111111110000,
-            "negc",
+            "negc Rm,Rn",
             super::cpu::Cpu::negc as InstructionHandler,
         ),
         (
             0b0011000000001000,
             0b0000111111110000,
-            "sub",
+            "sub Rm,Rn",
             super::cpu::Cpu::sub as InstructionHandler,
         ),
         (
             0b0011000000001010,
             0b0000111111110000,
-            "subc",
+            "subc Rm,Rn",
             super::cpu::Cpu::subc as InstructionHandler,
         ),
         (
             0b0010000000001001,
             0b0000111111110000,
-            "and",
+            "and Rm,Rn",
             super::cpu::Cpu::and as InstructionHandler,
         ),
         (
             0b1100100100000000,
             0b0000000011111111,
-            "and #imm",
+            "and #imm,R0",
             super::cpu::Cpu::andi as InstructionHandler,
         ),
         (
             0b0110000000000111,
             0b0000111111110000,
-            "not",
+            "not Rm,Rn",
             super::cpu::Cpu::not as InstructionHandler,
         ),
         (
             0b0010000000001011,
             0b0000111111110000,
-            "or",
+            "or Rm,Rn",
             super::cpu::Cpu::or as InstructionHandler,
         ),
         (
             0b1100101100000000,
             0b0000000011111111,
-            "or #imm",
+            "or #imm,R0",
             super::cpu::Cpu::ori as InstructionHandler,
         ),
         (
             0b1100111100000000,
             0b0000000011111111,
-            "orm",
+            "or.b #imm,@(R0,GBR)",
             super::cpu::Cpu::orm as InstructionHandler,
         ),
         (
@@ -517,175 +517,175 @@ pub fn build_opcode_lut() -> Vec<DecodedInstruction> {
         (
             0b0010000000001000,
             0b0000111111110000,
-            "tst",
+            "tst Rm,Rn",
             super::cpu::Cpu::tst as InstructionHandler,
         ),
         (
             0b1100100000000000,
             0b0000000011111111,
-            "tst #imm",
+            "tst #imm,R0",
             super::cpu::Cpu::tsti as InstructionHandler,
         ),
         (
             0b0010000000001010,
             0b0000111111110000,
-            "xor",
+            "xor Rm,Rn",
             super::cpu::Cpu::xor as InstructionHandler,
         ),
         (
             0b1100101000000000,
             0b0000000011111111,
-            "xor #imm",
+            "xor #imm,R0",
             super::cpu::Cpu::xori as InstructionHandler,
         ),
         (
             0b0100000000100100,
             0b0000111100000000,
-            "rotcl",
+            "rotcl Rn",
             super::cpu::Cpu::rotcl as InstructionHandler,
         ),
         (
             0b0100000000100101,
             0b0000111100000000,
-            "rotcr",
+            "rotcr Rn",
             super::cpu::Cpu::rotcr as InstructionHandler,
         ),
         (
             0b0100000000000100,
             0b0000111100000000,
-            "rotl",
+            "rotl Rn",
             super::cpu::Cpu::rotl as InstructionHandler,
         ),
         (
             0b0100000000000101,
             0b0000111100000000,
-            "rotr",
+            "rotr Rn",
             super::cpu::Cpu::rotr as InstructionHandler,
         ),
         (
             0b0100000000001100,
             0b0000111111110000,
-            "shad",
+            "shad Rm,Rn",
             super::cpu::Cpu::shad as InstructionHandler,
         ),
         (
             0b0100000000100001,
             0b0000111100000000,
-            "shar",
+            "shar Rn",
             super::cpu::Cpu::shar as InstructionHandler,
         ),
         (
             0b0100000000001101,
             0b0000111111110000,
-            "shld",
+            "shld Rm,Rn",
             super::cpu::Cpu::shld as InstructionHandler,
         ),
         (
             0b0100000000000000,
             0b0000111100000000,
-            "shll",
+            "shll Rn",
             super::cpu::Cpu::shll as InstructionHandler,
         ),
         (
             0b0100000000001000,
             0b0000111100000000,
-            "shll2",
+            "shll2 Rn",
             super::cpu::Cpu::shll2 as InstructionHandler,
         ),
         (
             0b0100000000011000,
             0b0000111100000000,
-            "shll8",
+            "shll8 Rn",
             super::cpu::Cpu::shll8 as InstructionHandler,
         ),
         (
             0b0100000000101000,
             0b0000111100000000,
-            "shll16",
+            "shll16 Rn",
             super::cpu::Cpu::shll16 as InstructionHandler,
         ),
         (
             0b0100000000000001,
             0b0000111100000000,
-            "shlr",
+            "shlr Rn",
             super::cpu::Cpu::shlr as InstructionHandler,
         ),
         (
             0b0100000000001001,
             0b0000111100000000,
-            "shlr2",
+            "shlr2 Rn",
             super::cpu::Cpu::shlr2 as InstructionHandler,
         ),
         (
             0b0100000000011001,
             0b0000111100000000,
-            "shlr8",
+            "shlr8 Rn",
             super::cpu::Cpu::shlr8 as InstructionHandler,
         ),
         (
             0b0100000000101001,
             0b0000111100000000,
-            "shlr16",
+            "shlr16 Rn",
             super::cpu::Cpu::shlr16 as InstructionHandler,
         ),
         (
             0b1000101100000000,
             0b0000000011111111,
-            "bf label:8",
+            "bf label",
             super::cpu::Cpu::branch_if_false as InstructionHandler,
         ),
         (
             0b1000111100000000,
             0b0000000011111111,
-            "bf/s label:8",
+            "bf/s label",
             super::cpu::Cpu::branch_if_false_delayed as InstructionHandler,
         ),
         (
             0b1000100100000000,
             0b0000000011111111,
-            "bt label:8",
+            "bt label",
             super::cpu::Cpu::branch_if_true as InstructionHandler,
         ),
         (
             0b1000110100000000,
             0b0000000011111111,
-            "bt/s label:8",
+            "bt/s label",
             super::cpu::Cpu::branch_if_true_delayed as InstructionHandler,
         ),
         (
             0b1010000000000000,
             0b0000111111111111,
-            "bra label:12",
+            "bra label",
             super::cpu::Cpu::bra as InstructionHandler,
         ),
         (
             0b0000000000100011,
             0b0000111100000000,
-            "braf",
+            "braf Rn",
             super::cpu::Cpu::braf as InstructionHandler,
         ),
         (
             0b1011000000000000,
             0b0000111111111111,
-            "bsr label:12",
+            "bsr label",
             super::cpu::Cpu::bsr as InstructionHandler,
         ),
         (
             0b0000000000000011,
             0b0000111100000000,
-            "bsrf",
+            "bsrf Rn",
             super::cpu::Cpu::bsrf as InstructionHandler,
         ),
         (
             0b0100000000101011,
             0b0000111100000000,
-            "jmp",
+            "jmp @Rn",
             super::cpu::Cpu::jmp as InstructionHandler,
         ),
         (
             0b0100000000001011,
             0b0000111100000000,
-            "jsr",
+            "jsr @Rn",
             super::cpu::Cpu::jsr as InstructionHandler,
         ),
         (
@@ -829,7 +829,7 @@ pub fn build_opcode_lut() -> Vec<DecodedInstruction> {
         (
             0b0000000011000011,
             0b0000111100000000,
-            "movca.l",
+            "movca.l R0,@Rn",
             super::cpu::Cpu::movcal as InstructionHandler,
         ),
         (
@@ -841,25 +841,25 @@ pub fn build_opcode_lut() -> Vec<DecodedInstruction> {
         (
             0b0000000010010011,
             0b0000111100000000,
-            "ocbi",
+            "ocbi @Rn",
             super::cpu::Cpu::nop as InstructionHandler,
         ),
         (
             0b0000000010100011,
             0b0000111100000000,
-            "ocbp",
+            "ocbp @Rn",
             super::cpu::Cpu::nop as InstructionHandler,
         ),
         (
             0b0000000010110011,
             0b0000111100000000,
-            "ocbwb",
+            "ocbwb @Rn",
             super::cpu::Cpu::nop as InstructionHandler,
         ),
         (
             0b0000000010000011,
             0b0000111100000000,
-            "pref",
+            "pref @Rn",
             super::cpu::Cpu::pref as InstructionHandler,
         ),
         (
@@ -985,13 +985,13 @@ pub fn build_opcode_lut() -> Vec<DecodedInstruction> {
         (
             0b1111000010101101,
             0b0000111000000000,
-            "fcnvsd",
+            "fcnvsd FPUL,DRn",
             super::cpu::Cpu::fcnvsd as InstructionHandler,
         ),
         (
             0b1111000010111101,
             0b0000111000000000,
-            "fcnvds",
+            "fcnvds DRn,FPUL",
             super::cpu::Cpu::fcnvds as InstructionHandler,
         ),
         (
@@ -1039,121 +1039,121 @@ pub fn build_opcode_lut() -> Vec<DecodedInstruction> {
         (
             0b1111000010001101,
             0b0000111100000000,
-            "fldi0",
+            "fldi0 FRn",
             super::cpu::Cpu::fldi0 as InstructionHandler,
         ),
         (
             0b1111000010011101,
             0b0000111100000000,
-            "fldi1",
+            "fldi1 FRn",
             super::cpu::Cpu::fldi1 as InstructionHandler,
         ),
         (
             0b1111000000011101,
             0b0000111100000000,
-            "flds",
+            "flds FRn,FPUL",
             super::cpu::Cpu::flds as InstructionHandler,
         ),
         (
             0b1111000000001101,
             0b0000111100000000,
-            "fsts",
+            "fsts FPUL,FRn",
             super::cpu::Cpu::fsts as InstructionHandler,
         ),
         (
             0b1111000001011101,
             0b0000111100000000,
-            "fabs",
+            "fabs FRn",
             super::cpu::Cpu::fabs as InstructionHandler,
         ),
         (
             0b1111000001001101,
             0b0000111100000000,
-            "fneg",
+            "fneg FRn",
             super::cpu::Cpu::fneg as InstructionHandler,
         ),
         (
             0b1111000000000000,
             0b0000111111110000,
-            "fadd",
+            "fadd FRm,FRn",
             super::cpu::Cpu::fadd as InstructionHandler,
         ),
         (
             0b1111000000000001,
             0b0000111111110000,
-            "fsub",
+            "fsub FRm,FRn",
             super::cpu::Cpu::fsub as InstructionHandler,
         ),
         (
             0b1111000000000010,
             0b0000111111110000,
-            "fmul",
+            "fmul FRm,FRn",
             super::cpu::Cpu::fmul as InstructionHandler,
         ),
         (
             0b1111000000001110,
             0b0000111111110000,
-            "fmac",
+            "fmac FR0,FRm,FRn",
             super::cpu::Cpu::fmac as InstructionHandler,
         ),
         (
             0b1111000000000011,
             0b0000111111110000,
-            "fdiv",
+            "fdiv FRm,FRn",
             super::cpu::Cpu::fdiv as InstructionHandler,
         ),
         (
             0b1111000001101101,
             0b0000111100000000,
-            "fsqrt",
+            "fsqrt FRn",
             super::cpu::Cpu::fsqrt as InstructionHandler,
         ),
         (
             0b1111000000000100,
             0b0000111111110000,
-            "fcmp/eq",
+            "fcmp/eq FRm,FRn",
             super::cpu::Cpu::fcmpeq as InstructionHandler,
         ),
         (
             0b1111000000000101,
             0b0000111111110000,
-            "fcmp/gt",
+            "fcmp/gt FRm,FRn",
             super::cpu::Cpu::fcmpgt as InstructionHandler,
         ),
         (
             0b1111000000101101,
             0b0000111100000000,
-            "float",
+            "float FPUL,FRn",
             super::cpu::Cpu::float as InstructionHandler,
         ),
         (
             0b1111000000111101,
             0b0000111100000000,
-            "ftrc",
+            "ftrc FRn,FPUL",
             super::cpu::Cpu::ftrc as InstructionHandler,
         ),
         (
             0b1111000011101101,
             0b0000111100000000,
-            "fipr",
+            "fipr FVm,FVn",
             super::cpu::Cpu::fipr as InstructionHandler,
         ),
         (
             0b1111000111111101,
             0b0000110000000000,
-            "ftrv",
+            "ftrv XMTRX,FVn",
             super::cpu::Cpu::ftrv as InstructionHandler,
         ),
         (
             0b1111000001111101,
             0b0000111100000000,
-            "fsrra",
+            "fsrra FRn",
             super::cpu::Cpu::fsrra as InstructionHandler,
         ),
         (
             0b1111000011111101,
             0b0000111000000000,
-            "fsca",
+            "fsca FPUL,DRn",
             super::cpu::Cpu::fsca as InstructionHandler,
         ),
         (
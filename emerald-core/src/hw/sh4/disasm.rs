@@ -0,0 +1,163 @@
+// renders the decoder's operand templates in hitachi syntax.
+//
+// a template names its operands by where they live in the opcode rather than by the manual's n/m, Rn and
+// FRn are bits 8-11, Rm and FRm bits 4-7, Rm_BANK bits 4-6 and disp/#imm/label the low 4, 8 or 12 bits.
+// the fpu's register operands depend on fpscr (sz for fmov, pr for arithmetic) so that's passed in too.
+
+use super::{bus::CpuBus, decoder::DecodedInstruction};
+use crate::{hw::extensions::BitManipulation, symbols::SymbolTable};
+
+const TOKENS: [&str; 14] = [
+    "Rm_BANK", "XMTRX", "FVn", "FVm", "FRn", "FRm", "FR0", "DRn", "Rn", "Rm", "R0", "#imm", "disp",
+    "label",
+];
+
+// arithmetic that works on double pairs when fpscr.pr is set
+const PR_SENSITIVE: [&str; 11] = [
+    "fabs", "fneg", "fadd", "fsub", "fmul", "fdiv", "fsqrt", "fcmp/eq", "fcmp/gt", "float", "ftrc",
+];
+
+pub fn disassemble(
+    instr: &DecodedInstruction,
+    pc: u32,
+    fpscr: u32,
+    bus: &CpuBus,
+    symbols: &SymbolTable,
+) -> String {
+    let opcode = instr.opcode;
+    let template = instr.disassembly;
+
+    if template == "unk" {
+        return format!(".word 0x{:04x}", { opcode.0 });
+    }
+
+    let (mnemonic, operands) = template.split_once(' ').unwrap_or((template, ""));
+    let double_moves = mnemonic.starts_with("fmov") && fpscr.check_bit(20);
+    let double_precision = PR_SENSITIVE.contains(&mnemonic) && fpscr.check_bit(19);
+
+    // fmov.s moves a pair when sz is set, the manual drops the suffix for those
+    let mut text = if double_moves {
+        "fmov".to_owned()
+    } else {
+        mnemonic.to_owned()
+    };
+
+    let mut comment = None;
+    let mut rest = operands;
+
+    if !operands.is_empty() {
+        text.push(' ');
+    }
+
+    while !rest.is_empty() {
+        let Some(token) = TOKENS.iter().find(|token| rest.starts_with(**token)) else {
+            let c = rest.chars().next().unwrap();
+            text.push(c.to_ascii_lowercase());
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+
+        rest = &rest[token.len()..];
+
+        match *token {
+            "Rm_BANK" => text.push_str(&format!("r{}_bank", opcode.m() & 7)),
+            "XMTRX" => text.push_str("xmtrx"),
+            "FVn" => text.push_str(&format!("fv{}", (opcode.n() >> 2) * 4)),
+            "FVm" => text.push_str(&format!("fv{}", (opcode.n() & 3) * 4)),
+            "FRn" | "FRm" => {
+                let n = if *token == "FRn" {
+                    opcode.n()
+                } else {
+                    opcode.m()
+                };
+
+                text.push_str(&match (double_moves, double_precision) {
+                    (true, _) if n & 1 == 1 => format!("xd{}", n & 0xe),
+                    (true, _) | (_, true) => format!("dr{}", n & 0xe),
+                    _ => format!("fr{}", n),
+                });
+            }
+            "FR0" => text.push_str("fr0"),
+            "DRn" => text.push_str(&format!("dr{}", opcode.n() & 0xe)),
+            "Rn" => text.push_str(&format!("r{}", opcode.n())),
+            "Rm" => text.push_str(&format!("r{}", opcode.m())),
+            "R0" => text.push_str("r0"),
+            "#imm" => {
+                // the logical ops zero extend, everything else sign extends
+                let imm = if ["mov", "add", "cmp/eq"].contains(&mnemonic) {
+                    opcode.d8() as u8 as i8 as i32
+                } else {
+                    opcode.d8() as i32
+                };
+
+                text.push_str(&format!("#{}", imm));
+            }
+            "disp" => {
+                let scale = match mnemonic {
+                    "mova" => 4,
+                    _ if mnemonic.ends_with(".l") => 4,
+                    _ if mnemonic.ends_with(".w") => 2,
+                    _ => 1,
+                };
+
+                if rest.starts_with(",PC") {
+                    let disp = opcode.d8() * scale;
+                    let base = if scale == 4 { pc & !3 } else { pc };
+                    let target = base.wrapping_add(4).wrapping_add(disp);
+
+                    comment = Some(literal(target, mnemonic, bus, symbols));
+                    text.push_str(&disp.to_string());
+                } else if rest.starts_with(",GBR") {
+                    text.push_str(&(opcode.d8() * scale).to_string());
+                } else {
+                    text.push_str(&(opcode.d4() as u32 * scale).to_string());
+                }
+            }
+            "label" => {
+                let disp = if mnemonic == "bra" || mnemonic == "bsr" {
+                    ((opcode.d12() << 4) as u16 as i16 >> 4) as i32
+                } else {
+                    opcode.d8() as u8 as i8 as i32
+                };
+
+                let target = pc.wrapping_add(4).wrapping_add((disp * 2) as u32);
+                text.push_str(&format!("0x{:08x}", target));
+
+                // branches land inside functions as often as at their start
+                if symbols.nearest(target).is_some() {
+                    comment = Some(format!("<{}>", symbols.symbolicate(target)));
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    match comment {
+        Some(comment) => format!("{} ; {}", text, comment),
+        None => text,
+    }
+}
+
+// where a pc relative operand points, and for loads what's there if it's somewhere we can look
+fn literal(addr: u32, mnemonic: &str, bus: &CpuBus, symbols: &SymbolTable) -> String {
+    let value = match mnemonic {
+        "mov.w" => bus.peek_16(addr).map(|value| value as i16 as i32 as u32),
+        "mov.l" => bus.peek_32(addr),
+        _ => None,
+    };
+
+    let mut comment = match value {
+        Some(value) => format!("0x{:08x}: 0x{:08x}", addr, value),
+        None => format!("0x{:08x}", addr),
+    };
+
+    // a loaded value is often a function or a global, otherwise the label might be worth knowing
+    if let Some(symbol) = value
+        .and_then(|value| symbols.get(value))
+        .or_else(|| symbols.get(addr))
+    {
+        comment.push_str(&format!(" <{}>", symbol));
+    }
+
+    comment
+}
@@ -1,6 +1,7 @@
 use super::bus::PhysicalAddress;
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Default, Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DmacRegisters {
     pub sar0: u32,
     pub dar0: u32,
@@ -21,7 +22,7 @@ pub struct DmacRegisters {
     pub dmaor: u32,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Dmac {
     pub registers: DmacRegisters,
 }
@@ -1,6 +1,8 @@
 use super::bus::PhysicalAddress;
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize)]
 pub struct IntcRegisters {
     pub icr: u16, // interrupt control register (??)
     pub ipra: u16,
@@ -9,7 +11,7 @@ pub struct IntcRegisters {
     pub interrupt_requests: u64,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum InterruptKind {
     // nmi + external interrupt lines
     NMI,
@@ -50,18 +52,21 @@ pub enum InterruptKind {
     SCI1_TEI,
     SCIF_ERI,
     SCIF_RXI,
+    SCIF_BRI,
     SCIF_TXI,
-    SCIF_TEI,
     ITI,
     RCMI,
     ROVI,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Intc {
     pub registers: IntcRegisters,
+    #[serde(with = "BigArray")]
     pub interrupt_levels: [u8; 41],
+    #[serde(with = "BigArray")]
     pub prioritized_interrupts: [InterruptKind; 41],
+    #[serde(with = "BigArray")]
     pub interrupt_map: [u8; 41],
 }
 
@@ -106,8 +111,8 @@ impl Intc {
                 InterruptKind::SCI1_TEI,
                 InterruptKind::SCIF_ERI,
                 InterruptKind::SCIF_RXI,
+                InterruptKind::SCIF_BRI,
                 InterruptKind::SCIF_TXI,
-                InterruptKind::SCIF_TEI,
                 InterruptKind::ITI,
                 InterruptKind::RCMI,
                 InterruptKind::ROVI,
@@ -151,8 +156,8 @@ impl Intc {
                 0, // SCI1_TEI
                 0, // SCIF_ERI
                 0, // SCIF_RXI
+                0, // SCIF_BRI
                 0, // SCIF_TXI
-                0, // SCIF_TEI
                 0, // ITI
                 0, // RCMI
                 0, // ROVI
@@ -192,6 +197,12 @@ impl Intc {
         self.interrupt_levels[InterruptKind::PRI as isize as usize] = (IPRA & 0xf) as u8;
         self.interrupt_levels[InterruptKind::CUI as isize as usize] = (IPRA & 0xf) as u8;
 
+        let scif_level = ((IPRC & 0xf0) >> 4) as u8;
+        self.interrupt_levels[InterruptKind::SCIF_ERI as isize as usize] = scif_level;
+        self.interrupt_levels[InterruptKind::SCIF_RXI as isize as usize] = scif_level;
+        self.interrupt_levels[InterruptKind::SCIF_BRI as isize as usize] = scif_level;
+        self.interrupt_levels[InterruptKind::SCIF_TXI as isize as usize] = scif_level;
+
         self.prioritized_interrupts.sort_by(|lhs, rhs| {
             if self.interrupt_levels[*lhs as usize] == self.interrupt_levels[*rhs as usize] {
                 (*lhs as isize).cmp(&(*rhs as isize))
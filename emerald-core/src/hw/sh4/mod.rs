@@ -1,18 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+use scif::ScifEventData;
+use tmu::TmuEventData;
+
 pub mod bsc;
 pub mod bus;
 pub mod ccn;
 pub mod cpg;
 pub mod cpu;
+pub mod debug;
 pub mod decoder;
+pub mod disasm;
 pub mod dmac;
 pub mod fpu;
 pub mod intc;
 pub mod rtc;
+pub mod scif;
+#[cfg(feature = "json_tests")]
+pub mod test_bus;
 pub mod tmu;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SH4EventData {
     RaiseIRL { irl_number: usize },
+    Tmu(TmuEventData),
+    Scif(ScifEventData),
 }
 
 // fixme: would be nice to have a module ot hang sh4 components off of so we can get them out of bus.rs
@@ -1,12 +1,14 @@
 // real time clock
 use super::bus::PhysicalAddress;
+use serde::{Deserialize, Serialize};
 
-#[derive(Default, Clone, Debug, Eq, PartialEq)]
+#[derive(Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct RtcRegisters {
     rmonar: u8,
     rcr1: u8,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Rtc {
     pub registers: RtcRegisters,
 }
@@ -0,0 +1,405 @@
+// serial communication interface with fifo (scif), the dreamcast's serial port.
+//
+// bytes go out and come in at the configured baud rate, one scheduled event per byte. the transmitter
+// hands what it sends to the host backend (see serial.rs), the receiver polls the backend once per byte
+// time while it's enabled and stops taking bytes when its fifo is full, so the host is never overrun.
+// tdfe, tend and rdf follow the fifo levels rather than being latched, software can only clear the rest.
+//
+// the interrupt lines are level triggered on the real chip. here they're raised when they become active,
+// and again whenever scscr2 or scfsr2 is written while they still are, which is when a handler would look.
+
+use std::cell::RefCell;
+
+use super::{
+    bus::PhysicalAddress,
+    intc::{Intc, InterruptKind},
+    tmu::PERIPHERAL_CLOCK_DIVIDER,
+    SH4EventData,
+};
+use crate::{
+    context::Context,
+    fifo::Fifo,
+    hw::extensions::BitManipulation,
+    scheduler::{EventHandle, ScheduledEvent, Scheduler},
+    serial::SerialBackend,
+    trace::{TraceCategory, Tracer},
+};
+use serde::{Deserialize, Serialize};
+
+// scsmr2 bits
+const SCSMR_CHR: usize = 6; // 7 bit characters
+const SCSMR_PE: usize = 5;
+const SCSMR_STOP: usize = 3; // 2 stop bits
+
+// scscr2 bits
+const SCSCR_TIE: usize = 7;
+const SCSCR_RIE: usize = 6;
+const SCSCR_TE: usize = 5;
+const SCSCR_RE: usize = 4;
+const SCSCR_REIE: usize = 3;
+
+// scfsr2 bits
+const SCFSR_ER: usize = 7;
+const SCFSR_TEND: usize = 6;
+const SCFSR_TDFE: usize = 5;
+const SCFSR_BRK: usize = 4;
+const SCFSR_RDF: usize = 1;
+const SCFSR_DR: usize = 0;
+
+// scfcr2 bits
+const SCFCR_TFRST: usize = 2;
+const SCFCR_RFRST: usize = 1;
+const SCFCR_LOOP: usize = 0;
+
+// sclsr2 bits
+const SCLSR_ORER: usize = 0;
+
+#[derive(Copy, Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ScifRegisters {
+    pub scsmr2: u16,
+    pub scbrr2: u8,
+    pub scscr2: u16,
+    pub scfsr2: u16, // er, brk, fer, per and dr. the rest are worked out on read, see status
+    pub scfcr2: u16,
+    pub scsptr2: u16,
+    pub sclsr2: u16,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ScifEventData {
+    Transmit, // the byte in the shift register is out
+    Receive,  // time to poll the host for the next byte
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Scif {
+    pub registers: ScifRegisters,
+    tx_fifo: Fifo<u8>,
+    rx_fifo: RefCell<Fifo<u8>>, // reading scfrdr2 pops it
+    shifting: Option<u8>,       // the byte being sent
+    transmit_event: Option<EventHandle>,
+    receive_event: Option<EventHandle>,
+
+    // the host side isn't part of the machine, it stays behind when a state is loaded
+    #[serde(skip)]
+    pub backend: SerialBackend,
+    #[serde(skip)]
+    line: String, // for the serial trace
+    #[serde(skip)]
+    pub(crate) captured: Option<Vec<u8>>, // every byte sent since the last drain, see Machine::capture_serial
+}
+
+impl Scif {
+    pub fn new() -> Self {
+        Self {
+            registers: ScifRegisters {
+                scbrr2: 0xff,
+                ..Default::default()
+            },
+            tx_fifo: Fifo::new(),
+            rx_fifo: RefCell::new(Fifo::new()),
+            shifting: None,
+            transmit_event: None,
+            receive_event: None,
+            backend: SerialBackend::None,
+            line: String::new(),
+            captured: None,
+        }
+    }
+
+    // sh4 cycles per character, start bit and all. a bit is 32 * 4^cks * (scbrr2 + 1) pφ cycles
+    fn byte_time(&self) -> u64 {
+        let scsmr2 = self.registers.scsmr2;
+        let bits = 1
+            + if scsmr2.check_bit(SCSMR_CHR) { 7 } else { 8 }
+            + scsmr2.check_bit(SCSMR_PE) as u64
+            + if scsmr2.check_bit(SCSMR_STOP) { 2 } else { 1 };
+
+        let cks = (scsmr2 & 0x3) as u64;
+        bits * PERIPHERAL_CLOCK_DIVIDER * (32 << (2 * cks)) * (self.registers.scbrr2 as u64 + 1)
+    }
+
+    fn tx_trigger(&self) -> usize {
+        [8, 4, 2, 1][((self.registers.scfcr2 >> 4) & 0x3) as usize]
+    }
+
+    fn rx_trigger(&self) -> usize {
+        [1, 4, 8, 14][((self.registers.scfcr2 >> 6) & 0x3) as usize]
+    }
+
+    pub fn status(&self) -> u16 {
+        let transmitting = self.registers.scscr2.check_bit(SCSCR_TE);
+        let tx_len = self.tx_fifo.len();
+
+        self.registers
+            .scfsr2
+            .eval_bit(
+                SCFSR_TEND,
+                !transmitting || (self.shifting.is_none() && tx_len == 0),
+            )
+            .eval_bit(SCFSR_TDFE, tx_len <= self.tx_trigger())
+            .eval_bit(SCFSR_RDF, self.rx_fifo.borrow().len() >= self.rx_trigger())
+    }
+
+    // the active interrupt lines, one bit each from SCIF_ERI in intc order
+    fn lines(&self) -> u8 {
+        let scscr2 = self.registers.scscr2;
+        let status = self.status();
+        let errors = scscr2.check_bit(SCSCR_RIE) || scscr2.check_bit(SCSCR_REIE);
+
+        [
+            errors && status.check_bit(SCFSR_ER),
+            scscr2.check_bit(SCSCR_RIE)
+                && (status.check_bit(SCFSR_RDF) || status.check_bit(SCFSR_DR)),
+            errors && (status.check_bit(SCFSR_BRK) || self.registers.sclsr2.check_bit(SCLSR_ORER)),
+            scscr2.check_bit(SCSCR_TIE) && status.check_bit(SCFSR_TDFE),
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |lines, (i, active)| lines | ((*active as u8) << i))
+    }
+
+    // raises whatever is active now and wasn't in before
+    fn raise_lines(&self, before: u8, intc: &mut Intc) {
+        let raised = self.lines() & !before;
+        for i in 0..4 {
+            if raised.check_bit(i) {
+                intc.raise_irl(InterruptKind::SCIF_ERI as usize + i);
+            }
+        }
+    }
+
+    fn receiving(&self) -> bool {
+        self.registers.scscr2.check_bit(SCSCR_RE)
+            && !self.registers.scfcr2.check_bit(SCFCR_LOOP)
+            && self.backend.can_read()
+    }
+
+    // loads the shift register from the fifo if the transmitter is idle
+    fn start_transmit(&mut self, scheduler: &mut Scheduler) {
+        if self.shifting.is_some() || !self.registers.scscr2.check_bit(SCSCR_TE) {
+            return;
+        }
+
+        let Some(byte) = self.tx_fifo.pop() else {
+            return;
+        };
+
+        self.shifting = Some(byte);
+        self.transmit_event = Some(scheduler.schedule(ScheduledEvent::SH4Event {
+            deadline: self.byte_time(),
+            event_data: SH4EventData::Scif(ScifEventData::Transmit),
+        }));
+    }
+
+    fn stop_transmit(&mut self, scheduler: &mut Scheduler) {
+        if let Some(handle) = self.transmit_event.take() {
+            scheduler.cancel(handle);
+        }
+
+        self.shifting = None;
+    }
+
+    // starts polling the host if the receiver wants bytes and isn't polling already. also needed after a
+    // state is loaded, the backend may be able to read when the one the state was saved with couldn't
+    pub fn start_receiving(&mut self, scheduler: &mut Scheduler) {
+        if self.receive_event.is_none() && self.receiving() {
+            self.receive_event = Some(scheduler.schedule(ScheduledEvent::SH4Event {
+                deadline: self.byte_time(),
+                event_data: SH4EventData::Scif(ScifEventData::Receive),
+            }));
+        }
+    }
+
+    fn stop_receiving(&mut self, scheduler: &mut Scheduler) {
+        if let Some(handle) = self.receive_event.take() {
+            scheduler.cancel(handle);
+        }
+    }
+
+    // a byte has left the shift register. in loopback it goes straight to the receiver instead of the host
+    fn send(&mut self, byte: u8, tracer: &mut Tracer) {
+        if self.registers.scfcr2.check_bit(SCFCR_LOOP) {
+            if self.registers.scscr2.check_bit(SCSCR_RE)
+                && self.rx_fifo.get_mut().push(byte).is_err()
+            {
+                self.registers.sclsr2 = self.registers.sclsr2.set_bit(SCLSR_ORER);
+            }
+
+            return;
+        }
+
+        self.backend.write(byte);
+
+        if let Some(captured) = &mut self.captured {
+            captured.push(byte);
+        }
+
+        if byte == b'\n' {
+            tracer.message(TraceCategory::Serial, std::mem::take(&mut self.line));
+        } else {
+            self.line.push(byte as char);
+        }
+    }
+
+    pub fn on_scheduled_event(
+        &mut self,
+        context: &mut Context,
+        intc: &mut Intc,
+        event_data: ScifEventData,
+    ) {
+        let before = self.lines();
+
+        match event_data {
+            ScifEventData::Transmit => {
+                self.transmit_event = None;
+
+                if let Some(byte) = self.shifting.take() {
+                    self.send(byte, &mut context.tracer);
+                }
+
+                self.start_transmit(&mut context.scheduler);
+            }
+            ScifEventData::Receive => {
+                self.receive_event = None;
+                if !self.receiving() {
+                    return;
+                }
+
+                let rx_trigger = self.rx_trigger();
+                let rx_fifo = self.rx_fifo.get_mut();
+
+                // a full fifo leaves the byte with the host until there's room
+                if !rx_fifo.is_full() {
+                    match self.backend.read() {
+                        Some(byte) => rx_fifo.push(byte).unwrap(),
+
+                        // the line went quiet with fewer bytes than the trigger waiting, dr tells software
+                        // to come and get them anyway
+                        None if !rx_fifo.is_empty() && rx_fifo.len() < rx_trigger => {
+                            self.registers.scfsr2 = self.registers.scfsr2.set_bit(SCFSR_DR);
+                        }
+                        None => {}
+                    }
+                }
+
+                self.start_receiving(&mut context.scheduler);
+            }
+        }
+
+        self.raise_lines(before, intc);
+    }
+
+    pub fn write_16(
+        &mut self,
+        addr: PhysicalAddress,
+        value: u16,
+        scheduler: &mut Scheduler,
+        intc: &mut Intc,
+    ) {
+        let before = self.lines();
+
+        match addr.0 {
+            0x1fe80000 => self.registers.scsmr2 = value, // takes effect from the next character
+            0x1fe80008 => {
+                self.registers.scscr2 = value;
+
+                if value.check_bit(SCSCR_TE) {
+                    self.start_transmit(scheduler);
+                } else {
+                    self.stop_transmit(scheduler);
+                }
+
+                if value.check_bit(SCSCR_RE) {
+                    self.start_receiving(scheduler);
+                } else {
+                    self.stop_receiving(scheduler);
+                }
+
+                return self.raise_lines(0, intc);
+            }
+            0x1fe80010 => {
+                // writing 0 clears a flag, 1 leaves it alone
+                self.registers.scfsr2 &= value;
+                return self.raise_lines(0, intc);
+            }
+            0x1fe80018 => {
+                self.registers.scfcr2 = value;
+
+                if value.check_bit(SCFCR_TFRST) {
+                    self.tx_fifo.clear();
+                }
+
+                if value.check_bit(SCFCR_RFRST) {
+                    self.rx_fifo.get_mut().clear();
+                }
+
+                if value.check_bit(SCFCR_LOOP) {
+                    self.stop_receiving(scheduler);
+                } else {
+                    self.start_receiving(scheduler);
+                }
+            }
+            0x1fe80020 => self.registers.scsptr2 = value,
+            0x1fe80024 => self.registers.sclsr2 &= value,
+            _ => println!(
+                "scif: unknown mmio write (16-bit) @ 0x{:08x} with value 0x{:04x}",
+                addr.0, value
+            ),
+        }
+
+        self.raise_lines(before, intc);
+    }
+
+    pub fn write_8(
+        &mut self,
+        addr: PhysicalAddress,
+        value: u8,
+        scheduler: &mut Scheduler,
+        intc: &mut Intc,
+    ) {
+        let before = self.lines();
+
+        match addr.0 {
+            0x1fe80004 => self.registers.scbrr2 = value,
+            0x1fe8000c => {
+                // a write to a full fifo is lost
+                let _ = self.tx_fifo.push(value);
+                self.start_transmit(scheduler);
+            }
+            _ => println!(
+                "scif: unknown mmio write (8-bit) @ 0x{:08x} with value 0x{:02x}",
+                addr.0, value
+            ),
+        }
+
+        self.raise_lines(before, intc);
+    }
+
+    pub fn read_16(&self, addr: PhysicalAddress) -> u16 {
+        match addr.0 {
+            0x1fe80000 => self.registers.scsmr2,
+            0x1fe80008 => self.registers.scscr2,
+            0x1fe80010 => self.status(),
+            0x1fe80018 => self.registers.scfcr2,
+            0x1fe8001c => ((self.tx_fifo.len() as u16) << 8) | self.rx_fifo.borrow().len() as u16,
+            0x1fe80020 => self.registers.scsptr2,
+            0x1fe80024 => self.registers.sclsr2,
+            _ => {
+                println!("scif: unknown mmio read (16-bit) @ 0x{:08x}", addr.0);
+                0
+            }
+        }
+    }
+
+    pub fn read_8(&self, addr: PhysicalAddress) -> u8 {
+        match addr.0 {
+            0x1fe80004 => self.registers.scbrr2,
+            0x1fe80014 => self.rx_fifo.borrow_mut().pop().unwrap_or(0),
+            _ => {
+                println!("scif: unknown mmio read (8-bit) @ 0x{:08x}", addr.0);
+                0
+            }
+        }
+    }
+}
@@ -0,0 +1,65 @@
+// a flat memory that stands in for a cpu's whole address space, for single instruction conformance tests.
+//
+// when CpuBus::test_bus is set every access the sh4 makes goes here instead, with no address translation,
+// devices, store queues or debugger in the way, and gets logged so the bus activity can be compared too.
+// Aica::test_bus does the same for the arm7 behind ArmBus. memory that was never written reads as zero.
+
+use std::{cell::RefCell, collections::HashMap};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum BusCycleKind {
+    Fetch,
+    Read,
+    Write,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BusCycle {
+    pub addr: u32,
+    pub value: u64,
+    pub size: u8, // in bits
+    pub kind: BusCycleKind,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TestBus {
+    pub memory: HashMap<u32, u8>,
+    pub cycles: RefCell<Vec<BusCycle>>,
+}
+
+impl TestBus {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn read(&self, addr: u32, size: u8, kind: BusCycleKind) -> u64 {
+        let mut value = 0;
+        for i in 0..size as u32 / 8 {
+            let byte = self.memory.get(&addr.wrapping_add(i)).copied().unwrap_or(0);
+            value |= (byte as u64) << (i * 8);
+        }
+
+        self.cycles.borrow_mut().push(BusCycle {
+            addr,
+            value,
+            size,
+            kind,
+        });
+
+        value
+    }
+
+    pub fn write(&mut self, addr: u32, size: u8, value: u64) {
+        for i in 0..size as u32 / 8 {
+            self.memory
+                .insert(addr.wrapping_add(i), (value >> (i * 8)) as u8);
+        }
+
+        self.cycles.get_mut().push(BusCycle {
+            addr,
+            value,
+            size,
+            kind: BusCycleKind::Write,
+        });
+    }
+}
@@ -1,165 +1,205 @@
 // timers
+//
+// counters aren't stepped. each running channel remembers the count and cycle it was last latched at,
+// tcnt is derived from the elapsed cycles when read, and the next underflow is a scheduled event.
+// anything that changes how a channel counts latches it first and reschedules after.
 
-use super::{bus::PhysicalAddress, intc::InterruptKind};
-use crate::{context::Context, hw::extensions::BitManipulation, scheduler::Scheduler};
+use super::{
+    bus::PhysicalAddress,
+    intc::{Intc, InterruptKind},
+    SH4EventData,
+};
+use crate::{
+    hw::extensions::BitManipulation,
+    machine::SH4_CLOCK,
+    scheduler::{EventHandle, ScheduledEvent, Scheduler},
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Default, Clone, Debug, Eq, PartialEq)]
+// sh4 cycles per peripheral clock (pφ) cycle
+pub(super) const PERIPHERAL_CLOCK_DIVIDER: u64 = 4;
+
+// the rtc output clock the timers can count instead of pφ
+const RTC_OUTPUT_CLOCK: u64 = 16384;
+
+// tcr bits
+const TCR_UNF: usize = 8;
+const TCR_UNIE: usize = 5;
+const TCR_ICPF: usize = 9; // channel 2 only
+
+#[derive(Copy, Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct TmuRegisters {
     tocr: u8,
     pub tstr: u8,
-    tcor0: u32,
-    tcor1: u32,
-    tcor2: u32,
-    tcnt0: u32,
-    tcnt1: u32,
-    tcnt2: u32,
-    tcr0: u16,
-    tcr1: u16,
-    tcr2: u16,
-
-    channel_0_cycles: u64,
-    channel_1_cycles: u64,
-    channel_2_cycles: u64,
+    tcor: [u32; 3],
+    tcr: [u16; 3],
+    tcpr2: u32, // tcnt2 as of the last input capture
 }
 
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Copy, Default, Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+struct TmuChannel {
+    base_count: u32, // tcnt as of base_cycle
+    base_cycle: u64, // always on a tick boundary while the channel runs
+    underflow_event: Option<EventHandle>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum TmuEventData {
     Sync { channel: usize }, // channel
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Tmu {
     pub registers: TmuRegisters,
+    channels: [TmuChannel; 3],
 }
 
 impl Tmu {
     pub fn new() -> Self {
         Self {
             registers: TmuRegisters {
-                tcr0: 0x2, // fixme: should be to load_elf
-                tcr1: 0x100,
+                tcr: [0x2, 0x100, 0], // fixme: tcr0 should be up to load_elf
                 ..Default::default()
             },
+            channels: Default::default(),
+        }
+    }
+
+    // sh4 cycles per count, none if the channel is clocked from something the dreamcast doesn't drive
+    fn period(&self, channel: usize) -> Option<u64> {
+        match self.registers.tcr[channel] & 0x7 {
+            tpsc @ 0..=4 => Some(PERIPHERAL_CLOCK_DIVIDER * (4_u64 << (2 * tpsc))),
+            6 => Some(SH4_CLOCK / RTC_OUTPUT_CLOCK),
+            _ => None, // reserved, or the external tclk pin
+        }
+    }
+
+    fn running(&self, channel: usize) -> Option<u64> {
+        if self.registers.tstr.check_bit(channel) {
+            self.period(channel)
+        } else {
+            None
+        }
+    }
+
+    // counts down to zero, reloads from tcor on the next count (that's the underflow), repeat
+    fn count_after(start: u32, tcor: u32, ticks: u64) -> u32 {
+        if ticks <= start as u64 {
+            start - ticks as u32
+        } else {
+            let reload = tcor as u64 + 1;
+            (tcor as u64 - (ticks - start as u64 - 1) % reload) as u32
+        }
+    }
+
+    pub fn tcnt(&self, channel: usize, now: u64) -> u32 {
+        let state = &self.channels[channel];
+
+        match self.running(channel) {
+            Some(period) => Self::count_after(
+                state.base_count,
+                self.registers.tcor[channel],
+                (now - state.base_cycle) / period,
+            ),
+            None => state.base_count,
+        }
+    }
+
+    // folds the elapsed counts into the base so the channel's clock or registers can change
+    fn latch(&mut self, channel: usize, now: u64) {
+        let count = self.tcnt(channel, now);
+        let running = self.running(channel);
+        let state = &mut self.channels[channel];
+
+        match running {
+            Some(period) => state.base_cycle += (now - state.base_cycle) / period * period,
+            None => state.base_cycle = now,
+        }
+
+        state.base_count = count;
+    }
+
+    fn reschedule(&mut self, channel: usize, scheduler: &mut Scheduler) {
+        if let Some(handle) = self.channels[channel].underflow_event.take() {
+            scheduler.cancel(handle);
         }
+
+        let Some(period) = self.running(channel) else {
+            return;
+        };
+
+        let state = &self.channels[channel];
+        let underflow_at = state.base_cycle + (state.base_count as u64 + 1) * period;
+
+        self.channels[channel].underflow_event =
+            Some(scheduler.schedule(ScheduledEvent::SH4Event {
+                deadline: underflow_at.saturating_sub(scheduler.now()),
+                event_data: SH4EventData::Tmu(TmuEventData::Sync { channel }),
+            }));
     }
 
     pub fn on_scheduled_event(
         &mut self,
         scheduler: &mut Scheduler,
-        r_ctrl: u32, // fixme: remove
-        target: u64,
-        overrun: u64,
+        intc: &mut Intc,
         event_data: TmuEventData,
     ) {
-        panic!("tmu: got scheduled event!");
-    }
+        match event_data {
+            TmuEventData::Sync { channel } => {
+                self.channels[channel].underflow_event = None;
+
+                let Some(period) = self.running(channel) else {
+                    return;
+                };
+
+                let state = &mut self.channels[channel];
+                state.base_cycle += (state.base_count as u64 + 1) * period;
+                state.base_count = self.registers.tcor[channel];
 
-    pub fn tick(&mut self, context: &mut Context) {
-        if self.registers.tstr.check_bit(0) {
-            self.registers.channel_0_cycles += 1;
-            let scale = match self.registers.tcr0 & 0x7 {
-                0 => 4,
-                1 => 16,
-                2 => 64,
-                3 => 256,
-                4 => 1024,
-                _ => panic!("wtf"),
-            };
-
-            while self.registers.channel_0_cycles >= scale {
-                self.registers.channel_0_cycles -= scale;
-                if self.registers.tcnt0 == 0 {
-                    self.registers.tcnt0 = self.registers.tcor0;
-
-                    // signal underflow
-                    self.registers.tcr0 = self.registers.tcr0.set_bit(8);
-
-                    if self.registers.tcr0.check_bit(5) {
-                        context
-                            .scheduler
-                            .schedule(crate::scheduler::ScheduledEvent::SH4Event {
-                                deadline: 200,
-                                event_data: crate::hw::sh4::SH4EventData::RaiseIRL {
-                                    irl_number: InterruptKind::TUNI0 as usize,
-                                },
-                            })
-                    }
-                } else {
-                    self.registers.tcnt0 = self.registers.tcnt0.wrapping_sub(1);
+                let tcr = &mut self.registers.tcr[channel];
+                *tcr = tcr.set_bit(TCR_UNF);
+
+                if tcr.check_bit(TCR_UNIE) {
+                    intc.raise_irl(InterruptKind::TUNI0 as usize + channel);
                 }
+
+                self.reschedule(channel, scheduler);
             }
         }
+    }
 
-        if self.registers.tstr.check_bit(1) {
-            self.registers.channel_1_cycles += 1;
-            let scale = match self.registers.tcr1 & 0x7 {
-                0 => 4,
-                1 => 16,
-                2 => 64,
-                3 => 256,
-                4 => 1024,
-                _ => unreachable!(),
-            };
-
-            while self.registers.channel_1_cycles >= scale {
-                self.registers.channel_1_cycles -= scale;
-                if self.registers.tcnt1 == 0 {
-                    self.registers.tcnt1 = self.registers.tcor1;
-
-                    // signal underflow
-                    self.registers.tcr1 = self.registers.tcr1.set_bit(8);
-                    if self.registers.tcr1.check_bit(5) {
-                        panic!("tmu: timer1 expected an interrupt");
-                    }
-                } else {
-                    self.registers.tcnt1 = self.registers.tcnt1.wrapping_sub(1);
-                }
-            }
+    // latches tcnt2 into tcpr2 as if tclk saw the configured edge. nothing on the dreamcast drives tclk,
+    // this is for hosts that want to pulse it, see Machine::pulse_tclk
+    pub fn input_capture(&mut self, now: u64, intc: &mut Intc) {
+        let icpe = (self.registers.tcr[2] >> 6) & 0x3;
+        if icpe & 0x2 == 0 {
+            return;
         }
 
-        if self.registers.tstr.check_bit(2) {
-            self.registers.channel_2_cycles += 1;
-            let scale = match self.registers.tcr2 & 0x7 {
-                0 => 4,
-                1 => 16,
-                2 => 64,
-                3 => 256,
-                4 => 1024,
-                _ => unreachable!(),
-            };
-
-            while self.registers.channel_2_cycles >= scale {
-                self.registers.channel_2_cycles -= scale;
-                if self.registers.tcnt2 == 0 {
-                    self.registers.tcnt2 = self.registers.tcor2;
-
-                    // signal underflow
-                    self.registers.tcr2 = self.registers.tcr2.set_bit(8);
-
-                    if self.registers.tcr2.check_bit(5) {
-                        context
-                            .scheduler
-                            .schedule(crate::scheduler::ScheduledEvent::SH4Event {
-                                deadline: 200,
-                                event_data: crate::hw::sh4::SH4EventData::RaiseIRL {
-                                    irl_number: InterruptKind::TUNI2 as usize,
-                                },
-                            })
-                    }
-                } else {
-                    self.registers.tcnt2 = self.registers.tcnt2.wrapping_sub(1);
-                }
-            }
+        self.registers.tcpr2 = self.tcnt(2, now);
+        self.registers.tcr[2] = self.registers.tcr[2].set_bit(TCR_ICPF);
+
+        if icpe == 0x3 {
+            intc.raise_irl(InterruptKind::TICPI2 as usize);
         }
     }
 
-    pub fn write_32(&mut self, addr: PhysicalAddress, value: u32) {
+    pub fn write_32(&mut self, addr: PhysicalAddress, value: u32, scheduler: &mut Scheduler) {
+        let now = scheduler.now();
         match addr.0 {
-            0x1fd80008 => self.registers.tcor0 = value,
-            0x1fd80014 => self.registers.tcor1 = value,
-            0x1fd80018 => self.registers.tcnt1 = value,
-            0x1fd80020 => self.registers.tcor2 = value,
-            0x1fd80024 => self.registers.tcnt2 = value,
-            0x1fd8000c => self.registers.tcnt0 = value,
+            0x1fd80008 | 0x1fd80014 | 0x1fd80020 => {
+                // a new tcor only applies from the next reload
+                let channel = ((addr.0 - 0x1fd80008) / 0xc) as usize;
+                self.latch(channel, now);
+                self.registers.tcor[channel] = value;
+                self.reschedule(channel, scheduler);
+            }
+            0x1fd8000c | 0x1fd80018 | 0x1fd80024 => {
+                let channel = ((addr.0 - 0x1fd8000c) / 0xc) as usize;
+                self.latch(channel, now);
+                self.channels[channel].base_count = value;
+                self.reschedule(channel, scheduler);
+            }
             _ => panic!(
                 "tmu: unknown mmio write (32-bit) @ 0x{:08x} with value 0x{:08x}",
                 addr.0, value
@@ -167,13 +207,18 @@ impl Tmu {
         }
     }
 
-    pub fn write_16(&mut self, addr: PhysicalAddress, value: u16) {
+    pub fn write_16(&mut self, addr: PhysicalAddress, value: u16, scheduler: &mut Scheduler) {
         match addr.0 {
-            0x1fd80010 => {
-                self.registers.tcr0 = value;
+            0x1fd80010 | 0x1fd8001c | 0x1fd80028 => {
+                let channel = ((addr.0 - 0x1fd80010) / 0xc) as usize;
+                self.latch(channel, scheduler.now());
+
+                // the status flags can only be cleared, writing 1 leaves them as they were
+                let flags = 0_u16.set_bit(TCR_UNF).set_bit(TCR_ICPF);
+                let tcr = self.registers.tcr[channel];
+                self.registers.tcr[channel] = (value & !flags) | (tcr & value & flags);
+                self.reschedule(channel, scheduler);
             }
-            0x1fd8001c => self.registers.tcr1 = value,
-            0x1fd80028 => self.registers.tcr2 = value,
             _ => panic!(
                 "tmu: unknown mmio write (16-bit) @ 0x{:08x} with value 0x{:08x}",
                 addr.0, value
@@ -181,10 +226,21 @@ impl Tmu {
         }
     }
 
-    pub fn write_8(&mut self, addr: PhysicalAddress, value: u8) {
+    pub fn write_8(&mut self, addr: PhysicalAddress, value: u8, scheduler: &mut Scheduler) {
         match addr.0 {
             0x1fd80000 => self.registers.tocr = value,
-            0x1fd80004 => self.registers.tstr = value,
+            0x1fd80004 => {
+                let now = scheduler.now();
+                for channel in 0..3 {
+                    self.latch(channel, now);
+                }
+
+                // stopped channels were latched at now, so anything starting counts from here
+                self.registers.tstr = value;
+                for channel in 0..3 {
+                    self.reschedule(channel, scheduler);
+                }
+            }
             _ => panic!(
                 "tmu: unknown mmio write (8-bit) @ 0x{:08x} with value 0x{:08x}",
                 addr.0, value
@@ -192,11 +248,15 @@ impl Tmu {
         }
     }
 
-    pub fn read_32(&self, addr: PhysicalAddress) -> u32 {
+    pub fn read_32(&self, addr: PhysicalAddress, now: u64) -> u32 {
         match addr.0 {
-            0x1fd8000c => self.registers.tcnt0,
-            0x1fd80018 => self.registers.tcnt1,
-            0x1fd80024 => self.registers.tcnt2,
+            0x1fd80008 => self.registers.tcor[0],
+            0x1fd80014 => self.registers.tcor[1],
+            0x1fd80020 => self.registers.tcor[2],
+            0x1fd8000c => self.tcnt(0, now),
+            0x1fd80018 => self.tcnt(1, now),
+            0x1fd80024 => self.tcnt(2, now),
+            0x1fd8002c => self.registers.tcpr2,
             _ => {
                 println!("tmu: unknown mmio read (32-bit) @ 0x{:08x}", addr.0);
                 0
@@ -206,9 +266,9 @@ impl Tmu {
 
     pub fn read_16(&self, addr: PhysicalAddress) -> u16 {
         match addr.0 {
-            0x1fd80010 => self.registers.tcr0,
-            0x1fd8001c => self.registers.tcr1,
-            0x1fd80028 => self.registers.tcr2,
+            0x1fd80010 => self.registers.tcr[0],
+            0x1fd8001c => self.registers.tcr[1],
+            0x1fd80028 => self.registers.tcr[2],
             _ => {
                 println!("tmu: unknown mmio read (16-bit) @ 0x{:08x}", addr.0);
                 0
@@ -218,9 +278,9 @@ impl Tmu {
 
     pub fn read_8(&self, addr: PhysicalAddress) -> u8 {
         match addr.0 {
+            0x1fd80000 => self.registers.tocr,
             0x1fd80004 => self.registers.tstr,
             _ => {
-                //#[cfg(feature = "log_io")]
                 println!("tmu: unknown mmio read (8-bit) @ 0x{:08x}", addr.0);
                 0
             }
@@ -0,0 +1,735 @@
+use std::path::Path;
+
+use crate::{
+    boot::{self, DiscBootError},
+    config::{ConfigError, EmulatorConfig},
+    context::Context,
+    dcload::{self, DcLoad},
+    emulator::{Emulator, EmulatorState},
+    error::EmulatorError,
+    hle_bios::HleBios,
+    hw::{
+        extensions::BitManipulation,
+        holly::{
+            g1::gdi::{GdiImage, GdiParser},
+            g2::aica::arm_bus::ArmBus,
+            HollyEventData,
+        },
+        sh4::{bus::CpuBus, cpu::Cpu, debug::DebugStop, SH4EventData},
+    },
+    movie::{MovieError, MovieInput, MoviePlayer, MovieRecorder},
+    scheduler::{ScheduledEvent, Scheduler},
+    serial::SerialBackend,
+    symbols::SymbolTable,
+    trace::Tracer,
+    ControllerButton, EmulatorFrontendRequest, EmulatorFrontendResponse,
+};
+
+// number of sh4 cycles the threaded run loop executes between checks for frontend requests
+pub const TIMESLICE: u64 = 448;
+
+// sh4 cycles consumed per stepped instruction
+pub const CPU_RATIO: u64 = 8;
+
+pub const SH4_CLOCK: u64 = 200_000_000;
+pub const AUDIO_SAMPLE_RATE: u64 = 44_100;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MachineEvent {
+    FrameReady(u32), // the ta finished a display list, needs to be rendered by the frontend
+    BlitFramebuffer(Vec<u8>, u32, u32),
+    VBlank,
+    Halted, // the machine isn't running, returned on every step until it is again
+    Error(EmulatorError), // the machine just halted on this
+    DebugStop(DebugStop), // a breakpoint or watchpoint on the bus paused the machine
+    Exited(i32), // the program exited through dcload or the bios menu, halted follows
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputTiming {
+    Immediate,    // controller input lands whenever the frontend sends it
+    FrameAligned, // controller input is held until the next vblank, so runs are reproducible
+}
+
+// owns a complete dreamcast and advances it synchronously on the caller's thread.
+// frontends that don't want the threaded run loop (tests, headless runners, other hosts) drive this directly.
+pub struct Machine {
+    pub config: EmulatorConfig,
+    pub cpu: Cpu,
+    pub bus: CpuBus,
+    pub context: Context,
+    pub state: EmulatorState,
+    pub total_cycles: u64,
+    pub frame_count: u64,
+    pub(crate) send_frame: bool,
+    pub(crate) blit_frame: bool,
+    pub(crate) dl_id: u32,
+    pub input_timing: InputTiming,
+    pub recorder: Option<MovieRecorder>,
+    pub player: Option<MoviePlayer>,
+    pub(crate) pending_inputs: Vec<EmulatorFrontendRequest>,
+    pub(crate) audio_frames: u64, // stereo frames handed out by drain_audio so far
+    pub dcload: Option<DcLoad>,   // host syscalls for homebrew, installed when direct booting
+    pub hle_bios: Option<HleBios>, // the bios's syscalls without the bios, installed the same way
+}
+
+impl Machine {
+    pub fn new() -> Self {
+        let mut bus = CpuBus::new();
+        let mut scheduler = Scheduler::new();
+
+        // initialize peripherals so they can schedule their initial events
+        bus.holly.init(&mut scheduler);
+
+        Self {
+            config: EmulatorConfig::default(),
+            cpu: Cpu::new(),
+            bus,
+            context: Context {
+                scheduler,
+                cyc: 0,
+                tracer: Tracer::new(),
+                error: None,
+            },
+            state: EmulatorState::Running,
+            total_cycles: 0,
+            frame_count: 0,
+            send_frame: false,
+            blit_frame: false,
+            dl_id: 0,
+            input_timing: InputTiming::Immediate,
+            recorder: None,
+            player: None,
+            pending_inputs: Vec::new(),
+            audio_frames: 0,
+            dcload: None,
+            hle_bios: None,
+        }
+    }
+
+    // builds a machine with the configured roms, disc and elf loaded
+    pub fn from_config(config: &EmulatorConfig) -> Result<Self, Vec<ConfigError>> {
+        let roms = config.load_roms()?;
+        let mut machine = Self::new();
+        machine.config = config.clone();
+
+        let boot_rom = &mut machine.bus.holly.g1_bus.boot_rom;
+        boot_rom.load(roms.bios, roms.flash);
+        boot_rom.region = config.region;
+        boot_rom.language = config.language;
+        machine.bus.bsc.cable_type = config.cable_type;
+
+        machine.bus.scif.backend = SerialBackend::open(&config.serial).map_err(|error| {
+            vec![ConfigError::Serial {
+                serial: config.serial.clone(),
+                error,
+            }]
+        })?;
+
+        machine
+            .context
+            .tracer
+            .configure(&config.trace)
+            .map_err(|error| {
+                vec![ConfigError::Trace {
+                    path: config.trace.output.clone().unwrap_or_default(),
+                    error,
+                }]
+            })?;
+
+        if config.hle_bios {
+            if config.elf_path.is_none() && config.disc_path.is_none() {
+                return Err(vec![ConfigError::HleBiosNeedsProgram]);
+            }
+
+            machine.hle_bios = Some(HleBios::new());
+        }
+
+        if config.fast_boot && config.disc_path.is_none() {
+            return Err(vec![ConfigError::FastBootNeedsDisc]);
+        }
+
+        if let Some(root) = &config.dcload_root {
+            if let Err(error) = std::fs::read_dir(root) {
+                return Err(vec![ConfigError::Io {
+                    path: root.clone(),
+                    error,
+                }]);
+            }
+
+            machine.dcload = Some(DcLoad::new(root.clone()));
+        }
+
+        if let (Some(dcload), Some(port)) = (&mut machine.dcload, config.dcload_gdb_port) {
+            dcload
+                .listen_for_gdb(port)
+                .map_err(|error| vec![ConfigError::Gdb { port, error }])?;
+        }
+
+        if let Some(disc_path) = &config.disc_path {
+            machine.load_disc(disc_path).map_err(|e| vec![e])?;
+
+            // an elf takes over from the disc's program anyway
+            if (config.fast_boot || config.hle_bios) && config.elf_path.is_none() {
+                machine.boot_disc().map_err(|error| {
+                    vec![ConfigError::DiscBoot {
+                        path: disc_path.clone(),
+                        error,
+                    }]
+                })?;
+            }
+        }
+
+        if let Some(elf_path) = &config.elf_path {
+            machine.load_elf(elf_path).map_err(|e| vec![e])?;
+        }
+
+        for path in &config.symbol_paths {
+            machine.load_symbols(path).map_err(|e| vec![e])?;
+        }
+
+        if config.frame_aligned_input {
+            machine.input_timing = InputTiming::FrameAligned;
+        }
+
+        let movie_error = |path: &Path, error| {
+            vec![ConfigError::Movie {
+                path: path.to_path_buf(),
+                error,
+            }]
+        };
+
+        // playback first, so recording while playing back captures the movie's rtc seed
+        if let Some(path) = &config.play_movie {
+            machine
+                .start_playback(path)
+                .map_err(|e| movie_error(path, e))?;
+        }
+
+        if let Some(path) = &config.record_movie {
+            machine
+                .start_recording(path)
+                .map_err(|e| movie_error(path, e))?;
+        }
+
+        Ok(machine)
+    }
+
+    pub fn insert_disc(&mut self, gdi_image: GdiImage) {
+        self.bus.holly.g1_bus.gd_rom.set_gdi(gdi_image);
+    }
+
+    pub fn load_disc(&mut self, disc_path: &Path) -> Result<(), ConfigError> {
+        if let Err(error) = std::fs::metadata(disc_path) {
+            return Err(ConfigError::Io {
+                path: disc_path.to_path_buf(),
+                error,
+            });
+        }
+
+        self.insert_disc(GdiParser::load_from_file(&disc_path.to_string_lossy()));
+        Ok(())
+    }
+
+    // starts the inserted disc's program without going through the bios's boot, see boot::load_disc
+    pub fn boot_disc(&mut self) -> Result<(), DiscBootError> {
+        let (cpu, context) = (&mut self.cpu, &mut self.context);
+        self.bus
+            .untracked(|bus| boot::load_disc(cpu, context, bus))?;
+
+        self.cpu.symbols = SymbolTable::new();
+        self.install_traps();
+        Ok(())
+    }
+
+    // replaces the bios and flash, validating them the same way from_config does
+    pub fn load_roms(&mut self, bios: Vec<u8>, flash: Vec<u8>) -> Result<(), Vec<ConfigError>> {
+        let mut errors = self.config.validate_bios(&self.config.bios_path, &bios);
+        errors.extend(self.config.validate_flash(&self.config.flash_path, &flash));
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        self.bus.holly.g1_bus.boot_rom.load(bios, flash);
+        Ok(())
+    }
+
+    pub fn load_elf(&mut self, elf_path: &Path) -> Result<(), ConfigError> {
+        let elf = std::fs::read(elf_path).map_err(|error| ConfigError::Io {
+            path: elf_path.to_path_buf(),
+            error,
+        })?;
+
+        self.load_elf_bytes(&elf, elf_path)
+    }
+
+    // source is only used to name the elf in errors. anything without an elf header is taken as a raw binary,
+    // see boot::load_binary
+    pub fn load_elf_bytes(&mut self, elf: &[u8], source: &Path) -> Result<(), ConfigError> {
+        let ip_bin = EmulatorConfig::read_optional(&self.config.ip_bin_path)?;
+
+        let (cpu, context) = (&mut self.cpu, &mut self.context);
+        let syms = self
+            .bus
+            .untracked(|bus| {
+                if elf.starts_with(b"\x7fELF") {
+                    Emulator::load_elf(elf, ip_bin.as_deref(), cpu, context, bus)
+                } else {
+                    boot::load_binary(elf, ip_bin.as_deref(), cpu, context, bus)
+                        .map(|_| SymbolTable::new())
+                }
+            })
+            .map_err(|_| ConfigError::InvalidElf(source.to_path_buf()))?;
+
+        self.cpu.symbols = syms;
+        self.install_traps();
+        Ok(())
+    }
+
+    // direct booting clears the bios's work area, which is where these go
+    fn install_traps(&mut self) {
+        let context = &mut self.context;
+
+        if let Some(hle_bios) = &self.hle_bios {
+            self.bus.untracked(|bus| hle_bios.install(bus, context));
+        }
+
+        if let Some(dcload) = &self.dcload {
+            self.bus.untracked(|bus| dcload.install(bus, context));
+        }
+    }
+
+    // adds to the elf's symbols rather than replacing them, see SymbolTable::load_map
+    pub fn load_symbols(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let text = std::fs::read_to_string(path).map_err(|error| ConfigError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+
+        match self.cpu.symbols.load_map(&text) {
+            0 => Err(ConfigError::NoSymbols(path.to_path_buf())),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn start_recording(&mut self, path: &Path) -> Result<(), MovieError> {
+        self.recorder = Some(MovieRecorder::create(
+            path,
+            self.bus.holly.aica.rtc.timestamp,
+        )?);
+        self.input_timing = InputTiming::FrameAligned;
+        Ok(())
+    }
+
+    pub fn start_playback(&mut self, path: &Path) -> Result<(), MovieError> {
+        let player = MoviePlayer::open(path)?;
+        self.bus.holly.aica.rtc.timestamp = player.rtc_timestamp;
+        self.player = Some(player);
+        self.input_timing = InputTiming::FrameAligned;
+        Ok(())
+    }
+
+    // executes a single sh4 instruction along with everything that runs in lockstep with it, then dispatches
+    // any scheduled events that came due and takes pending interrupts. events fire on the first instruction
+    // boundary at or after their deadline, so they're never more than one instruction late.
+    pub fn step_instruction(&mut self) -> Vec<MachineEvent> {
+        let mut events = Vec::new();
+
+        if self.state != EmulatorState::Running {
+            events.push(MachineEvent::Halted);
+            return events;
+        }
+
+        let pc = self.cpu.registers.current_pc;
+        if let Some(stop) = self.bus.debugger.check_breakpoint(pc) {
+            self.state = EmulatorState::Paused;
+            events.push(MachineEvent::DebugStop(stop));
+            return events;
+        }
+
+        // the call is served here, the rts at the entry point then returns to the guest
+        if pc & 0x1fffffff == dcload::ENTRY_POINT & 0x1fffffff {
+            if let Some(dcload) = &mut self.dcload {
+                if let Some(status) =
+                    dcload.syscall(&mut self.cpu, &mut self.bus, &mut self.context)
+                {
+                    self.exit(status, &mut events);
+                    return events;
+                }
+            }
+        }
+
+        // the same for the bios's syscalls
+        if let Some(hle_bios) = &mut self.hle_bios {
+            if let Some(status) =
+                hle_bios.syscall(pc, &mut self.cpu, &mut self.bus, &mut self.context)
+            {
+                self.exit(status, &mut events);
+                return events;
+            }
+        }
+
+        self.cpu
+            .step(&mut self.bus, &mut self.context, self.total_cycles);
+
+        // the instruction still finishes, we stop before the next one
+        let stop = self.bus.debugger.dispatch(pc, self.context.scheduler.now());
+
+        if let Some(error) = self.context.error.take() {
+            self.halt(error.at(pc, self.context.scheduler.now()), &mut events);
+            return events;
+        }
+
+        let mut arm7bus = ArmBus {
+            aica: &mut self.bus.holly.aica,
+        };
+
+        self.bus.holly.arm7tdmi.step(&mut arm7bus);
+
+        self.total_cycles += CPU_RATIO;
+        self.bus.holly.cyc += CPU_RATIO;
+        self.context.scheduler.add_cycles(CPU_RATIO);
+
+        // fixme: see if we can move this out
+        let gd_rom = &mut self.bus.holly.g1_bus.gd_rom;
+        if gd_rom.output_fifo.borrow().is_empty() {
+            // needed bc this transitions during a mutable read ....
+            if let Some(pending_state) = gd_rom.pending_state {
+                if let Err(error) = gd_rom.transition(&mut self.context, pending_state) {
+                    self.halt(error.at(pc, self.context.scheduler.now()), &mut events);
+                    return events;
+                }
+
+                gd_rom.pending_state = None;
+                gd_rom
+                    .registers
+                    .status
+                    .set(gd_rom.registers.status.get().clear_bit(3));
+            }
+        }
+
+        let scheduler = &self.context.scheduler;
+        if scheduler
+            .peek_deadline()
+            .is_some_and(|deadline| deadline <= scheduler.now())
+        {
+            if let Err(error) = self.dispatch_events(&mut events) {
+                let pc = self.cpu.registers.current_pc;
+                self.halt(error.at(pc, self.context.scheduler.now()), &mut events);
+                return events;
+            }
+        }
+
+        self.cpu
+            .process_interrupts(&mut self.bus, &mut self.context, self.total_cycles);
+
+        if let Some(stop) = stop {
+            self.state = EmulatorState::Paused;
+            events.push(MachineEvent::DebugStop(stop));
+        }
+
+        events
+    }
+
+    // stops the machine where it is. the frontend gets the error once, after that every step reports halted.
+    pub fn halt(&mut self, error: EmulatorError, events: &mut Vec<MachineEvent>) {
+        self.state = EmulatorState::Halted(error.clone());
+        self.context.tracer.flush();
+
+        if self.cpu.symbols.nearest(error.pc()).is_some() {
+            eprintln!("emerald: halted in {}", self.cpu.symbolicate(error.pc()));
+        }
+
+        events.push(MachineEvent::Error(error));
+        events.push(MachineEvent::Halted);
+    }
+
+    // the program is done, through dcload's exit or the bios menu. reported like a halt
+    pub fn exit(&mut self, status: i32, events: &mut Vec<MachineEvent>) {
+        self.state = EmulatorState::Exited(status);
+        self.context.tracer.flush();
+
+        events.push(MachineEvent::Exited(status));
+        events.push(MachineEvent::Halted);
+    }
+
+    pub fn run_cycles(&mut self, cycles: u64) -> Vec<MachineEvent> {
+        let mut events = Vec::new();
+        let target = self.total_cycles + cycles;
+
+        while self.total_cycles < target {
+            let step_events = self.step_instruction();
+            let halted = step_events.contains(&MachineEvent::Halted);
+            events.extend(step_events);
+
+            if halted {
+                break;
+            }
+        }
+
+        events
+    }
+
+    // runs until the next vblank (or until the machine halts)
+    pub fn run_frame(&mut self) -> Vec<MachineEvent> {
+        let mut events = Vec::new();
+
+        loop {
+            let step_events = self.step_instruction();
+            let done = step_events
+                .iter()
+                .any(|e| *e == MachineEvent::VBlank || *e == MachineEvent::Halted);
+            events.extend(step_events);
+
+            if done {
+                return events;
+            }
+        }
+    }
+
+    // appends interleaved stereo samples at AUDIO_SAMPLE_RATE for the emulated time since the last call.
+    // fixme: the aica doesn't mix its channels yet, so this is silence paced to emulated time
+    pub fn drain_audio(&mut self, out: &mut Vec<i16>) {
+        let due = self.total_cycles * AUDIO_SAMPLE_RATE / SH4_CLOCK;
+        let frames = due.saturating_sub(self.audio_frames);
+
+        out.resize(out.len() + 2 * frames as usize, 0);
+        self.audio_frames = due;
+    }
+
+    // an edge on the tmu's tclk pin, which only the host can provide. captures tcnt2 if tcr2 asks for it
+    pub fn pulse_tclk(&mut self) {
+        let now = self.context.scheduler.now();
+        self.bus.tmu.input_capture(now, &mut self.bus.intc);
+    }
+
+    // keeps what the guest writes to the scif, or to dcload's console, until drain_serial hands it out
+    pub fn capture_serial(&mut self) {
+        self.bus.scif.captured.get_or_insert_with(Vec::new);
+    }
+
+    pub fn drain_serial(&mut self, out: &mut Vec<u8>) {
+        if let Some(captured) = &mut self.bus.scif.captured {
+            out.append(captured);
+        }
+    }
+
+    // entry point for requests coming from a frontend. with frame aligned input, controller input
+    // is deferred to the next vblank and live input is dropped entirely while a movie plays back.
+    pub fn queue_input(&mut self, request: EmulatorFrontendRequest) {
+        if self.input_timing == InputTiming::Immediate {
+            self.handle_request(request);
+            return;
+        }
+
+        match request {
+            EmulatorFrontendRequest::ButtonPressed(_)
+            | EmulatorFrontendRequest::ButtonReleased(_) => {
+                if self.player.is_none() {
+                    self.pending_inputs.push(request);
+                }
+            }
+            // the machine signals end of render itself, see dispatch_events
+            EmulatorFrontendRequest::RenderingDone => {}
+            _ => self.handle_request(request),
+        }
+    }
+
+    pub fn handle_request(&mut self, request: EmulatorFrontendRequest) {
+        let maple = &mut self.bus.holly.maple;
+        match request {
+            EmulatorFrontendRequest::ButtonPressed(controller_button) => match controller_button {
+                ControllerButton::A => maple.is_a_pressed = true,
+                ControllerButton::B => maple.is_b_pressed = true,
+                ControllerButton::X => maple.is_x_pressed = true,
+                ControllerButton::Y => maple.is_y_pressed = true,
+                ControllerButton::Start => maple.is_start_pressed = true,
+                ControllerButton::Left => maple.is_left_pressed = true,
+                ControllerButton::Right => maple.is_right_pressed = true,
+                ControllerButton::Up => maple.is_up_pressed = true,
+                ControllerButton::Down => maple.is_down_pressed = true,
+            },
+            EmulatorFrontendRequest::ButtonReleased(controller_button) => match controller_button {
+                ControllerButton::A => maple.is_a_pressed = false,
+                ControllerButton::B => maple.is_b_pressed = false,
+                ControllerButton::X => maple.is_x_pressed = false,
+                ControllerButton::Y => maple.is_y_pressed = false,
+                ControllerButton::Start => maple.is_start_pressed = false,
+                ControllerButton::Left => maple.is_left_pressed = false,
+                ControllerButton::Right => maple.is_right_pressed = false,
+                ControllerButton::Up => maple.is_up_pressed = false,
+                ControllerButton::Down => maple.is_down_pressed = false,
+            },
+            EmulatorFrontendRequest::ToggleWireframe => {
+                self.bus.holly.pvr.wireframe = !self.bus.holly.pvr.wireframe;
+            }
+            EmulatorFrontendRequest::RenderingDone => self.raise_render_done(),
+            // a halted machine stays halted
+            EmulatorFrontendRequest::Pause if self.state == EmulatorState::Running => {
+                self.state = EmulatorState::Paused
+            }
+            EmulatorFrontendRequest::Resume if self.state == EmulatorState::Paused => {
+                self.state = EmulatorState::Running
+            }
+            _ => {}
+        }
+    }
+
+    fn raise_render_done(&mut self) {
+        // end of render: isp, tsp, and video end-of-render
+        for bit in [2, 1, 0] {
+            self.context.scheduler.schedule(ScheduledEvent::HollyEvent {
+                deadline: 0,
+                event_data: HollyEventData::RaiseInterruptNormal {
+                    istnrm: 0.set_bit(bit),
+                },
+            });
+        }
+    }
+
+    // applies whatever input is due on the vblank that just happened, logging it if we're recording
+    fn apply_frame_inputs(&mut self) {
+        let requests: Vec<EmulatorFrontendRequest> = match &mut self.player {
+            Some(player) => player
+                .inputs_for_frame(self.frame_count)
+                .into_iter()
+                .map(|input| input.request)
+                .collect(),
+            None => std::mem::take(&mut self.pending_inputs),
+        };
+
+        for request in requests {
+            if let Some(recorder) = &mut self.recorder {
+                let input = MovieInput {
+                    frame: self.frame_count,
+                    cycle: self.total_cycles,
+                    request,
+                };
+
+                if let Err(e) = recorder.record(&input) {
+                    println!("movie: stopped recording, {}", e);
+                    self.recorder = None;
+                }
+            }
+
+            self.handle_request(request);
+        }
+    }
+
+    // packages a machine event up for the frontends that render over a channel
+    pub fn frontend_response(&self, event: MachineEvent) -> Option<EmulatorFrontendResponse> {
+        match event {
+            MachineEvent::FrameReady(dl_id) => Some(EmulatorFrontendResponse::RenderHwRast(
+                dl_id,
+                self.bus.holly.pvr.texture_atlas.clone(),
+                self.bus.holly.pvr.vram.clone(),
+                self.bus.holly.pvr.pram.clone(),
+                self.bus.holly.pvr.dlb.clone(),
+                self.bus.holly.pvr.build_bg_verts(),
+            )),
+            MachineEvent::BlitFramebuffer(rgba, width, height) => Some(
+                EmulatorFrontendResponse::BlitFramebuffer(rgba, width, height),
+            ),
+            MachineEvent::Error(error) => Some(EmulatorFrontendResponse::Halted(error)),
+            _ => None,
+        }
+    }
+
+    // at this point, Reicast call UpdateSystem
+    fn dispatch_events(&mut self, events: &mut Vec<MachineEvent>) -> Result<(), EmulatorError> {
+        let bus = &mut self.bus;
+        let context = &mut self.context;
+
+        let mut vblank = false;
+        let mut frame_ready = false;
+
+        let now = context.scheduler.now();
+        while let Some(entry) = context.scheduler.tick() {
+            match entry.event {
+                ScheduledEvent::SH4Event { event_data, .. } => {
+                    // fixme: this processing should live somewhere? in cpu.rs? in mod.rs?
+                    match event_data {
+                        SH4EventData::RaiseIRL { irl_number } => {
+                            bus.intc.raise_irl(irl_number);
+                        }
+                        SH4EventData::Tmu(event_data) => {
+                            bus.tmu.on_scheduled_event(
+                                &mut context.scheduler,
+                                &mut bus.intc,
+                                event_data,
+                            );
+                        }
+                        SH4EventData::Scif(event_data) => {
+                            bus.scif
+                                .on_scheduled_event(context, &mut bus.intc, event_data);
+                        }
+                    }
+                }
+                ScheduledEvent::HollyEvent {
+                    event_data,
+                    deadline,
+                } => {
+                    if let HollyEventData::FrameReady(dl_id) = event_data {
+                        self.send_frame = true;
+                        self.dl_id = dl_id;
+                        frame_ready = true;
+                    }
+
+                    if let HollyEventData::VBlank = event_data {
+                        self.blit_frame = true;
+                        self.frame_count += 1;
+                        vblank = true;
+                        bus.holly.framebuffer.invalidate_watches();
+                        context.tracer.flush(); // keeps a trace file current to the frame
+                        events.push(MachineEvent::VBlank);
+                    }
+
+                    let mut dmac = bus.dmac;
+                    let target = deadline - entry.start;
+                    let overrun = (now - entry.start) - target;
+
+                    bus.holly.on_scheduled_event(
+                        context,
+                        &mut dmac,
+                        &mut bus.system_ram,
+                        target,
+                        overrun,
+                        event_data,
+                    )?;
+                }
+            }
+        }
+
+        if !self.send_frame
+            && bus.holly.framebuffer.dirty
+            && bus.holly.framebuffer.registers.read_ctrl.fb_enable
+            && self.blit_frame
+        {
+            bus.holly.framebuffer.dirty = false;
+            self.blit_frame = false;
+            let (rgba, width, height) = bus
+                .holly
+                .framebuffer
+                .render_framebuffer(&bus.holly.pvr.vram.read().unwrap())?;
+
+            events.push(MachineEvent::BlitFramebuffer(rgba, width, height));
+        } else if self.send_frame {
+            events.push(MachineEvent::FrameReady(self.dl_id));
+            self.send_frame = false;
+        }
+
+        if self.input_timing == InputTiming::FrameAligned {
+            // the frontend renders on its own schedule, so don't wait on it to say it's done
+            if frame_ready {
+                self.raise_render_done();
+            }
+
+            if vblank {
+                self.apply_frame_inputs();
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::EmulatorFrontendRequest;
+
+pub const MOVIE_MAGIC: [u8; 4] = *b"EMMV";
+pub const MOVIE_VERSION: u32 = 1;
+
+// a movie is the header followed by every applied input, in the order they were applied.
+// entries are appended as they happen so a movie survives the frontend exiting without warning.
+#[derive(Serialize, Deserialize)]
+struct MovieHeader {
+    magic: [u8; 4],
+    version: u32,
+    rtc_timestamp: u32, // the aica rtc is seeded from the host clock, playback has to start from the same second
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct MovieInput {
+    pub frame: u64, // the vblank this input was applied on
+    pub cycle: u64,
+    pub request: EmulatorFrontendRequest,
+}
+
+#[derive(Debug)]
+pub enum MovieError {
+    Io(io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    Encoding(bincode::Error),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MovieError::Io(e) => write!(f, "{}", e),
+            MovieError::BadMagic => write!(f, "not an emerald input movie"),
+            MovieError::UnsupportedVersion(version) => write!(
+                f,
+                "movie version {} is not supported (expected {})",
+                version, MOVIE_VERSION
+            ),
+            MovieError::Encoding(e) => write!(f, "movie is corrupt: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> Self {
+        MovieError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for MovieError {
+    fn from(e: bincode::Error) -> Self {
+        MovieError::Encoding(e)
+    }
+}
+
+pub struct MovieRecorder {
+    writer: BufWriter<File>,
+}
+
+impl MovieRecorder {
+    pub fn create<P: AsRef<Path>>(path: P, rtc_timestamp: u32) -> Result<Self, MovieError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        bincode::serialize_into(
+            &mut writer,
+            &MovieHeader {
+                magic: MOVIE_MAGIC,
+                version: MOVIE_VERSION,
+                rtc_timestamp,
+            },
+        )?;
+
+        writer.flush()?;
+        Ok(Self { writer })
+    }
+
+    pub fn record(&mut self, input: &MovieInput) -> Result<(), MovieError> {
+        bincode::serialize_into(&mut self.writer, input)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub struct MoviePlayer {
+    pub rtc_timestamp: u32,
+    pub inputs: Vec<MovieInput>,
+    cursor: usize,
+}
+
+impl MoviePlayer {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, MovieError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, MovieError> {
+        let mut reader = data;
+
+        let header: MovieHeader = bincode::deserialize_from(&mut reader)?;
+        if header.magic != MOVIE_MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+
+        if header.version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(header.version));
+        }
+
+        let mut inputs = Vec::new();
+        while !reader.is_empty() {
+            inputs.push(bincode::deserialize_from(&mut reader)?);
+        }
+
+        Ok(Self {
+            rtc_timestamp: header.rtc_timestamp,
+            inputs,
+            cursor: 0,
+        })
+    }
+
+    // everything recorded on this frame, in the order it was originally applied
+    pub fn inputs_for_frame(&mut self, frame: u64) -> Vec<MovieInput> {
+        let start = self.cursor;
+        while self.cursor < self.inputs.len() && self.inputs[self.cursor].frame <= frame {
+            self.cursor += 1;
+        }
+
+        self.inputs[start..self.cursor].to_vec()
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cursor == self.inputs.len()
+    }
+}
@@ -0,0 +1,350 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    hle_bios::HleBios,
+    hw::{
+        holly::{
+            g1::gdrom::Gdrom,
+            g2::aica::{arm, Aica},
+            maple::Maple,
+            pvr::{
+                display_list::DisplayListBuilder, framebuffer::Framebuffer, DrawingContext,
+                PvrRegisters,
+            },
+            sb::SystemBlock,
+            spg::Spg,
+            HollyRegisters,
+        },
+        sh4::{
+            bsc::Bsc,
+            ccn::Ccn,
+            cpg::Cpg,
+            cpu::{CpuRegisters, CpuState},
+            dmac::Dmac,
+            intc::Intc,
+            rtc::Rtc,
+            scif::Scif,
+            tmu::Tmu,
+        },
+    },
+    machine::Machine,
+    scheduler::Scheduler,
+};
+
+pub const SAVE_STATE_MAGIC: [u8; 4] = *b"EMSS";
+
+// bump this whenever anything serialized below changes shape
+pub const SAVE_STATE_VERSION: u32 = 8;
+
+// vram is allocated much larger than the 8mb the hardware has. only the part the bus and ch2-dma masks can reach is saved,
+// with trailing zeroes trimmed off
+const VRAM_STATE_WINDOW: usize = 0x4000000;
+
+// room for everything that isn't one of the memories: registers, the scheduler's queue, the gd-rom's output fifo and a
+// frame's worth of display lists
+const SAVE_STATE_OVERHEAD: usize = 0x1000000;
+
+#[derive(Debug)]
+pub enum SaveStateError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    Encoding(bincode::Error),
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::BadMagic => write!(f, "not an emerald save state"),
+            SaveStateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state version {} is not supported (expected {})",
+                version, SAVE_STATE_VERSION
+            ),
+            SaveStateError::Encoding(e) => write!(f, "save state is corrupt: {}", e),
+            SaveStateError::Corrupt(what) => write!(f, "save state is corrupt: bad {}", what),
+        }
+    }
+}
+
+impl std::error::Error for SaveStateError {}
+
+impl From<bincode::Error> for SaveStateError {
+    fn from(e: bincode::Error) -> Self {
+        SaveStateError::Encoding(e)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveStateHeader {
+    magic: [u8; 4],
+    version: u32,
+}
+
+// generates a borrowing struct for saving and an owning one for loading from a single field list,
+// so the two can't drift out of order
+macro_rules! save_state {
+    ($($field:ident: $ty:ty,)*) => {
+        #[derive(Serialize)]
+        struct SaveStateRef<'a> {
+            $($field: &'a $ty,)*
+        }
+
+        #[derive(Deserialize)]
+        struct SaveState {
+            $($field: $ty,)*
+        }
+    };
+}
+
+save_state! {
+    // machine
+    total_cycles: u64,
+    frame_count: u64,
+    send_frame: bool,
+    blit_frame: bool,
+    dl_id: u32,
+    context_cyc: u64,
+    scheduler: Scheduler,
+    hle_bios: Option<HleBios>,
+
+    // sh4
+    cpu_registers: CpuRegisters,
+    cpu_state: CpuState,
+    current_opcode: u16,
+    cpu_cyc: u64,
+    system_ram: Vec<u8>,
+    ccn: Ccn,
+    store_queues: [[u32; 8]; 2],
+    tmu: Tmu,
+    scif: Scif,
+    intc: Intc,
+    dmac: Dmac,
+    bsc: Bsc,
+    cpg: Cpg,
+    rtc: Rtc,
+    armsdt: u32,
+    basra: u8,
+    basrb: u8,
+    bara: u32,
+    barb: u32,
+    unk_val: u32,
+    unk_val1: u32,
+
+    // holly
+    holly_registers: HollyRegisters,
+    holly_cyc: u64,
+    spg: Spg,
+    sb: SystemBlock,
+    gd_rom: Gdrom,
+    maple: Maple,
+    framebuffer: Framebuffer,
+    pvr_registers: PvrRegisters,
+    parameter_buffer: [u32; 16],
+    parameter_cursor: usize,
+    drawing_context: DrawingContext,
+    display_lists: [DisplayListBuilder; 5],
+    aica: Aica,
+    arm7tdmi: arm::Cpu,
+}
+
+impl Machine {
+    pub fn save_state(&self) -> Result<Vec<u8>, SaveStateError> {
+        let bus = &self.bus;
+        let holly = &bus.holly;
+        let mut out = Vec::new();
+
+        bincode::serialize_into(
+            &mut out,
+            &SaveStateHeader {
+                magic: SAVE_STATE_MAGIC,
+                version: SAVE_STATE_VERSION,
+            },
+        )?;
+
+        bincode::serialize_into(
+            &mut out,
+            &SaveStateRef {
+                total_cycles: &self.total_cycles,
+                frame_count: &self.frame_count,
+                send_frame: &self.send_frame,
+                blit_frame: &self.blit_frame,
+                dl_id: &self.dl_id,
+                context_cyc: &self.context.cyc,
+                scheduler: &self.context.scheduler,
+                hle_bios: &self.hle_bios,
+
+                cpu_registers: &self.cpu.registers,
+                cpu_state: &self.cpu.state,
+                current_opcode: &self.cpu.current_opcode,
+                cpu_cyc: &self.cpu.cyc,
+                system_ram: &bus.system_ram,
+                ccn: &bus.ccn,
+                store_queues: &bus.store_queues,
+                tmu: &bus.tmu,
+                scif: &bus.scif,
+                intc: &bus.intc,
+                dmac: &bus.dmac,
+                bsc: &bus.bsc,
+                cpg: &bus.cpg,
+                rtc: &bus.rtc,
+                armsdt: &bus.armsdt,
+                basra: &bus.basra,
+                basrb: &bus.basrb,
+                bara: &bus.bara,
+                barb: &bus.barb,
+                unk_val: &bus.unk_val,
+                unk_val1: &bus.unk_val1,
+
+                holly_registers: &holly.registers,
+                holly_cyc: &holly.cyc,
+                spg: &holly.spg,
+                sb: &holly.sb,
+                gd_rom: &holly.g1_bus.gd_rom,
+                maple: &holly.maple,
+                framebuffer: &holly.framebuffer,
+                pvr_registers: &holly.pvr.registers,
+                parameter_buffer: &holly.pvr.parameter_buffer,
+                parameter_cursor: &holly.pvr.parameter_cursor,
+                drawing_context: &holly.pvr.context,
+                display_lists: &holly.pvr.dlb,
+                aica: &holly.aica,
+                arm7tdmi: &holly.arm7tdmi,
+            },
+        )?;
+
+        {
+            let vram = holly.pvr.vram.read().unwrap();
+            let window = &vram[..VRAM_STATE_WINDOW];
+            let len = window.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+            bincode::serialize_into(&mut out, &window[..len])?;
+        }
+
+        bincode::serialize_into(&mut out, &holly.pvr.pram.read().unwrap()[..])?;
+
+        Ok(out)
+    }
+
+    // no state this machine saves is larger, however much of vram the guest touches. for frontends like libretro
+    // that need one size up front
+    pub fn max_save_state_size(&self) -> usize {
+        let holly = &self.bus.holly;
+
+        self.bus.system_ram.len()
+            + VRAM_STATE_WINDOW
+            + holly.pvr.pram.read().unwrap().len()
+            + holly.aica.wave_ram.len()
+            + SAVE_STATE_OVERHEAD
+    }
+
+    // everything is decoded up front, so a bad state leaves the machine untouched
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), SaveStateError> {
+        let mut reader = data;
+
+        let header: SaveStateHeader = bincode::deserialize_from(&mut reader)?;
+        if header.magic != SAVE_STATE_MAGIC {
+            return Err(SaveStateError::BadMagic);
+        }
+
+        if header.version != SAVE_STATE_VERSION {
+            return Err(SaveStateError::UnsupportedVersion(header.version));
+        }
+
+        let state: SaveState = bincode::deserialize_from(&mut reader)?;
+        let vram: Vec<u8> = bincode::deserialize_from(&mut reader)?;
+        let pram: Vec<u8> = bincode::deserialize_from(&mut reader)?;
+
+        if vram.len() > VRAM_STATE_WINDOW {
+            return Err(SaveStateError::Corrupt("vram"));
+        }
+
+        if pram.len() != self.bus.holly.pvr.pram.read().unwrap().len() {
+            return Err(SaveStateError::Corrupt("pram"));
+        }
+
+        if state.system_ram.len() != self.bus.system_ram.len() {
+            return Err(SaveStateError::Corrupt("system ram"));
+        }
+
+        self.total_cycles = state.total_cycles;
+        self.frame_count = state.frame_count;
+        self.send_frame = state.send_frame;
+        self.blit_frame = state.blit_frame;
+        self.dl_id = state.dl_id;
+        self.context.cyc = state.context_cyc;
+        self.context.scheduler = state.scheduler;
+
+        // its traps are in the saved ram, so it comes and goes with the state
+        self.hle_bios = state.hle_bios;
+
+        self.cpu.registers = state.cpu_registers;
+        self.cpu.state = state.cpu_state;
+        self.cpu.current_opcode = state.current_opcode;
+        self.cpu.cyc = state.cpu_cyc;
+
+        let bus = &mut self.bus;
+        bus.system_ram = state.system_ram;
+        bus.ccn = state.ccn;
+        bus.store_queues = state.store_queues;
+        bus.tmu = state.tmu;
+        bus.intc = state.intc;
+        bus.dmac = state.dmac;
+        bus.bsc = state.bsc;
+        bus.cpg = state.cpg;
+        bus.rtc = state.rtc;
+        bus.armsdt = state.armsdt;
+        bus.basra = state.basra;
+        bus.basrb = state.basrb;
+        bus.bara = state.bara;
+        bus.barb = state.barb;
+        bus.unk_val = state.unk_val;
+        bus.unk_val1 = state.unk_val1;
+
+        // the host end of the serial port stays plugged in, like the disc below
+        let backend = std::mem::take(&mut bus.scif.backend);
+        let captured = bus.scif.captured.take();
+        bus.scif = state.scif;
+        bus.scif.backend = backend;
+        bus.scif.captured = captured;
+        bus.scif.start_receiving(&mut self.context.scheduler);
+
+        let holly = &mut bus.holly;
+        holly.registers = state.holly_registers;
+        holly.cyc = state.holly_cyc;
+        holly.spg = state.spg;
+        holly.sb = state.sb;
+        holly.maple = state.maple;
+        holly.framebuffer = state.framebuffer;
+        holly.aica = state.aica;
+        holly.arm7tdmi = state.arm7tdmi;
+
+        // the disc isn't part of the state, keep whatever is in the drive
+        let gdi_image = holly.g1_bus.gd_rom.gdi_image.take();
+        holly.g1_bus.gd_rom = state.gd_rom;
+        holly.g1_bus.gd_rom.gdi_image = gdi_image;
+
+        let pvr = &mut holly.pvr;
+        pvr.registers = state.pvr_registers;
+        pvr.parameter_buffer = state.parameter_buffer;
+        pvr.parameter_cursor = state.parameter_cursor;
+        pvr.context = state.drawing_context;
+        pvr.dlb = state.display_lists;
+
+        {
+            let mut pvr_vram = pvr.vram.write().unwrap();
+            pvr_vram[..VRAM_STATE_WINDOW].fill(0);
+            pvr_vram[..vram.len()].copy_from_slice(&vram);
+        }
+
+        pvr.pram.write().unwrap().copy_from_slice(&pram);
+
+        // every cached texture may be stale now
+        for texture in pvr.texture_atlas.write().unwrap().textures.values_mut() {
+            texture.dirty = true;
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,11 @@
 use crate::hw::{holly::HollyEventData, sh4::SH4EventData};
-use std::cmp::Ordering;
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BinaryHeap},
+};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub enum ScheduledEvent {
     HollyEvent {
         deadline: u64, // cycle in which the event will be consumed (not including overrun)
@@ -49,34 +53,51 @@ impl ScheduledEvent {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+// identifies a scheduled event so it can be cancelled or moved before it fires
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct EventHandle(u64);
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct SchedulerEntry {
     pub start: u64,
     pub event: ScheduledEvent,
+    pub handle: EventHandle,
+    seq: u64, // insertion order, breaks deadline ties and tells stale heap entries apart
 }
 
+// reversed so the binary heap pops the earliest deadline first, first scheduled first on ties
 impl Ord for SchedulerEntry {
     fn cmp(&self, other: &Self) -> Ordering {
-        other.event.deadline().cmp(&self.event.deadline())
+        other
+            .event
+            .deadline()
+            .cmp(&self.event.deadline())
+            .then_with(|| other.seq.cmp(&self.seq))
     }
 }
 
 impl PartialOrd for SchedulerEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
     }
 }
 
-#[derive(Debug)]
+// cancelling and rescheduling don't touch the heap. the live map tracks which entry is current for
+// each handle (and what it holds, for rescheduling) and anything else is dropped when it reaches the top.
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Scheduler {
-    pub events: Vec<SchedulerEntry>,
+    events: BinaryHeap<SchedulerEntry>,
+    live: BTreeMap<EventHandle, (u64, ScheduledEvent)>,
+    next_seq: u64,
     timestamp: u64,
 }
 
 impl Scheduler {
     pub fn new() -> Self {
         Self {
-            events: Vec::new(),
+            events: BinaryHeap::new(),
+            live: BTreeMap::new(),
+            next_seq: 0,
             timestamp: 0,
         }
     }
@@ -89,32 +110,85 @@ impl Scheduler {
         self.timestamp += cycles;
     }
 
-    pub fn schedule(&mut self, event: ScheduledEvent) {
-        let new_deadline = self.timestamp + event.deadline();
-        let new_event = event.with_updated_deadline(new_deadline);
-
-        // Check for duplicates
-        if !self
-            .events
-            .iter()
-            .any(|e| e.event.deadline() == new_event.deadline() && e.event == new_event)
-        {
-            self.events.push(SchedulerEntry {
-                start: self.now(),
-                event: new_event,
-            });
-            self.events.sort_by(|a, b| b.cmp(&a));
-        }
+    // the event's deadline is relative to now
+    pub fn schedule(&mut self, event: ScheduledEvent) -> EventHandle {
+        let handle = EventHandle(self.next_seq);
+        self.push(handle, event);
+        handle
+    }
+
+    // returns false if the event already fired or was cancelled
+    pub fn cancel(&mut self, handle: EventHandle) -> bool {
+        self.live.remove(&handle).is_some()
+    }
+
+    // moves a pending event to a new deadline (relative to now), keeping its handle.
+    // returns false if the event already fired or was cancelled
+    pub fn reschedule(&mut self, handle: EventHandle, new_deadline: u64) -> bool {
+        let Some((_, event)) = self.live.get(&handle) else {
+            return false;
+        };
+
+        let event = event.with_updated_deadline(new_deadline);
+        self.push(handle, event);
+        true
+    }
+
+    pub fn is_scheduled(&self, handle: EventHandle) -> bool {
+        self.live.contains_key(&handle)
+    }
+
+    // absolute cycle of the next pending event
+    pub fn next_deadline(&mut self) -> Option<u64> {
+        self.discard_stale();
+        self.events.peek().map(|e| e.event.deadline())
+    }
+
+    // like next_deadline, but without discarding cancelled entries so it may report one early.
+    // cheap enough to poll after every instruction
+    pub fn peek_deadline(&self) -> Option<u64> {
+        self.events.peek().map(|e| e.event.deadline())
+    }
+
+    // cycles until the next pending event, zero if it's already due
+    pub fn time_to_next_event(&mut self) -> Option<u64> {
+        self.next_deadline()
+            .map(|deadline| deadline.saturating_sub(self.timestamp))
     }
 
     pub fn tick(&mut self) -> Option<SchedulerEntry> {
-        if let Some(entry) = self.events.first() {
-            if entry.event.deadline() <= self.timestamp {
-                let entry = self.events.remove(0);
-                return Some(entry);
-            }
+        self.discard_stale();
+
+        if self.events.peek()?.event.deadline() > self.timestamp {
+            return None;
         }
 
-        None
+        let entry = self.events.pop()?;
+        self.live.remove(&entry.handle);
+        Some(entry)
+    }
+
+    fn push(&mut self, handle: EventHandle, event: ScheduledEvent) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let event = event.with_updated_deadline(self.timestamp + event.deadline());
+        self.live.insert(handle, (seq, event.clone()));
+        self.events.push(SchedulerEntry {
+            start: self.timestamp,
+            event,
+            handle,
+            seq,
+        });
+    }
+
+    fn discard_stale(&mut self) {
+        while let Some(entry) = self.events.peek() {
+            if matches!(self.live.get(&entry.handle), Some((seq, _)) if *seq == entry.seq) {
+                break;
+            }
+
+            self.events.pop();
+        }
     }
 }
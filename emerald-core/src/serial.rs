@@ -0,0 +1,228 @@
+// the host end of the sh4's scif, where the guest's serial port is plugged in.
+//
+// stdout and file only take what the guest sends. a pty shows up as a terminal device that screen, minicom or
+// dc-tool can open, and tcp listens on localhost for one client at a time, `nc 127.0.0.1 <port>` works. both
+// of those go in the other direction too, the scif polls them for bytes while its receiver is enabled.
+
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SerialConfig {
+    #[default]
+    None,
+    Stdout,
+    File(PathBuf),
+    Pty,
+    Tcp(u16),
+}
+
+// none, stdout, file:<path>, pty or tcp:<port>
+impl FromStr for SerialConfig {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(path) = s.strip_prefix("file:") {
+            return Ok(SerialConfig::File(PathBuf::from(path)));
+        }
+
+        if let Some(port) = s.strip_prefix("tcp:") {
+            return port.parse().map(SerialConfig::Tcp).map_err(|_| ());
+        }
+
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(SerialConfig::None),
+            "stdout" => Ok(SerialConfig::Stdout),
+            "pty" => Ok(SerialConfig::Pty),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for SerialConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerialConfig::None => write!(f, "none"),
+            SerialConfig::Stdout => write!(f, "stdout"),
+            SerialConfig::File(path) => write!(f, "file:{}", path.display()),
+            SerialConfig::Pty => write!(f, "pty"),
+            SerialConfig::Tcp(port) => write!(f, "tcp:{}", port),
+        }
+    }
+}
+
+#[derive(Default)]
+pub enum SerialBackend {
+    #[default]
+    None,
+    Stdout,
+    File(File),
+    Pty(File), // the master side, nonblocking
+    Tcp {
+        listener: TcpListener,
+        stream: Option<TcpStream>,
+    },
+}
+
+impl SerialBackend {
+    pub fn open(config: &SerialConfig) -> io::Result<Self> {
+        match config {
+            SerialConfig::None => Ok(SerialBackend::None),
+            SerialConfig::Stdout => Ok(SerialBackend::Stdout),
+            SerialConfig::File(path) => Ok(SerialBackend::File(File::create(path)?)),
+            SerialConfig::Pty => {
+                let (master, name) = open_pty()?;
+                println!("serial: pty at {}", name);
+                Ok(SerialBackend::Pty(master))
+            }
+            SerialConfig::Tcp(port) => {
+                let listener = TcpListener::bind(("127.0.0.1", *port))?;
+                listener.set_nonblocking(true)?;
+
+                println!(
+                    "serial: listening on port {}",
+                    listener.local_addr()?.port()
+                );
+
+                Ok(SerialBackend::Tcp {
+                    listener,
+                    stream: None,
+                })
+            }
+        }
+    }
+
+    // whether read can ever return anything, the scif doesn't bother polling otherwise
+    pub fn can_read(&self) -> bool {
+        matches!(self, SerialBackend::Pty(_) | SerialBackend::Tcp { .. })
+    }
+
+    // a byte the guest sent. there's no flow control towards the host, anything it can't take is dropped
+    pub fn write(&mut self, byte: u8) {
+        match self {
+            SerialBackend::None => {}
+            SerialBackend::Stdout => {
+                let mut stdout = io::stdout();
+                let _ = stdout.write_all(&[byte]).and_then(|_| stdout.flush());
+            }
+            SerialBackend::File(file) | SerialBackend::Pty(file) => {
+                let _ = file.write_all(&[byte]);
+            }
+            SerialBackend::Tcp { listener, stream } => {
+                Self::accept(listener, stream);
+                if let Some(client) = stream {
+                    if let Err(e) = client.write_all(&[byte]) {
+                        if e.kind() != io::ErrorKind::WouldBlock {
+                            Self::disconnect(stream);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // the next byte from the host, if it has sent one
+    pub fn read(&mut self) -> Option<u8> {
+        let mut byte = [0];
+
+        match self {
+            SerialBackend::Pty(master) => match master.read(&mut byte) {
+                Ok(1) => Some(byte[0]),
+                _ => None, // nothing yet, or no terminal has the slave side open
+            },
+            SerialBackend::Tcp { listener, stream } => {
+                Self::accept(listener, stream);
+                match stream.as_mut()?.read(&mut byte) {
+                    Ok(1) => Some(byte[0]),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => None,
+                    _ => {
+                        Self::disconnect(stream);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn accept(listener: &TcpListener, stream: &mut Option<TcpStream>) {
+        if stream.is_some() {
+            return;
+        }
+
+        if let Ok((client, addr)) = listener.accept() {
+            if client.set_nonblocking(true).is_ok() {
+                let _ = client.set_nodelay(true);
+                println!("serial: {} connected", addr);
+                *stream = Some(client);
+            }
+        }
+    }
+
+    fn disconnect(stream: &mut Option<TcpStream>) {
+        if stream.take().is_some() {
+            println!("serial: client disconnected");
+        }
+    }
+}
+
+// a raw, nonblocking pty master and the path of its slave
+#[cfg(unix)]
+fn open_pty() -> io::Result<(File, String)> {
+    use std::{ffi::CStr, os::fd::FromRawFd};
+
+    unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // owns the fd from here on, so it gets closed on the error paths too
+        let master = File::from_raw_fd(fd);
+
+        if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let name = libc::ptsname(fd);
+        if name.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+
+        // no echo or line editing, the guest sees exactly what the terminal sends
+        let mut termios = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(fd, &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        libc::cfmakeraw(&mut termios);
+        if libc::tcsetattr(fd, libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok((master, name))
+    }
+}
+
+#[cfg(not(unix))]
+fn open_pty() -> io::Result<(File, String)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "ptys are only available on unix hosts",
+    ))
+}
@@ -0,0 +1,235 @@
+// symbols and source lines for guest code, used by the disassembler, traces, halts and the gdb stub.
+//
+// everything is keyed by physical address (& 0x1fffffff) so p1/p2 mirrors find the same symbol. symbols
+// come from an elf's .symtab, `nm` output or a gnu ld .map file, lines from an elf's dwarf line tables.
+
+use std::collections::{BTreeMap, HashMap};
+
+use gimli::{EndianSlice, RunTimeEndian};
+use goblin::elf::{sym, Elf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub size: u32, // 0 when the source didn't say, then it covers everything up to the next symbol
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct LineEntry {
+    file: usize, // index into files
+    line: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SymbolTable {
+    symbols: BTreeMap<u32, Symbol>,
+    lines: BTreeMap<u32, Option<LineEntry>>, // None ends a sequence, the addresses after it have no line
+    files: Vec<String>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.symbols.is_empty() && self.lines.is_empty()
+    }
+
+    pub fn insert(&mut self, addr: u32, name: String, size: u32) {
+        let addr = addr & 0x1fffffff;
+
+        // an unsized alias shouldn't hide the size we already know
+        if size == 0 && self.symbols.get(&addr).is_some_and(|s| s.size != 0) {
+            return;
+        }
+
+        self.symbols.insert(addr, Symbol { name, size });
+    }
+
+    // only a symbol that starts exactly at addr
+    pub fn get(&self, addr: u32) -> Option<&str> {
+        self.symbols
+            .get(&(addr & 0x1fffffff))
+            .map(|symbol| symbol.name.as_str())
+    }
+
+    // where a symbol starts, as a physical address
+    pub fn find(&self, name: &str) -> Option<u32> {
+        self.symbols
+            .iter()
+            .find(|(_, symbol)| symbol.name == name)
+            .map(|(addr, _)| *addr)
+    }
+
+    // the symbol addr falls inside of and how far into it
+    pub fn nearest(&self, addr: u32) -> Option<(&str, u32)> {
+        let addr = addr & 0x1fffffff;
+        let (start, symbol) = self.symbols.range(..=addr).next_back()?;
+        let offset = addr - start;
+
+        if symbol.size != 0 && offset >= symbol.size {
+            return None;
+        }
+
+        Some((&symbol.name, offset))
+    }
+
+    pub fn source_line(&self, addr: u32) -> Option<(&str, u32)> {
+        let (_, entry) = self.lines.range(..=(addr & 0x1fffffff)).next_back()?;
+        entry.map(|entry| (self.files[entry.file].as_str(), entry.line))
+    }
+
+    // "func+0x1c", or the address when there's no symbol for it
+    pub fn symbolicate(&self, addr: u32) -> String {
+        match self.nearest(addr) {
+            Some((name, 0)) => name.to_owned(),
+            Some((name, offset)) => format!("{}+0x{:x}", name, offset),
+            None => format!("0x{:08x}", addr),
+        }
+    }
+
+    // symbolicate plus the source line when the dwarf has one, "func+0x1c (main.c:42)"
+    pub fn describe(&self, addr: u32) -> String {
+        match self.source_line(addr) {
+            Some((file, line)) => format!("{} ({}:{})", self.symbolicate(addr), file, line),
+            None => self.symbolicate(addr),
+        }
+    }
+
+    pub fn load_elf(&mut self, elf: &Elf, buffer: &[u8]) {
+        for symbol in &elf.syms {
+            // sections and files aren't things an address can be inside of
+            if matches!(symbol.st_type(), sym::STT_SECTION | sym::STT_FILE) || symbol.st_shndx == 0
+            {
+                continue;
+            }
+
+            if let Some(name) = elf.strtab.get_at(symbol.st_name).filter(|n| !n.is_empty()) {
+                self.insert(
+                    symbol.st_value as u32,
+                    name.to_owned(),
+                    symbol.st_size as u32,
+                );
+            }
+        }
+
+        // line info is a nice to have, a bad .debug_line shouldn't stop the elf from booting
+        let _ = self.load_dwarf(elf, buffer);
+    }
+
+    fn load_dwarf(&mut self, elf: &Elf, buffer: &[u8]) -> Result<(), gimli::Error> {
+        let endian = if elf.little_endian {
+            RunTimeEndian::Little
+        } else {
+            RunTimeEndian::Big
+        };
+
+        let dwarf = gimli::Dwarf::load(|id| -> Result<_, gimli::Error> {
+            let data = elf
+                .section_headers
+                .iter()
+                .find(|header| elf.shdr_strtab.get_at(header.sh_name) == Some(id.name()))
+                .and_then(|header| {
+                    buffer.get(
+                        header.sh_offset as usize..(header.sh_offset + header.sh_size) as usize,
+                    )
+                })
+                .unwrap_or(&[]);
+
+            Ok(EndianSlice::new(data, endian))
+        })?;
+
+        let mut interned: HashMap<String, usize> = HashMap::new();
+        let mut units = dwarf.units();
+
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row()? {
+                let addr = row.address() as u32 & 0x1fffffff;
+
+                if row.end_sequence() {
+                    self.lines.entry(addr).or_insert(None);
+                    continue;
+                }
+
+                let (Some(line), Some(file)) = (row.line(), row.file(header)) else {
+                    continue;
+                };
+
+                let mut path = dwarf
+                    .attr_string(&unit, file.path_name())?
+                    .to_string_lossy()
+                    .into_owned();
+
+                // directory 0 is the compilation directory, leave that off to keep things short
+                if file.directory_index() != 0 && !path.starts_with('/') {
+                    if let Some(directory) = file.directory(header) {
+                        let directory = dwarf.attr_string(&unit, directory)?;
+                        path = format!("{}/{}", directory.to_string_lossy(), path);
+                    }
+                }
+
+                let file = *interned.entry(path).or_insert_with_key(|path| {
+                    self.files.push(path.clone());
+                    self.files.len() - 1
+                });
+
+                self.lines.insert(
+                    addr,
+                    Some(LineEntry {
+                        file,
+                        line: line.get() as u32,
+                    }),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    // `nm` output (with or without -S) or a gnu ld map file, anything else in the file is skipped.
+    // returns how many symbols were found
+    pub fn load_map(&mut self, text: &str) -> usize {
+        let mut count = 0;
+
+        for (addr, size, name) in text.lines().filter_map(parse_map_line) {
+            self.insert(addr, name.to_owned(), size);
+            count += 1;
+        }
+
+        count
+    }
+}
+
+fn parse_map_line(line: &str) -> Option<(u32, u32, &str)> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    match words.as_slice() {
+        // nm: "8c010000 T _main", nm -S: "8c010000 00000040 T _main"
+        [addr, kind, name] if kind.len() == 1 && *kind != "U" => Some((parse_hex(addr)?, 0, name)),
+        [addr, size, kind, name] if kind.len() == 1 && *kind != "U" => {
+            Some((parse_hex(addr)?, parse_hex(size)?, name))
+        }
+        // ld: "                0x8c010000                _main"
+        [addr, name] if addr.starts_with("0x") && is_identifier(name) => {
+            Some((parse_hex(addr)?, 0, name))
+        }
+        _ => None,
+    }
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    u32::from_str_radix(s.strip_prefix("0x").unwrap_or(s), 16).ok()
+}
+
+fn is_identifier(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '$'))
+}
@@ -0,0 +1,260 @@
+// runtime tracing, replacing the old log_* cargo features.
+//
+// every subsystem has its own level and records only go out while the pc and cycle triggers allow it.
+// the output is one json object per line (a TraceRecord), so a trace can be read back by tools like
+// the trace differ rather than scraped.
+
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::{Range, RangeInclusive},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+pub enum TraceCategory {
+    Instructions, // info is the disassembly, verbose adds the registers
+    Memory,       // sh4 reads and writes, not instruction fetches
+    Interrupts,
+    Dma,
+    Gdrom,
+    Io,      // mmio the core ignores
+    Bios,    // well known bios routines being entered
+    Symbols, // functions from the loaded elf being entered
+    Serial,  // lines written to the scif
+}
+
+const CATEGORY_COUNT: usize = 9;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Serialize, Deserialize)]
+pub enum TraceLevel {
+    #[default]
+    Off,
+    Info,
+    Verbose,
+}
+
+impl FromStr for TraceCategory {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "instructions" | "instrs" => Ok(TraceCategory::Instructions),
+            "memory" | "mem" => Ok(TraceCategory::Memory),
+            "interrupts" | "ints" => Ok(TraceCategory::Interrupts),
+            "dma" => Ok(TraceCategory::Dma),
+            "gdrom" => Ok(TraceCategory::Gdrom),
+            "io" => Ok(TraceCategory::Io),
+            "bios" => Ok(TraceCategory::Bios),
+            "symbols" | "kos" => Ok(TraceCategory::Symbols),
+            "serial" => Ok(TraceCategory::Serial),
+            _ => Err(()),
+        }
+    }
+}
+
+impl FromStr for TraceLevel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(TraceLevel::Off),
+            "info" => Ok(TraceLevel::Info),
+            "verbose" => Ok(TraceLevel::Verbose),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraceConfig {
+    pub levels: BTreeMap<TraceCategory, TraceLevel>,
+
+    pub start_pc: Option<u32>, // nothing is traced until the sh4 first reaches this
+    pub pc_range: Option<RangeInclusive<u32>>, // only trace while the sh4 is executing in here
+    pub cycle_range: Option<Range<u64>>,
+
+    pub output: Option<PathBuf>, // stdout when not set
+}
+
+impl TraceConfig {
+    // "instructions=verbose,memory", a category alone means info
+    pub fn parse_levels(&mut self, s: &str) -> Result<(), ()> {
+        for entry in s.split(',').filter(|entry| !entry.is_empty()) {
+            let (category, level) = match entry.split_once('=') {
+                Some((category, level)) => (category.parse()?, level.parse()?),
+                None => (entry.parse()?, TraceLevel::Info),
+            };
+
+            self.levels.insert(category, level);
+        }
+
+        Ok(())
+    }
+}
+
+// r0-r15 as the current bank sees them, fr0-fr15 likewise
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sh4Registers {
+    pub r: [u32; 16],
+    pub fr: [u32; 16],
+    pub sr: u32,
+    pub fpscr: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event")]
+pub enum TraceEvent {
+    Instruction {
+        opcode: u16,
+        disassembly: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        source: Option<String>, // file:line from the elf's dwarf
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        registers: Option<Sh4Registers>,
+    },
+    Read {
+        addr: u32,
+        size: u8, // in bits
+        value: u64,
+    },
+    Write {
+        addr: u32,
+        size: u8,
+        value: u64,
+    },
+    Interrupt {
+        interrupt: String,
+        vector: u32,
+    },
+    Message {
+        text: String,
+    },
+}
+
+// pc and cycle are the sh4 instruction that was executing, or last executed for scheduled events
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub cycle: u64,
+    pub pc: u32,
+    pub category: TraceCategory,
+    #[serde(flatten)]
+    pub event: TraceEvent,
+}
+
+enum TraceOutput {
+    Stdout,
+    File(BufWriter<File>),
+}
+
+pub struct Tracer {
+    config: TraceConfig,
+    levels: [TraceLevel; CATEGORY_COUNT],
+    output: TraceOutput,
+    started: bool,
+    active: bool, // the triggers allow the current instruction to be traced
+    pc: u32,
+    cycle: u64,
+}
+
+impl Tracer {
+    pub fn new() -> Self {
+        Self {
+            config: TraceConfig::default(),
+            levels: [TraceLevel::Off; CATEGORY_COUNT],
+            output: TraceOutput::Stdout,
+            started: false,
+            active: false,
+            pc: 0,
+            cycle: 0,
+        }
+    }
+
+    pub fn configure(&mut self, config: &TraceConfig) -> io::Result<()> {
+        self.output = match &config.output {
+            Some(path) => TraceOutput::File(BufWriter::new(File::create(path)?)),
+            None => TraceOutput::Stdout,
+        };
+
+        self.levels = [TraceLevel::Off; CATEGORY_COUNT];
+        for (category, level) in &config.levels {
+            self.levels[*category as usize] = *level;
+        }
+
+        self.config = config.clone();
+        self.started = false;
+        self.active = false;
+        Ok(())
+    }
+
+    // the cpu calls this before every instruction, including delay slots
+    pub fn begin_instruction(&mut self, pc: u32, cycle: u64) {
+        if self.config.levels.is_empty() {
+            return;
+        }
+
+        self.pc = pc;
+        self.cycle = cycle;
+        self.started |= self.config.start_pc.map_or(true, |start| start == pc);
+        self.active = self.started
+            && self
+                .config
+                .pc_range
+                .as_ref()
+                .map_or(true, |range| range.contains(&pc))
+            && self
+                .config
+                .cycle_range
+                .as_ref()
+                .map_or(true, |range| range.contains(&cycle));
+    }
+
+    // off whenever the triggers say so, check this before building anything expensive
+    pub fn level(&self, category: TraceCategory) -> TraceLevel {
+        if self.active {
+            self.levels[category as usize]
+        } else {
+            TraceLevel::Off
+        }
+    }
+
+    pub fn enabled(&self, category: TraceCategory) -> bool {
+        self.level(category) != TraceLevel::Off
+    }
+
+    pub fn record(&mut self, category: TraceCategory, event: TraceEvent) {
+        if !self.enabled(category) {
+            return;
+        }
+
+        let record = TraceRecord {
+            cycle: self.cycle,
+            pc: self.pc,
+            category,
+            event,
+        };
+
+        let line = serde_json::to_string(&record).unwrap();
+
+        // a trace that can't be written isn't worth stopping the machine over
+        let _ = match &mut self.output {
+            TraceOutput::Stdout => writeln!(io::stdout(), "{}", line),
+            TraceOutput::File(file) => writeln!(file, "{}", line),
+        };
+    }
+
+    pub fn message(&mut self, category: TraceCategory, text: String) {
+        self.record(category, TraceEvent::Message { text });
+    }
+
+    pub fn flush(&mut self) {
+        if let TraceOutput::File(file) = &mut self.output {
+            let _ = file.flush();
+        }
+    }
+}
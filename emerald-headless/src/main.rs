@@ -0,0 +1,310 @@
+// runs a disc or elf with no window, for test programs on machines without a display.
+//
+// takes the usual emulator options (see config::USAGE) plus the ones below. it runs for --frames frames, or
+// stops as soon as the pc, symbol or serial text it was told to wait for shows up. serial output goes to
+// stdout as it happens, everything else to stderr. the final framebuffer can be saved as a png and its crc32
+// checked against --expect-hash.
+//
+// exits with 0 on a pass, 1 on a fail (halted, never saw what it was waiting for, wrong image) and 2 when it
+// couldn't start. a program run with --dcload can exit on its own, the status it passed is the exit code then.
+// 1 and 2 are ambiguous there, tests that care should stick to other codes.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use emerald_core::{
+    checksum::crc32,
+    config::{EmulatorConfig, USAGE},
+    error::EmulatorError,
+    machine::{Machine, MachineEvent},
+    serial::SerialConfig,
+    EmulatorFrontendRequest,
+};
+
+const HEADLESS_USAGE: &str = "headless options:
+  --frames <count> (defaults to 600, the limit when waiting for something below)
+  --until-pc <hex address>
+  --until-symbol <name>
+  --until-serial <text>
+  --screenshot <path.png>
+  --expect-hash <hex crc32 of the final framebuffer>";
+
+struct HeadlessConfig {
+    frames: u64,
+    until_pc: Option<u32>,
+    until_symbol: Option<String>,
+    until_serial: Option<String>,
+    screenshot: Option<PathBuf>,
+    expect_hash: Option<u32>,
+}
+
+enum Stop {
+    Frames,
+    Pc(u32),
+    Serial,
+    Halted(EmulatorError),
+    Exited(i32),
+}
+
+fn usage(message: String) -> String {
+    format!("{}\n{}\n{}", message, USAGE, HEADLESS_USAGE)
+}
+
+// pulls out the headless options and leaves the rest for EmulatorConfig::from_args
+fn parse_args(args: Vec<String>) -> Result<(HeadlessConfig, Vec<String>), String> {
+    let mut config = HeadlessConfig {
+        frames: 600,
+        until_pc: None,
+        until_symbol: None,
+        until_serial: None,
+        screenshot: None,
+        expect_hash: None,
+    };
+
+    let mut rest = Vec::new();
+    let mut iter = args.into_iter();
+
+    while let Some(argument) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| usage(format!("{} needs a value", argument)))
+        };
+
+        let hex = |v: String| {
+            u32::from_str_radix(v.trim_start_matches("0x"), 16)
+                .map_err(|_| usage(format!("invalid value {} for {}", v, argument)))
+        };
+
+        match argument.as_str() {
+            "--frames" => {
+                let v = value()?;
+                config.frames = v
+                    .parse()
+                    .map_err(|_| usage(format!("invalid value {} for {}", v, argument)))?;
+            }
+            "--until-pc" => config.until_pc = Some(hex(value()?)?),
+            "--until-symbol" => config.until_symbol = Some(value()?),
+            "--until-serial" => config.until_serial = Some(value()?),
+            "--screenshot" => config.screenshot = Some(value()?.into()),
+            "--expect-hash" => config.expect_hash = Some(hex(value()?)?),
+            _ => rest.push(argument),
+        }
+    }
+
+    Ok((config, rest))
+}
+
+pub fn main() -> ExitCode {
+    match run() {
+        Ok(status) => ExitCode::from(status),
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::from(2)
+        }
+    }
+}
+
+// the exit code, see the top of the file
+fn run() -> Result<u8, String> {
+    let (headless, args) = parse_args(std::env::args().skip(1).collect())?;
+    let config = EmulatorConfig::from_args(args).map_err(|e| usage(e.to_string()))?;
+
+    let mut machine = Machine::from_config(&config).map_err(|errors| {
+        errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    })?;
+
+    // kos symbols carry the compiler's leading underscore, accept the name either way
+    let until_pc = match &headless.until_symbol {
+        Some(name) => Some(
+            machine
+                .cpu
+                .symbols
+                .find(name)
+                .or_else(|| machine.cpu.symbols.find(&format!("_{}", name)))
+                .ok_or_else(|| format!("no symbol named {}", name))?,
+        ),
+        None => headless.until_pc.map(|pc| pc & 0x1fffffff),
+    };
+
+    let needle = headless.until_serial.as_deref().map(str::as_bytes);
+    let waiting = until_pc.is_some() || needle.is_some();
+
+    machine.capture_serial();
+
+    // --serial stdout already prints it as it goes out
+    let echo_serial = machine.config.serial != SerialConfig::Stdout;
+
+    let mut serial = Vec::new();
+    let mut pending = Vec::new();
+    let mut frames = 0;
+    let mut stdout = io::stdout();
+
+    let stop = 'run: loop {
+        for event in machine.step_instruction() {
+            match event {
+                MachineEvent::VBlank => frames += 1,
+                // there's no rasterizer here, let the guest carry on as if the frame was drawn
+                MachineEvent::FrameReady(_) => {
+                    machine.queue_input(EmulatorFrontendRequest::RenderingDone)
+                }
+                MachineEvent::Error(error) => break 'run Stop::Halted(error),
+                MachineEvent::Exited(status) => break 'run Stop::Exited(status),
+                _ => {}
+            }
+        }
+
+        machine.drain_serial(&mut pending);
+        if !pending.is_empty() {
+            if echo_serial {
+                let _ = stdout.write_all(&pending);
+            }
+
+            let new = pending.len();
+            serial.append(&mut pending);
+
+            // only the new bytes and whatever they could complete need searching
+            if let Some(needle) = needle {
+                let start = serial.len().saturating_sub(new + needle.len());
+                if needle.is_empty() || serial[start..].windows(needle.len()).any(|w| w == needle) {
+                    break Stop::Serial;
+                }
+            }
+        }
+
+        let pc = machine.cpu.registers.current_pc;
+        if until_pc == Some(pc & 0x1fffffff) {
+            break Stop::Pc(pc);
+        }
+
+        if frames >= headless.frames {
+            break Stop::Frames;
+        }
+    };
+
+    let _ = stdout.flush();
+
+    let mut status = match stop {
+        Stop::Halted(error) => {
+            eprintln!("emerald-headless: halted, {}", error);
+            1
+        }
+        Stop::Frames if waiting => {
+            eprintln!("emerald-headless: gave up after {} frames", frames);
+            1
+        }
+        Stop::Frames => {
+            eprintln!("emerald-headless: ran {} frames", frames);
+            0
+        }
+        // whatever the guest's code is, it's the low byte the host shell would see
+        Stop::Exited(status) => {
+            eprintln!(
+                "emerald-headless: exited with status {} after {} frames",
+                status, frames
+            );
+            status as u8
+        }
+        Stop::Pc(pc) => {
+            eprintln!(
+                "emerald-headless: reached {} after {} frames",
+                machine.cpu.symbolicate(pc),
+                frames
+            );
+            0
+        }
+        Stop::Serial => {
+            eprintln!(
+                "emerald-headless: saw the serial text after {} frames",
+                frames
+            );
+            0
+        }
+    };
+
+    if headless.screenshot.is_none() && headless.expect_hash.is_none() {
+        return Ok(status);
+    }
+
+    let framebuffer = {
+        let vram = machine.bus.holly.pvr.vram.read().unwrap();
+        machine.bus.holly.framebuffer.render_framebuffer(&vram)
+    };
+
+    let (rgba, width, height) = match framebuffer {
+        Ok(framebuffer) => framebuffer,
+        Err(error) => {
+            eprintln!(
+                "emerald-headless: couldn't render the framebuffer, {}",
+                error
+            );
+            return Ok(status.max(1));
+        }
+    };
+
+    let hash = crc32(&rgba);
+    eprintln!(
+        "emerald-headless: framebuffer {}x{}, crc32 {:08x}",
+        width, height, hash
+    );
+
+    if let Some(expected) = headless.expect_hash {
+        if hash != expected {
+            eprintln!("emerald-headless: expected crc32 {:08x}", expected);
+            status = status.max(1);
+        }
+    }
+
+    if let Some(path) = &headless.screenshot {
+        if width == 0 || height == 0 {
+            eprintln!("emerald-headless: the framebuffer is off, no screenshot");
+        } else {
+            write_png(path, &rgba, width, height)
+                .map_err(|e| format!("couldn't write {}: {}", path.display(), e))?;
+        }
+    }
+
+    Ok(status)
+}
+
+// 8-bit rgba, no filtering. the images are small and only need to be looked at
+fn write_png(path: &Path, rgba: &[u8], width: u32, height: u32) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]); // depth, color type (rgba), compression, filter, interlace
+    write_chunk(&mut file, b"IHDR", &header)?;
+
+    let stride = width as usize * 4;
+    let mut scanlines = Vec::with_capacity((stride + 1) * height as usize);
+    for row in rgba.chunks_exact(stride) {
+        scanlines.push(0); // filter type none
+        scanlines.extend_from_slice(row);
+    }
+
+    let compressed = miniz_oxide::deflate::compress_to_vec_zlib(&scanlines, 6);
+    write_chunk(&mut file, b"IDAT", &compressed)?;
+    write_chunk(&mut file, b"IEND", &[])?;
+
+    file.flush()
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc_input = Vec::with_capacity(4 + data.len());
+    crc_input.extend_from_slice(kind);
+    crc_input.extend_from_slice(data);
+
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(&crc_input)?;
+    out.write_all(&crc32(&crc_input).to_be_bytes())
+}
@@ -0,0 +1,470 @@
+// libretro core on top of emerald_core::machine::Machine. frontends drive it one frame at a time
+// through retro_run, so the threaded run loop isn't used here.
+
+#![allow(clippy::missing_safety_doc)]
+
+mod libretro;
+
+use std::{
+    ffi::{c_char, c_uint, c_void, CStr},
+    path::PathBuf,
+    ptr, slice,
+    sync::Mutex,
+};
+
+use emerald_core::{
+    config::EmulatorConfig,
+    machine::{Machine, MachineEvent, AUDIO_SAMPLE_RATE},
+    ControllerButton, EmulatorFrontendRequest,
+};
+
+use libretro::*;
+
+const DEFAULT_WIDTH: u32 = 640;
+const DEFAULT_HEIGHT: u32 = 480;
+
+// positional mapping, the dreamcast's a/b/x/y sit where the retropad's b/a/y/x do
+const BUTTON_MAP: [(c_uint, ControllerButton); 9] = [
+    (RETRO_DEVICE_ID_JOYPAD_B, ControllerButton::A),
+    (RETRO_DEVICE_ID_JOYPAD_A, ControllerButton::B),
+    (RETRO_DEVICE_ID_JOYPAD_Y, ControllerButton::X),
+    (RETRO_DEVICE_ID_JOYPAD_X, ControllerButton::Y),
+    (RETRO_DEVICE_ID_JOYPAD_START, ControllerButton::Start),
+    (RETRO_DEVICE_ID_JOYPAD_UP, ControllerButton::Up),
+    (RETRO_DEVICE_ID_JOYPAD_DOWN, ControllerButton::Down),
+    (RETRO_DEVICE_ID_JOYPAD_LEFT, ControllerButton::Left),
+    (RETRO_DEVICE_ID_JOYPAD_RIGHT, ControllerButton::Right),
+];
+
+struct Core {
+    machine: Option<Machine>,
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+    pressed: [bool; BUTTON_MAP.len()],
+    frame: Vec<u32>, // xrgb8888
+    frame_width: u32,
+    frame_height: u32,
+    audio: Vec<i16>,
+}
+
+static CORE: Mutex<Core> = Mutex::new(Core {
+    machine: None,
+    environment: None,
+    video_refresh: None,
+    audio_sample_batch: None,
+    input_poll: None,
+    input_state: None,
+    pressed: [false; BUTTON_MAP.len()],
+    frame: Vec::new(),
+    frame_width: 0,
+    frame_height: 0,
+    audio: Vec::new(),
+});
+
+fn core() -> std::sync::MutexGuard<'static, Core> {
+    CORE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl Core {
+    fn clear_frame(&mut self) {
+        self.frame_width = DEFAULT_WIDTH;
+        self.frame_height = DEFAULT_HEIGHT;
+        self.frame = vec![0; (DEFAULT_WIDTH * DEFAULT_HEIGHT) as usize];
+    }
+
+    fn poll_input(&mut self) {
+        let (Some(input_poll), Some(input_state)) = (self.input_poll, self.input_state) else {
+            return;
+        };
+
+        unsafe { input_poll() };
+
+        let Some(machine) = &mut self.machine else {
+            return;
+        };
+
+        for (i, (id, button)) in BUTTON_MAP.iter().enumerate() {
+            let pressed = unsafe { input_state(0, RETRO_DEVICE_JOYPAD, 0, *id) } != 0;
+            if pressed == self.pressed[i] {
+                continue;
+            }
+
+            self.pressed[i] = pressed;
+            machine.queue_input(if pressed {
+                EmulatorFrontendRequest::ButtonPressed(*button)
+            } else {
+                EmulatorFrontendRequest::ButtonReleased(*button)
+            });
+        }
+    }
+
+    fn system_directory(&self) -> Option<PathBuf> {
+        let environment = self.environment?;
+        let mut dir: *const c_char = ptr::null();
+
+        let ok = unsafe {
+            environment(
+                RETRO_ENVIRONMENT_GET_SYSTEM_DIRECTORY,
+                &mut dir as *mut *const c_char as *mut c_void,
+            )
+        };
+
+        if !ok || dir.is_null() {
+            return None;
+        }
+
+        unsafe { CStr::from_ptr(dir) }
+            .to_str()
+            .ok()
+            .map(PathBuf::from)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(cb: RetroEnvironment) {
+    core().environment = Some(cb);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(cb: RetroVideoRefresh) {
+    core().video_refresh = Some(cb);
+}
+
+// everything goes through the batch callback
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_cb: RetroAudioSample) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(cb: RetroAudioSampleBatch) {
+    core().audio_sample_batch = Some(cb);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(cb: RetroInputPoll) {
+    core().input_poll = Some(cb);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(cb: RetroInputState) {
+    core().input_state = Some(cb);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {
+    core().clear_frame();
+}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    let mut core = core();
+    core.machine = None;
+    core.audio.clear();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    if let Some(info) = info.as_mut() {
+        *info = RetroSystemInfo {
+            library_name: c"emerald".as_ptr(),
+            library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+            valid_extensions: c"gdi|elf".as_ptr(),
+            need_fullpath: true, // a gdi's tracks are loaded relative to it
+            block_extract: false,
+        };
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    if let Some(info) = info.as_mut() {
+        *info = RetroSystemAvInfo {
+            geometry: RetroGameGeometry {
+                base_width: DEFAULT_WIDTH,
+                base_height: DEFAULT_HEIGHT,
+                max_width: 640,
+                max_height: 576,
+                aspect_ratio: 4.0 / 3.0,
+            },
+            timing: RetroSystemTiming {
+                fps: 59.94,
+                sample_rate: AUDIO_SAMPLE_RATE as f64,
+            },
+        };
+    }
+}
+
+// only a standard controller in port 0 is supported
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    let mut core = core();
+    let Some(config) = core.machine.as_ref().map(|m| m.config.clone()) else {
+        return;
+    };
+
+    match Machine::from_config(&config) {
+        Ok(machine) => core.machine = Some(machine),
+        Err(errors) => {
+            for error in errors {
+                eprintln!("emerald: reset failed: {}", error);
+            }
+        }
+    }
+
+    core.pressed = [false; BUTTON_MAP.len()];
+    core.clear_frame();
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let mut core = core();
+    core.poll_input();
+
+    let Some(machine) = &mut core.machine else {
+        return;
+    };
+
+    let events = machine.run_frame();
+    let mut audio = std::mem::take(&mut core.audio);
+    for event in events {
+        match event {
+            MachineEvent::BlitFramebuffer(rgba, width, height) => {
+                core.frame = rgba
+                    .chunks_exact(4)
+                    .map(|p| (p[0] as u32) << 16 | (p[1] as u32) << 8 | p[2] as u32)
+                    .collect();
+                core.frame_width = width;
+                core.frame_height = height;
+            }
+            // fixme: the hw rasterizer needs a window, so ta renders can't be presented yet.
+            // tell the guest we're done so it doesn't stall waiting on the end-of-render interrupts
+            MachineEvent::FrameReady(_) => {
+                if let Some(machine) = &mut core.machine {
+                    machine.queue_input(EmulatorFrontendRequest::RenderingDone);
+                }
+            }
+            MachineEvent::Error(error) => eprintln!("emerald: halted, {}", error),
+            MachineEvent::Exited(status) => eprintln!("emerald: exited with status {}", status),
+            MachineEvent::VBlank | MachineEvent::Halted | MachineEvent::DebugStop(_) => {}
+        }
+    }
+
+    if let Some(machine) = &mut core.machine {
+        machine.drain_audio(&mut audio);
+    }
+
+    if let Some(video_refresh) = core.video_refresh {
+        unsafe {
+            video_refresh(
+                core.frame.as_ptr() as *const c_void,
+                core.frame_width,
+                core.frame_height,
+                core.frame_width as usize * 4,
+            )
+        };
+    }
+
+    if let Some(audio_sample_batch) = core.audio_sample_batch {
+        let mut samples = &audio[..];
+        while !samples.is_empty() {
+            let written = unsafe { audio_sample_batch(samples.as_ptr(), samples.len() / 2) };
+            if written == 0 {
+                break;
+            }
+
+            samples = &samples[(written * 2).min(samples.len())..];
+        }
+    }
+
+    audio.clear();
+    core.audio = audio;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    // a bound rather than a measurement, rewind, netplay and runahead need the size to never change
+    core()
+        .machine
+        .as_ref()
+        .map_or(0, |machine| 8 + machine.max_save_state_size())
+}
+
+// the state is length prefixed, libretro hands back the whole (padded) buffer
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let core = core();
+    let Some(machine) = &core.machine else {
+        return false;
+    };
+
+    let state = match machine.save_state() {
+        Ok(state) => state,
+        Err(e) => {
+            eprintln!("emerald: couldn't save state: {}", e);
+            return false;
+        }
+    };
+
+    if data.is_null() {
+        return false;
+    }
+
+    if 8 + state.len() > size {
+        eprintln!(
+            "emerald: save state is {} bytes, more than the {} asked for",
+            state.len(),
+            size
+        );
+        return false;
+    }
+
+    let out = slice::from_raw_parts_mut(data as *mut u8, size);
+    out[..8].copy_from_slice(&(state.len() as u64).to_le_bytes());
+    out[8..8 + state.len()].copy_from_slice(&state);
+    out[8 + state.len()..].fill(0);
+
+    true
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    let mut core = core();
+    let Some(machine) = &mut core.machine else {
+        return false;
+    };
+
+    if data.is_null() || size < 8 {
+        return false;
+    }
+
+    let data = slice::from_raw_parts(data as *const u8, size);
+    let len = u64::from_le_bytes(data[..8].try_into().unwrap()) as usize;
+    if len > size - 8 {
+        return false;
+    }
+
+    match machine.load_state(&data[8..8 + len]) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("emerald: couldn't load state: {}", e);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+// the bios and flash are expected at <system directory>/dc/dc_boot.bin and dc_flash.bin
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const RetroGameInfo) -> bool {
+    let mut core = core();
+
+    let Some(game) = game.as_ref() else {
+        return false;
+    };
+
+    if game.path.is_null() {
+        return false;
+    }
+
+    let Ok(path) = CStr::from_ptr(game.path).to_str().map(PathBuf::from) else {
+        return false;
+    };
+
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    let pixel_format_ok = core.environment.map_or(false, |environment| {
+        environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut format as *mut c_uint as *mut c_void,
+        )
+    });
+
+    if !pixel_format_ok {
+        eprintln!("emerald: frontend doesn't support xrgb8888");
+        return false;
+    }
+
+    let mut config = EmulatorConfig::default();
+    if let Some(system_dir) = core.system_directory() {
+        config.bios_path = system_dir.join("dc").join("dc_boot.bin");
+        config.flash_path = system_dir.join("dc").join("dc_flash.bin");
+    }
+
+    let is_elf = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("elf"))
+        .unwrap_or(false);
+
+    if is_elf {
+        config.elf_path = Some(path);
+    } else {
+        config.disc_path = Some(path);
+    }
+
+    match Machine::from_config(&config) {
+        Ok(machine) => {
+            core.machine = Some(machine);
+            core.pressed = [false; BUTTON_MAP.len()];
+            core.clear_frame();
+            true
+        }
+        Err(errors) => {
+            for error in errors {
+                eprintln!("emerald: {}", error);
+            }
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    let mut core = core();
+    core.machine = None;
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    match (&mut core().machine, id) {
+        (Some(machine), RETRO_MEMORY_SYSTEM_RAM) => {
+            machine.bus.system_ram.as_mut_ptr() as *mut c_void
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    match (&core().machine, id) {
+        (Some(machine), RETRO_MEMORY_SYSTEM_RAM) => machine.bus.system_ram.len(),
+        _ => 0,
+    }
+}
@@ -0,0 +1,212 @@
+// arm7 vectors for the aica's sound cpu, the same layout as the sh4 ones:
+//
+//     {
+//       "name": "adds r0,r1,r2,lsl r3 (ne, shift by 32)",
+//       "initial": { "cpsr": 19, "r": [...], "banked": { "r13_usr": 4096 }, "ram": [[0, 17], ...] },
+//       "final": { "cpsr": 1610612755, "r": [...] },
+//       "cycles": [[8, 0, "fetch", 32]]
+//     }
+//
+// r is r0-r15 as the mode in cpsr sees them, spsr is the current mode's, and banked has any of the copies the
+// mode doesn't see by name: r8_usr-r14_usr, r8_fiq-r14_fiq, r13_irq, r14_irq, r13_svc, r14_svc, spsr_fiq,
+// spsr_irq and spsr_svc. r15 is the address of the instruction plus 8, the way the core keeps it, so the
+// final r15 is the next instruction's plus 8. ram, cycles and missing registers work as in sh4.rs.
+//
+// the pipeline is filled from ram before the instruction runs and those fetches aren't part of the cycles,
+// the first cycle is the prefetch of the instruction 8 bytes on. memory is flat, see test_bus.rs in the core.
+
+use std::{collections::BTreeMap, path::Path};
+
+use emerald_core::hw::{
+    holly::g2::aica::{arm::Cpu, arm_bus::ArmBus, Aica},
+    sh4::test_bus::TestBus,
+};
+use serde::Deserialize;
+
+use super::{catch, compare_bus, paths, Cycle, Summary};
+
+const BANKED: [&str; 21] = [
+    "r8_usr", "r9_usr", "r10_usr", "r11_usr", "r12_usr", "r13_usr", "r14_usr", "r8_fiq", "r9_fiq",
+    "r10_fiq", "r11_fiq", "r12_fiq", "r13_fiq", "r14_fiq", "r13_irq", "r14_irq", "r13_svc",
+    "r14_svc", "spsr_fiq", "spsr_irq", "spsr_svc",
+];
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct State {
+    r: Option<[u32; 16]>,
+    cpsr: Option<u32>,
+    spsr: Option<u32>,
+    banked: BTreeMap<String, u32>,
+    ram: Vec<(u32, u8)>,
+}
+
+#[derive(Deserialize)]
+struct Vector {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Option<Vec<Cycle>>,
+}
+
+pub fn run(pattern: &str) -> Result<(), String> {
+    let mut aica = Aica::new();
+    let mut summary = Summary::new();
+
+    for path in paths(pattern)? {
+        let vectors = load(&path)?;
+        let mut failures = Vec::new();
+
+        for vector in &vectors {
+            let differences = execute(&mut aica, vector);
+            if !differences.is_empty() {
+                failures.push((vector.name.clone(), differences));
+            }
+        }
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        summary.add(&name, vectors.len(), &failures);
+    }
+
+    summary.finish("arm7")
+}
+
+fn load(path: &Path) -> Result<Vec<Vector>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn banked_register<'a>(cpu: &'a mut Cpu, name: &str) -> Option<&'a mut u32> {
+    let registers = &mut cpu.registers;
+    let register = match name {
+        "r8_usr" => &mut registers.r8,
+        "r9_usr" => &mut registers.r9,
+        "r10_usr" => &mut registers.r10,
+        "r11_usr" => &mut registers.r11,
+        "r12_usr" => &mut registers.r12,
+        "r13_usr" => &mut registers.r13,
+        "r14_usr" => &mut registers.r14,
+        "r8_fiq" => &mut registers.r8_fiq,
+        "r9_fiq" => &mut registers.r9_fiq,
+        "r10_fiq" => &mut registers.r10_fiq,
+        "r11_fiq" => &mut registers.r11_fiq,
+        "r12_fiq" => &mut registers.r12_fiq,
+        "r13_fiq" => &mut registers.r13_fiq,
+        "r14_fiq" => &mut registers.r14_fiq,
+        "r13_irq" => &mut registers.r13_irq,
+        "r14_irq" => &mut registers.r14_irq,
+        "r13_svc" => &mut registers.r13_svc,
+        "r14_svc" => &mut registers.r14_svc,
+        "spsr_fiq" => &mut registers.spsr_fiq,
+        "spsr_irq" => &mut registers.spsr_irq,
+        "spsr_svc" => &mut registers.spsr_svc,
+        _ => return None,
+    };
+
+    Some(register)
+}
+
+// runs one vector and returns what didn't match
+fn execute(aica: &mut Aica, vector: &Vector) -> Vec<String> {
+    let initial = &vector.initial;
+
+    let unknown: Vec<String> = [&initial.banked, &vector.expected.banked]
+        .into_iter()
+        .flat_map(|banked| banked.keys())
+        .filter(|name| !BANKED.contains(&name.as_str()))
+        .map(|name| format!("unknown banked register {}", name))
+        .collect();
+
+    if !unknown.is_empty() {
+        return unknown;
+    }
+
+    // everything starts at zero rather than the core's reset values. the banks go in before cpsr picks
+    // which of them r means
+    let mut cpu = Cpu::new();
+    for name in BANKED {
+        *banked_register(&mut cpu, name).unwrap() = initial.banked.get(name).copied().unwrap_or(0);
+    }
+
+    cpu.registers.cpsr = initial.cpsr.unwrap_or(0);
+    if let Some(spsr) = initial.spsr {
+        cpu.set_spsr(spsr);
+    }
+
+    let r = initial.r.unwrap_or_default();
+    for (i, value) in r.iter().enumerate().take(15) {
+        cpu.set_register_by_index(i, *value);
+    }
+
+    let mut test_bus = TestBus::new();
+    test_bus.memory.extend(initial.ram.iter().copied());
+    aica.test_bus = Some(test_bus);
+
+    let result = catch(|| {
+        let mut bus = ArmBus { aica: &mut *aica };
+        cpu.running = true;
+        cpu.set_pc(r[15].wrapping_sub(8), &bus);
+        bus.aica.test_bus.as_mut().unwrap().cycles.get_mut().clear();
+
+        // the sh4 clocks the arm 8 times per instruction, the last one runs it
+        loop {
+            cpu.step(&mut bus);
+            if cpu.at_instruction_boundary() {
+                break;
+            }
+        }
+    });
+
+    let test_bus = aica.test_bus.take().unwrap();
+    if let Err(message) = result {
+        return vec![format!("panicked: {}", message)];
+    }
+
+    let mut differences = Vec::new();
+    compare_registers(&mut cpu, &vector.expected, &mut differences);
+    compare_bus(
+        test_bus,
+        &vector.expected.ram,
+        vector.cycles.as_deref(),
+        &mut differences,
+    );
+
+    differences
+}
+
+fn compare_registers(cpu: &mut Cpu, expected: &State, differences: &mut Vec<String>) {
+    let mut checks = vec![
+        ("cpsr".to_owned(), expected.cpsr, cpu.registers.cpsr),
+        ("spsr".to_owned(), expected.spsr, cpu.get_spsr()),
+    ];
+
+    for i in 0..16 {
+        checks.push((
+            format!("r{}", i),
+            expected.r.map(|r| r[i]),
+            cpu.get_register_by_index(i),
+        ));
+    }
+
+    for (name, value) in &expected.banked {
+        checks.push((
+            name.clone(),
+            Some(*value),
+            *banked_register(cpu, name).unwrap(),
+        ));
+    }
+
+    for (name, expected, actual) in checks {
+        if let Some(expected) = expected {
+            if expected != actual {
+                differences.push(format!(
+                    "{}: expected {:08x}, got {:08x}",
+                    name, expected, actual
+                ));
+            }
+        }
+    }
+}
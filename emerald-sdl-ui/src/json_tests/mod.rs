@@ -0,0 +1,225 @@
+// single instruction conformance tests, run instead of the emulator when one of these is on the command line:
+//
+//     --sh4-tests <glob>    json vectors for the sh4 interpreter, see sh4.rs
+//     --arm7-tests <glob>   the same for the aica's arm7, see arm7.rs
+//
+// every file the glob matches is one opcode's worth of vectors. each file gets a pass count and its first few
+// failures, and the run fails if any vector did.
+//
+// vectors/ has a few hand written files that `cargo test --features json_tests` runs, enough to keep the
+// harnesses honest. the full suites are too big to check in.
+
+mod arm7;
+mod sh4;
+
+use emerald_core::hw::sh4::test_bus::{BusCycle, BusCycleKind, TestBus};
+use serde::Deserialize;
+
+const FAILURES_SHOWN: usize = 3; // per file, the rest are usually the same bug
+
+// None when no tests were asked for and the emulator should start as usual
+pub fn run(args: &[String]) -> Option<Result<(), String>> {
+    let (run, pattern): (fn(&str) -> Result<(), String>, _) = match args {
+        [flag, pattern] if flag == "--sh4-tests" => (sh4::run, pattern),
+        [flag, pattern] if flag == "--arm7-tests" => (arm7::run, pattern),
+        _ => return None,
+    };
+
+    // catch reports panics with the vector that caused them, the default hook would only add noise
+    std::panic::set_hook(Box::new(|_| {}));
+    Some(run(pattern))
+}
+
+// the files a glob matches, sorted so runs are comparable
+fn paths(pattern: &str) -> Result<Vec<std::path::PathBuf>, String> {
+    let mut paths = glob::glob(pattern)
+        .map_err(|e| format!("bad pattern {}: {}", pattern, e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    if paths.is_empty() {
+        return Err(format!("no test files match {}", pattern));
+    }
+
+    paths.sort();
+    Ok(paths)
+}
+
+// a panicking handler fails its vector instead of the whole run
+fn catch<F: FnOnce()>(f: F) -> Result<(), String> {
+    std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).map_err(|payload| {
+        payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_owned())
+    })
+}
+
+struct Summary {
+    files: usize,
+    failed_files: usize,
+    passed: usize,
+    total: usize,
+}
+
+impl Summary {
+    fn new() -> Self {
+        Self {
+            files: 0,
+            failed_files: 0,
+            passed: 0,
+            total: 0,
+        }
+    }
+
+    // prints the file's result, failures are (vector name, what differed)
+    fn add(&mut self, name: &str, total: usize, failures: &[(String, Vec<String>)]) {
+        self.files += 1;
+        self.total += total;
+        self.passed += total - failures.len();
+
+        if failures.is_empty() {
+            println!("{}: {}/{}", name, total, total);
+            return;
+        }
+
+        self.failed_files += 1;
+        println!("{}: {}/{}, FAILED", name, total - failures.len(), total);
+
+        for (vector, differences) in failures.iter().take(FAILURES_SHOWN) {
+            println!("  {}", vector);
+            for difference in differences {
+                println!("    {}", difference);
+            }
+        }
+
+        if failures.len() > FAILURES_SHOWN {
+            println!("  and {} more", failures.len() - FAILURES_SHOWN);
+        }
+    }
+
+    fn finish(self, cpu: &str) -> Result<(), String> {
+        println!(
+            "{}: {} of {} opcodes pass, {}/{} vectors",
+            cpu,
+            self.files - self.failed_files,
+            self.files,
+            self.passed,
+            self.total
+        );
+
+        if self.passed == self.total {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} {} vectors failed",
+                self.total - self.passed,
+                cpu
+            ))
+        }
+    }
+}
+
+#[derive(Copy, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum CycleKind {
+    Fetch,
+    Read,
+    Write,
+}
+
+// [address, value, "fetch" | "read" | "write", size in bits]
+type Cycle = (u32, u64, CycleKind, u8);
+
+// the bytes the vector lists and, when it has them, the cycles
+fn compare_bus(
+    test_bus: TestBus,
+    ram: &[(u32, u8)],
+    cycles: Option<&[Cycle]>,
+    differences: &mut Vec<String>,
+) {
+    for &(addr, expected) in ram {
+        let actual = test_bus.memory.get(&addr).copied().unwrap_or(0);
+        if actual != expected {
+            differences.push(format!(
+                "ram {:08x}: expected {:02x}, got {:02x}",
+                addr, expected, actual
+            ));
+        }
+    }
+
+    if let Some(expected) = cycles {
+        compare_cycles(expected, &test_bus.cycles.into_inner(), differences);
+    }
+}
+
+fn compare_cycles(expected: &[Cycle], actual: &[BusCycle], differences: &mut Vec<String>) {
+    let describe = |cycle: BusCycle| {
+        let kind = match cycle.kind {
+            BusCycleKind::Fetch => "fetch",
+            BusCycleKind::Read => "read",
+            BusCycleKind::Write => "write",
+        };
+
+        format!(
+            "{} {} bits, {:08x} = {:x}",
+            kind, cycle.size, cycle.addr, cycle.value
+        )
+    };
+
+    for i in 0..expected.len().max(actual.len()) {
+        let expected = expected.get(i).map(|&(addr, value, kind, size)| {
+            let kind = match kind {
+                CycleKind::Fetch => BusCycleKind::Fetch,
+                CycleKind::Read => BusCycleKind::Read,
+                CycleKind::Write => BusCycleKind::Write,
+            };
+
+            BusCycle {
+                addr,
+                value,
+                size,
+                kind,
+            }
+        });
+
+        let actual = actual.get(i).copied();
+        if expected == actual {
+            continue;
+        }
+
+        let [expected, actual] = [expected, actual].map(|cycle| match cycle {
+            Some(cycle) => describe(cycle),
+            None => "nothing".to_owned(),
+        });
+
+        // everything after the first difference is usually a consequence of it
+        differences.push(format!(
+            "cycle {}: expected {}, got {}",
+            i, expected, actual
+        ));
+        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn vectors(cpu: &str) -> String {
+        format!(
+            "{}/src/json_tests/vectors/{}/*.json",
+            env!("CARGO_MANIFEST_DIR"),
+            cpu
+        )
+    }
+
+    #[test]
+    fn sh4() {
+        super::sh4::run(&vectors("sh4")).unwrap();
+    }
+
+    #[test]
+    fn arm7() {
+        super::arm7::run(&vectors("arm7")).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+// sh4 vectors, in the style of the SingleStepTests suites. a file is a json array of
+//
+//     {
+//       "name": "fadd fr2,fr1 #17",
+//       "initial": { "pc": 2348875776, "fpscr": 262145, "fr": [...], "ram": [[2348875776, 32], [2348875777, 241]] },
+//       "final": { "pc": 2348875778, "fr": [...] },
+//       "cycles": [[2348875776, 61728, "fetch", 16]]
+//     }
+//
+// the states have pc, sr, gbr, vbr, ssr, spc, sgr, dbr, pr, mach, macl, fpscr and fpul as numbers, r (r0-r15
+// as the bank selected by sr.rb sees them), r_bank (the other bank's r0-r7), fr (fr0-fr15 of the bank
+// selected by fpscr.fr, as raw bits) and xf (the other bank), plus ram as [address, byte] pairs. anything
+// missing from the initial state is zero, anything missing from the final state isn't checked, and only the
+// bytes listed in the final ram are compared.
+//
+// cycles is every access in order as [address, value, "fetch" | "read" | "write", size in bits], and is
+// only compared when it's there. a branch runs its delay slot as part of the same step, so its vector has
+// the slot's fetch and accesses too. the whole address space is flat memory, see test_bus.rs in the core.
+
+use std::path::Path;
+
+use emerald_core::{
+    context::Context,
+    hw::sh4::{
+        bus::CpuBus,
+        cpu::{Cpu, CpuRegisters, CpuState, FpuBank},
+        test_bus::TestBus,
+    },
+    scheduler::Scheduler,
+    trace::Tracer,
+};
+use serde::Deserialize;
+
+use super::{catch, compare_bus, paths, Cycle, Summary};
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct State {
+    pc: Option<u32>,
+    r: Option<[u32; 16]>,
+    r_bank: Option<[u32; 8]>,
+    fr: Option<[u32; 16]>,
+    xf: Option<[u32; 16]>,
+    sr: Option<u32>,
+    gbr: Option<u32>,
+    vbr: Option<u32>,
+    ssr: Option<u32>,
+    spc: Option<u32>,
+    sgr: Option<u32>,
+    dbr: Option<u32>,
+    pr: Option<u32>,
+    mach: Option<u32>,
+    macl: Option<u32>,
+    fpscr: Option<u32>,
+    fpul: Option<u32>,
+    ram: Vec<(u32, u8)>,
+}
+
+#[derive(Deserialize)]
+struct Vector {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Option<Vec<Cycle>>,
+}
+
+pub fn run(pattern: &str) -> Result<(), String> {
+    let mut cpu = Cpu::new();
+    let mut bus = CpuBus::new();
+    let mut context = Context {
+        scheduler: Scheduler::new(),
+        cyc: 0,
+        tracer: Tracer::new(),
+        error: None,
+    };
+
+    let mut summary = Summary::new();
+
+    for path in paths(pattern)? {
+        let vectors = load(&path)?;
+        let mut failures = Vec::new();
+
+        for vector in &vectors {
+            let differences = execute(&mut cpu, &mut bus, &mut context, vector);
+            if !differences.is_empty() {
+                failures.push((vector.name.clone(), differences));
+            }
+        }
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        summary.add(&name, vectors.len(), &failures);
+    }
+
+    summary.finish("sh4")
+}
+
+fn load(path: &Path) -> Result<Vec<Vector>, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("couldn't read {}: {}", path.display(), e))?;
+
+    serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// runs one vector and returns what didn't match
+fn execute(cpu: &mut Cpu, bus: &mut CpuBus, context: &mut Context, vector: &Vector) -> Vec<String> {
+    let initial = &vector.initial;
+
+    // raw fields rather than set_sr and set_fpscr, the state already says which banks are current
+    cpu.registers = CpuRegisters {
+        current_pc: initial.pc.unwrap_or(0),
+        r: initial.r.unwrap_or_default(),
+        r_bank: initial.r_bank.unwrap_or_default(),
+        sr: initial.sr.unwrap_or(0),
+        gbr: initial.gbr.unwrap_or(0),
+        vbr: initial.vbr.unwrap_or(0),
+        dbr: initial.dbr.unwrap_or(0),
+        ssr: initial.ssr.unwrap_or(0),
+        spc: initial.spc.unwrap_or(0),
+        sgr: initial.sgr.unwrap_or(0),
+        pr: initial.pr.unwrap_or(0),
+        macl: initial.macl.unwrap_or(0),
+        mach: initial.mach.unwrap_or(0),
+        fpul: initial.fpul.unwrap_or(0),
+        fpscr: initial.fpscr.unwrap_or(0),
+        fpu_banks: [
+            FpuBank {
+                fr: initial.fr.unwrap_or_default().map(f32::from_bits),
+            },
+            FpuBank {
+                fr: initial.xf.unwrap_or_default().map(f32::from_bits),
+            },
+        ],
+    };
+    cpu.state = CpuState::Running;
+
+    let mut test_bus = TestBus::new();
+    test_bus.memory.extend(initial.ram.iter().copied());
+    bus.test_bus = Some(test_bus);
+    context.error = None;
+
+    if let Err(message) = catch(|| cpu.step(bus, context, 0)) {
+        return vec![format!("panicked: {}", message)];
+    }
+
+    let mut differences = Vec::new();
+    if let Some(error) = context.error.take() {
+        differences.push(format!("faulted: {}", error));
+    }
+
+    compare_registers(cpu, &vector.expected, &mut differences);
+
+    compare_bus(
+        bus.test_bus.take().unwrap(),
+        &vector.expected.ram,
+        vector.cycles.as_deref(),
+        &mut differences,
+    );
+
+    differences
+}
+
+fn compare_registers(cpu: &Cpu, expected: &State, differences: &mut Vec<String>) {
+    let registers = &cpu.registers;
+
+    let mut checks = vec![
+        ("pc".to_owned(), expected.pc, registers.current_pc),
+        ("sr".to_owned(), expected.sr, cpu.get_sr()),
+        ("gbr".to_owned(), expected.gbr, registers.gbr),
+        ("vbr".to_owned(), expected.vbr, registers.vbr),
+        ("ssr".to_owned(), expected.ssr, registers.ssr),
+        ("spc".to_owned(), expected.spc, registers.spc),
+        ("sgr".to_owned(), expected.sgr, registers.sgr),
+        ("dbr".to_owned(), expected.dbr, registers.dbr),
+        ("pr".to_owned(), expected.pr, registers.pr),
+        ("mach".to_owned(), expected.mach, registers.mach),
+        ("macl".to_owned(), expected.macl, registers.macl),
+        ("fpscr".to_owned(), expected.fpscr, cpu.get_fpscr()),
+        ("fpul".to_owned(), expected.fpul, registers.fpul),
+    ];
+
+    for i in 0..16 {
+        checks.push((format!("r{}", i), expected.r.map(|r| r[i]), registers.r[i]));
+    }
+
+    for i in 0..8 {
+        checks.push((
+            format!("r{}_bank", i),
+            expected.r_bank.map(|r| r[i]),
+            registers.r_bank[i],
+        ));
+    }
+
+    for (name, expected, actual) in checks {
+        if let Some(expected) = expected {
+            if expected != actual {
+                differences.push(format!(
+                    "{}: expected {:08x}, got {:08x}",
+                    name, expected, actual
+                ));
+            }
+        }
+    }
+
+    // floats get their values alongside the bits, a wrong rounding mode is easier to spot that way
+    for (bank, expected, actual) in [
+        ("fr", expected.fr, registers.fpu_banks[0].get_fr()),
+        ("xf", expected.xf, registers.fpu_banks[1].get_fr()),
+    ] {
+        let Some(expected) = expected else {
+            continue;
+        };
+
+        for i in 0..16 {
+            let actual = actual[i].to_bits();
+            if expected[i] != actual {
+                differences.push(format!(
+                    "{}{}: expected {:08x} ({}), got {:08x} ({})",
+                    bank,
+                    i,
+                    expected[i],
+                    f32::from_bits(expected[i]),
+                    actual,
+                    f32::from_bits(actual)
+                ));
+            }
+        }
+    }
+}
@@ -27,10 +27,26 @@ use std::{
     sync::{mpsc, Arc, Mutex},
 };
 
-use emerald_core::emulator::Emulator;
+use emerald_core::{
+    config::{EmulatorConfig, USAGE},
+    emulator::Emulator,
+};
 use sdl2::{event::Event, keyboard::Keycode, libc::memcpy, pixels::PixelFormatEnum};
 
+#[cfg(feature = "json_tests")]
+mod json_tests;
+
 pub fn main() -> Result<(), String> {
+    #[cfg(feature = "json_tests")]
+    if let Some(result) = json_tests::run(&std::env::args().skip(1).collect::<Vec<_>>()) {
+        return result;
+    }
+
+    let config = EmulatorConfig::from_args(std::env::args().skip(1)).map_err(|e| {
+        println!("{}", USAGE);
+        e.to_string()
+    })?;
+
     let sdl_context = sdl2::init()?;
     let video_subsystem = sdl_context.video()?;
 
@@ -48,7 +64,19 @@ pub fn main() -> Result<(), String> {
     let (frame_ready_sender, frame_ready_receiver) = mpsc::channel();
     let (frontend_request_sender, frontend_request_receiver) = mpsc::channel();
 
-    Emulator::run_loop(emulator, frame_ready_sender, frontend_request_receiver);
+    Emulator::run_loop(
+        emulator,
+        config,
+        frame_ready_sender,
+        frontend_request_receiver,
+    )
+    .map_err(|errors| {
+        errors
+            .iter()
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>()
+            .join("\n")
+    })?;
 
     'running: loop {
         for event in event_pump.poll_iter() {
@@ -205,6 +233,10 @@ pub fn main() -> Result<(), String> {
                         .send(EmulatorFrontendRequest::RenderingDone)
                         .unwrap();
                 }
+                // the window stays up so the last frame can still be looked at
+                EmulatorFrontendResponse::Halted(error) => {
+                    eprintln!("emerald: halted, {}", error);
+                }
             }
         }
     }